@@ -0,0 +1,454 @@
+//! NEP-141 receipt token of a seed staked in the farming contract.
+//! Balances are the staked balances of the seed, pushed by the farming contract with `ft_sync`.
+//! Transfers are forwarded to the farming contract, which moves the staked seed
+//! and pushes the new balances back, so they show up here once the sync lands.
+//! A transfer fails when the farming contract does not move the seed.
+use std::convert::TryFrom;
+
+use near_contract_standards::fungible_token::metadata::{
+    FungibleTokenMetadata, FungibleTokenMetadataProvider,
+};
+use near_contract_standards::storage_management::{StorageBalance, StorageBalanceBounds};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::{ValidAccountId, U128, U64};
+use near_sdk::serde_json::json;
+use near_sdk::{
+    assert_one_yocto, env, ext_contract, near_bindgen, AccountId, Balance, Gas, PanicOnDefault,
+    Promise, PromiseOrValue, PromiseResult,
+};
+
+near_sdk::setup_alloc!();
+
+/// receipt_transfer moves the seed and claims rewards of every farm in the seed
+const GAS_FOR_RECEIPT_TRANSFER: Gas = 60_000_000_000_000;
+const GAS_FOR_RESOLVE_RECEIPT_TRANSFER: Gas = 5_000_000_000_000;
+const GAS_FOR_TRANSFER_CALL_CONTINUE: Gas = 20_000_000_000_000;
+const GAS_FOR_RECEIPT_REFUND: Gas = 60_000_000_000_000;
+const GAS_FOR_RESOLVE_TRANSFER: Gas = 10_000_000_000_000 + GAS_FOR_RECEIPT_REFUND;
+/// kept out of the receiver call of ft_transfer_call
+const GAS_FOR_FT_TRANSFER_CALL: Gas =
+    GAS_FOR_RECEIPT_TRANSFER + GAS_FOR_TRANSFER_CALL_CONTINUE + GAS_FOR_RESOLVE_TRANSFER;
+
+/// calls of the farming contract, generated with the gas and deposit arguments
+#[allow(clippy::too_many_arguments)]
+mod farming {
+    use super::*;
+
+    #[ext_contract(ext_farming)]
+    pub trait Farming {
+        fn receipt_transfer(
+            &mut self,
+            seed_id: String,
+            sender_id: AccountId,
+            receiver_id: AccountId,
+            amount: U128,
+            memo: Option<String>,
+        );
+
+        fn receipt_refund(
+            &mut self,
+            seed_id: String,
+            sender_id: AccountId,
+            receiver_id: AccountId,
+            amount: U128,
+            unused_amount: U128,
+        ) -> U128;
+    }
+}
+use farming::ext_farming;
+
+#[ext_contract(ext_fungible_token_receiver)]
+pub trait FungibleTokenReceiver {
+    fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128>;
+}
+
+/// callbacks of the receipt token, generated with the gas and deposit arguments
+#[allow(clippy::too_many_arguments)]
+mod resolver {
+    use super::*;
+
+    #[ext_contract(ext_self)]
+    pub trait ReceiptTokenResolver {
+        fn ft_resolve_receipt_transfer(
+            &mut self,
+            sender_id: AccountId,
+            receiver_id: AccountId,
+            amount: U128,
+            memo: Option<String>,
+        );
+
+        fn ft_transfer_call_continue(
+            &mut self,
+            sender_id: AccountId,
+            receiver_id: AccountId,
+            amount: U128,
+            memo: Option<String>,
+            msg: String,
+        ) -> PromiseOrValue<U128>;
+
+        fn ft_resolve_transfer(
+            &mut self,
+            sender_id: AccountId,
+            receiver_id: AccountId,
+            amount: U128,
+        ) -> PromiseOrValue<U128>;
+    }
+}
+use resolver::ext_self;
+
+#[near_bindgen]
+#[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
+pub struct Contract {
+    farming_id: AccountId,
+    seed_id: String,
+    metadata: FungibleTokenMetadata,
+    /// staked balance of the account with the nonce it was pushed with
+    accounts: LookupMap<AccountId, (Balance, u64)>,
+    total_supply: Balance,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new(farming_id: ValidAccountId, seed_id: String, metadata: FungibleTokenMetadata) -> Self {
+        assert!(!env::state_exists(), "Already initialized");
+        metadata.assert_valid();
+        Self {
+            farming_id: farming_id.into(),
+            seed_id,
+            metadata,
+            accounts: LookupMap::new(b"a".to_vec()),
+            total_supply: 0,
+        }
+    }
+
+    /// set staked balance of the account, balances pushed out of order are ignored.
+    pub fn ft_sync(&mut self, account_id: ValidAccountId, balance: U128, nonce: U64) {
+        assert_eq!(env::predecessor_account_id(), self.farming_id, "Only the farming contract can sync");
+        let account_id: AccountId = account_id.into();
+        let (prev_balance, prev_nonce) = self.accounts.get(&account_id).unwrap_or((0, 0));
+        if nonce.0 <= prev_nonce {
+            return;
+        }
+        self.accounts.insert(&account_id, &(balance.0, nonce.0));
+        if balance.0 > prev_balance {
+            self.total_supply += balance.0 - prev_balance;
+            log_event("ft_mint", &account_id, balance.0 - prev_balance);
+        } else if balance.0 < prev_balance {
+            self.total_supply -= prev_balance - balance.0;
+            log_event("ft_burn", &account_id, prev_balance - balance.0);
+        }
+    }
+
+    #[payable]
+    pub fn ft_transfer(&mut self, receiver_id: ValidAccountId, amount: U128, memo: Option<String>) -> Promise {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        let receiver_id: AccountId = receiver_id.into();
+        ext_farming::receipt_transfer(
+            self.seed_id.clone(),
+            sender_id.clone(),
+            receiver_id.clone(),
+            amount,
+            memo.clone(),
+            &self.farming_id,
+            0,
+            GAS_FOR_RECEIPT_TRANSFER,
+        )
+        .then(ext_self::ft_resolve_receipt_transfer(
+            sender_id,
+            receiver_id,
+            amount,
+            memo,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_RECEIPT_TRANSFER,
+        ))
+    }
+
+    /// fail the transfer if the farming contract did not move the seed.
+    #[private]
+    pub fn ft_resolve_receipt_transfer(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+    ) {
+        assert_receipt_transferred();
+        log_transfer_event(&sender_id, &receiver_id, amount.0, memo);
+    }
+
+    #[payable]
+    pub fn ft_transfer_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        assert_one_yocto();
+        assert!(env::prepaid_gas() > GAS_FOR_FT_TRANSFER_CALL, "More gas is required");
+        let sender_id = env::predecessor_account_id();
+        let receiver_id: AccountId = receiver_id.into();
+        ext_farming::receipt_transfer(
+            self.seed_id.clone(),
+            sender_id.clone(),
+            receiver_id.clone(),
+            amount,
+            memo.clone(),
+            &self.farming_id,
+            0,
+            GAS_FOR_RECEIPT_TRANSFER,
+        )
+        .then(ext_self::ft_transfer_call_continue(
+            sender_id,
+            receiver_id,
+            amount,
+            memo,
+            msg,
+            &env::current_account_id(),
+            0,
+            env::prepaid_gas() - env::used_gas() - GAS_FOR_RECEIPT_TRANSFER,
+        ))
+        .into()
+    }
+
+    /// call the receiver once the farming contract moved the seed.
+    #[private]
+    pub fn ft_transfer_call_continue(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        assert_receipt_transferred();
+        log_transfer_event(&sender_id, &receiver_id, amount.0, memo);
+        ext_fungible_token_receiver::ft_on_transfer(
+            sender_id.clone(),
+            amount,
+            msg,
+            &receiver_id,
+            0,
+            env::prepaid_gas() - env::used_gas() - GAS_FOR_TRANSFER_CALL_CONTINUE - GAS_FOR_RESOLVE_TRANSFER,
+        )
+        .then(ext_self::ft_resolve_transfer(
+            sender_id,
+            receiver_id,
+            amount,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+        .into()
+    }
+
+    /// hand the unused amount to the farming contract to be moved back,
+    /// returns the amount used by the receiver.
+    #[private]
+    pub fn ft_resolve_transfer(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> PromiseOrValue<U128> {
+        let unused_amount = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => {
+                if let Ok(unused_amount) = near_sdk::serde_json::from_slice::<U128>(&value) {
+                    std::cmp::min(amount.0, unused_amount.0)
+                } else {
+                    amount.0
+                }
+            }
+            PromiseResult::Failed => amount.0,
+        };
+        if unused_amount == 0 {
+            return PromiseOrValue::Value(amount);
+        }
+        ext_farming::receipt_refund(
+            self.seed_id.clone(),
+            sender_id,
+            receiver_id,
+            amount,
+            U128(unused_amount),
+            &self.farming_id,
+            0,
+            GAS_FOR_RECEIPT_REFUND,
+        )
+        .into()
+    }
+
+    pub fn ft_total_supply(&self) -> U128 {
+        self.total_supply.into()
+    }
+
+    pub fn ft_balance_of(&self, account_id: ValidAccountId) -> U128 {
+        self.accounts.get(account_id.as_ref()).map(|(balance, _)| balance).unwrap_or(0).into()
+    }
+
+    pub fn get_seed_id(&self) -> String {
+        self.seed_id.clone()
+    }
+
+    /// balances are registered on the farming contract, nothing to deposit here.
+    #[payable]
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<ValidAccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let _ = registration_only;
+        let amount = env::attached_deposit();
+        if amount > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(amount);
+        }
+        self.storage_balance_of(account_id.unwrap_or_else(|| {
+            ValidAccountId::try_from(env::predecessor_account_id()).unwrap()
+        }))
+    }
+
+    pub fn storage_balance_of(&self, account_id: ValidAccountId) -> StorageBalance {
+        let _ = account_id;
+        StorageBalance { total: U128(0), available: U128(0) }
+    }
+
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds { min: U128(0), max: Some(U128(0)) }
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenMetadataProvider for Contract {
+    fn ft_metadata(&self) -> FungibleTokenMetadata {
+        self.metadata.clone()
+    }
+}
+
+fn assert_receipt_transferred() {
+    assert_eq!(env::promise_results_count(), 1, "Expected 1 promise result");
+    assert!(
+        matches!(env::promise_result(0), PromiseResult::Successful(_)),
+        "Receipt transfer failed"
+    );
+}
+
+fn log_event(event: &str, owner_id: &str, amount: Balance) {
+    let event = json!({
+        "standard": "nep141",
+        "version": "1.0.0",
+        "event": event,
+        "data": [{"owner_id": owner_id, "amount": amount.to_string()}],
+    });
+    env::log(format!("EVENT_JSON:{}", event).as_bytes());
+}
+
+fn log_transfer_event(old_owner_id: &str, new_owner_id: &str, amount: Balance, memo: Option<String>) {
+    let mut data = json!({
+        "old_owner_id": old_owner_id,
+        "new_owner_id": new_owner_id,
+        "amount": amount.to_string(),
+    });
+    if let Some(memo) = memo {
+        data["memo"] = json!(memo);
+    }
+    let event = json!({
+        "standard": "nep141",
+        "version": "1.0.0",
+        "event": "ft_transfer",
+        "data": [data],
+    });
+    env::log(format!("EVENT_JSON:{}", event).as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, MockedBlockchain};
+
+    use super::*;
+
+    fn setup_contract() -> (VMContextBuilder, Contract) {
+        let context = VMContextBuilder::new();
+        testing_env!(context.build());
+        let contract = Contract::new(
+            accounts(0),
+            accounts(1).into(),
+            FungibleTokenMetadata {
+                spec: String::from("ft-1.0.0"),
+                name: String::from("Staked bob"),
+                symbol: String::from("stBOB"),
+                icon: None,
+                reference: None,
+                reference_hash: None,
+                decimals: 18,
+            },
+        );
+        (context, contract)
+    }
+
+    #[test]
+    fn test_sync() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.ft_sync(accounts(2), U128(10), U64(1));
+        contract.ft_sync(accounts(3), U128(5), U64(2));
+        contract.ft_sync(accounts(2), U128(4), U64(3));
+        assert_eq!(contract.ft_balance_of(accounts(2)), U128(4));
+        assert_eq!(contract.ft_balance_of(accounts(3)), U128(5));
+        assert_eq!(contract.ft_total_supply(), U128(9));
+
+        // outdated balance is ignored
+        contract.ft_sync(accounts(2), U128(10), U64(1));
+        assert_eq!(contract.ft_balance_of(accounts(2)), U128(4));
+        assert_eq!(contract.ft_total_supply(), U128(9));
+        assert_eq!(contract.ft_metadata().symbol, String::from("stBOB"));
+    }
+
+    #[test]
+    fn test_resolve_receipt_transfer() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(
+            context.current_account_id(accounts(4)).predecessor_account_id(accounts(4)).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.ft_resolve_receipt_transfer(accounts(2).into(), accounts(3).into(), U128(10), None);
+        let logs = near_sdk::test_utils::get_logs();
+        assert_eq!(logs.len(), 1);
+        assert!(logs[0].contains("\"event\":\"ft_transfer\""));
+        assert!(logs[0].contains("\"new_owner_id\":\"danny\""));
+    }
+
+    #[test]
+    #[should_panic(expected = "Receipt transfer failed")]
+    fn test_resolve_receipt_transfer_failed() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(
+            context.current_account_id(accounts(4)).predecessor_account_id(accounts(4)).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.ft_resolve_receipt_transfer(accounts(2).into(), accounts(3).into(), U128(10), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the farming contract can sync")]
+    fn test_sync_not_farming() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.ft_sync(accounts(2), U128(10), U64(1));
+    }
+}
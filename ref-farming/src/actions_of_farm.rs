@@ -7,6 +7,7 @@ use crate::errors::*;
 use crate::*;
 use std::collections::HashMap;
 use crate::farm_seed::{FarmSeedMetadata, NFTTokenId};
+use crate::event::{NearEvent, CreateFarmData, ClearFarmData};
 
 
 #[near_bindgen]
@@ -61,13 +62,6 @@ impl Contract {
         let mut farm_seed: VersionedFarmSeed;
         if let Some(fs) = self.get_seed_wrapped(&terms.seed_id) {
             farm_seed = fs;
-            env::log(
-                format!(
-                    "New farm created In seed {}, with existed min_deposit {}",
-                    terms.seed_id, farm_seed.get_ref().min_deposit
-                )
-                .as_bytes(),
-            );
         } else {
             if let Some(nft_balance) = nft_balance {
                 farm_seed = VersionedFarmSeed::new(&terms.seed_id, min_deposit, true, metadata);
@@ -75,13 +69,6 @@ impl Contract {
             } else {
                 farm_seed = VersionedFarmSeed::new(&terms.seed_id, min_deposit, false, metadata);
             }
-            env::log(
-                format!(
-                    "The first farm created In seed {}, with min_deposit {}",
-                    terms.seed_id, farm_seed.get_ref().min_deposit
-                )
-                .as_bytes(),
-            );
         }
 
         let farm_id: FarmId = gen_farm_id(&terms.seed_id, farm_seed.get_ref().next_index as usize);
@@ -95,9 +82,34 @@ impl Contract {
         farm_seed.get_ref_mut().next_index += 1;
         self.data_mut().seeds.insert(&terms.seed_id, &farm_seed);
         self.data_mut().farms.insert(&farm_id.clone(), &farm);
+
+        NearEvent::log_create_farm(CreateFarmData {
+            farm_id: farm_id.clone(),
+            seed_id: terms.seed_id.clone(),
+            reward_token: terms.reward_token.to_string(),
+            start_at: terms.start_at,
+            reward_per_session: terms.reward_per_session.0.to_string(),
+            session_interval: terms.session_interval,
+            min_deposit: farm_seed.get_ref().min_deposit.to_string(),
+        });
         farm_id
     }
 
+    /// push a checkpoint of the farm if checkpoints are enabled for it
+    /// and its distribution moved on from prev_round.
+    pub(crate) fn internal_record_farm_checkpoint(&mut self, farm: &Farm, prev_round: u32, total_seeds: &Balance) {
+        if farm.get_last_round() == prev_round {
+            return;
+        }
+        let farm_id = farm.get_farm_id();
+        if let Some(mut checkpoints) = self.data().farm_checkpoints.get(&farm_id) {
+            let prev_storage = env::storage_usage();
+            checkpoints.push(&farm.checkpoint(total_seeds));
+            self.data_mut().farm_checkpoints.insert(&farm_id, &checkpoints);
+            self.internal_track_shared_storage(prev_storage);
+        }
+    }
+
     pub(crate) fn internal_remove_farm_by_farm_id(&mut self, farm_id: &FarmId) -> bool {
         let (seed_id, _) = parse_farm_id(farm_id);
         let mut removable = false;
@@ -110,10 +122,20 @@ impl Contract {
             }
             if removable {
                 let mut farm = self.data_mut().farms.remove(farm_id).expect(ERR41_FARM_NOT_EXIST);
+                let prev_round = farm.get_last_round();
                 farm.move_to_clear(&seed_amount);
+                self.internal_record_farm_checkpoint(&farm, prev_round, &seed_amount);
                 self.data_mut().outdated_farms.insert(farm_id, &farm);
                 farm_seed.get_ref_mut().farms.remove(farm_id);
                 self.data_mut().seeds.insert(&seed_id, &farm_seed);
+
+                let farm_info = FarmInfo::from(&farm);
+                NearEvent::log_clear_farm(ClearFarmData {
+                    farm_id: farm_id.clone(),
+                    seed_id,
+                    reward_token: farm_info.reward_token,
+                    beneficiary_reward: farm_info.beneficiary_reward.0.to_string(),
+                });
                 return true;
             }
         }
@@ -4,8 +4,10 @@ use std::convert::TryInto;
 use near_sdk::json_types::{ValidAccountId, U128};
 use near_sdk::{assert_one_yocto, env, near_bindgen, AccountId, Balance, PromiseResult};
 
-use crate::utils::{ext_fungible_token, ext_self, GAS_FOR_FT_TRANSFER, GAS_FOR_RESOLVE_TRANSFER, parse_farm_id};
+use crate::event::{NearEvent, FarmRewardData, WithdrawRewardData, CompoundData};
+use crate::utils::{ext_fungible_token, ext_self, GAS_FOR_FT_TRANSFER, GAS_FOR_RESOLVE_TRANSFER, MAX_AUTO_COMPOUND_BATCH_SIZE, parse_farm_id};
 use crate::errors::*;
+use crate::farm_seed::SeedType;
 use crate::*;
 use uint::construct_uint;
 
@@ -21,26 +23,44 @@ impl Contract {
     /// return false if the rps is still valid.
     pub fn remove_user_rps_by_farm(&mut self, farm_id: FarmId) -> bool {
         let sender_id = env::predecessor_account_id();
+        let storage_snapshot = self.internal_farmer_storage_snapshot(&sender_id);
         let mut farmer = self.get_farmer(&sender_id);
         let (seed_id, _) = parse_farm_id(&farm_id);
         let farm_seed = self.get_seed(&seed_id);
         if !farm_seed.get_ref().farms.contains(&farm_id) {
             farmer.get_ref_mut().remove_rps(&farm_id);
             self.data_mut().farmers.insert(&sender_id, &farmer);
+            self.internal_update_farmer_storage(&sender_id, storage_snapshot);
             true
         } else {
             false
         }
     }
 
+    /// farm of a seed with approval staked nfts is claimed with all farms of the seed
+    /// after the nfts ownership is verified.
     pub fn claim_reward_by_farm(&mut self, farm_id: FarmId) {
         let sender_id = env::predecessor_account_id();
+        let (seed_id, _) = parse_farm_id(&farm_id);
+        let approval_staked_nfts = self.internal_get_approval_staked_nfts(&sender_id, &seed_id);
+        if !approval_staked_nfts.is_empty() {
+            self.assert_storage_usage(&sender_id);
+            self.internal_verify_approval_stakes(seed_id, sender_id, approval_staked_nfts, false);
+            return;
+        }
         self.internal_claim_user_reward_by_farm_id(&sender_id, &farm_id);
         self.assert_storage_usage(&sender_id);
     }
 
+    /// seed with approval staked nfts is claimed after the nfts ownership is verified.
     pub fn claim_reward_by_seed(&mut self, seed_id: SeedId) {
         let sender_id = env::predecessor_account_id();
+        let approval_staked_nfts = self.internal_get_approval_staked_nfts(&sender_id, &seed_id);
+        if !approval_staked_nfts.is_empty() {
+            self.assert_storage_usage(&sender_id);
+            self.internal_verify_approval_stakes(seed_id, sender_id, approval_staked_nfts, false);
+            return;
+        }
         self.internal_claim_user_reward_by_seed_id(&sender_id, &seed_id);
         self.assert_storage_usage(&sender_id);
     }
@@ -49,6 +69,7 @@ impl Contract {
     pub fn claim_reward_by_seed_and_deposit(&mut self, seed_id: SeedId, seed_id_deposit: SeedId, is_deposit_seed_reward: bool) {
         assert_one_yocto();
         let sender_id = env::predecessor_account_id();
+        assert!(self.internal_get_approval_staked_nfts(&sender_id, &seed_id).is_empty(), "{}", ERR56_APPROVAL_STAKED_SEED_CLAIM);
         // only claim active farm with seed_id_deposit as its reward
 
         let seed = self.get_seed(&seed_id);
@@ -71,6 +92,10 @@ impl Contract {
         let sender_id = env::predecessor_account_id();
         let farmer = self.get_farmer(&sender_id);
         for (seed_id, _) in farmer.get_ref().seeds.iter() {
+            // approval staked nfts need ownership verified before claim
+            if !self.internal_get_approval_staked_nfts(&sender_id, seed_id).is_empty() {
+                continue;
+            }
             let seed = self.get_seed(&seed_id);
             for farm_id in seed.get_ref().farms.iter() {
                 let farm = self.get_farm(farm_id.to_string()).unwrap();
@@ -84,10 +109,105 @@ impl Contract {
         self.internal_seed_redeposit(&seed_id_deposit, &sender_id, true);
     }
 
+    /// harvest all farms of the given seeds and the given farms,
+    /// then stake the whole claimed reward of the target seed token into the target seed.
+    /// returns the compounded amount, panics below min_deposit of the seed or min_amount.
+    #[payable]
+    pub fn compound(&mut self, seed_ids: Vec<SeedId>, farm_ids: Vec<FarmId>, target_seed_id: SeedId, min_amount: Option<U128>) -> U128 {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        let target_seed = self.get_seed(&target_seed_id);
+        assert_eq!(target_seed.get_ref().seed_type, SeedType::FT, "{}", ERR74_COMPOUND_TARGET_NOT_FT_SEED);
+        self.internal_assert_not_managed(&sender_id, &target_seed_id);
+
+        for seed_id in seed_ids.iter() {
+            assert!(self.internal_get_approval_staked_nfts(&sender_id, seed_id).is_empty(), "{}", ERR56_APPROVAL_STAKED_SEED_CLAIM);
+            self.internal_claim_user_reward_by_seed_id(&sender_id, seed_id);
+        }
+        for farm_id in farm_ids.iter() {
+            let (seed_id, _) = parse_farm_id(farm_id);
+            assert!(self.internal_get_approval_staked_nfts(&sender_id, &seed_id).is_empty(), "{}", ERR56_APPROVAL_STAKED_SEED_CLAIM);
+            self.internal_claim_user_reward_by_farm_id(&sender_id, farm_id);
+        }
+
+        let amount = self.get_farmer(&sender_id).get_ref().rewards.get(&target_seed_id).cloned().unwrap_or(0);
+        assert!(amount > 0, "{}", ERR75_NOTHING_TO_COMPOUND);
+        if amount < target_seed.get_ref().min_deposit {
+            env::panic(format!("{} {}", ERR34_BELOW_MIN_SEED_DEPOSITED, target_seed.get_ref().min_deposit).as_bytes());
+        }
+        if let Some(min_amount) = min_amount {
+            assert!(amount >= min_amount.0, "{}", ERR76_COMPOUND_BELOW_MIN_AMOUNT);
+        }
+
+        let amount = self.internal_compound_reward(&sender_id, &target_seed_id);
+        self.assert_storage_usage(&sender_id);
+
+        amount.into()
+    }
+
+    /// opt in or out of restaking rewards of the seed token into the seed
+    /// whenever all farms of the seed are settled for the farmer.
+    #[payable]
+    pub fn set_auto_compound(&mut self, seed_id: SeedId, enabled: bool) {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        assert_eq!(self.get_seed(&seed_id).get_ref().seed_type, SeedType::FT, "{}", ERR74_COMPOUND_TARGET_NOT_FT_SEED);
+        let storage_snapshot = self.internal_farmer_storage_snapshot(&sender_id);
+        self.get_farmer(&sender_id);
+        if enabled {
+            let mut farmers = self.data().auto_compound_farmers.get(&seed_id).unwrap_or_else(|| {
+                UnorderedSet::new(StorageKeys::AutoCompoundFarmers {
+                    seed_id: seed_id.clone(),
+                })
+            });
+            farmers.insert(&sender_id);
+            self.data_mut().auto_compound_farmers.insert(&seed_id, &farmers);
+        } else {
+            self.internal_remove_auto_compound(&sender_id, &seed_id);
+        }
+        self.internal_update_farmer_storage(&sender_id, storage_snapshot);
+        self.assert_storage_usage(&sender_id);
+    }
+
+    /// keeper settles a page of farmers opted in for auto compound of the seed,
+    /// returns the number of farmers settled. unregistered farmers are dropped from the list,
+    /// farmers whose storage deposit would not cover the settlement are skipped.
+    /// limit is capped by MAX_AUTO_COMPOUND_BATCH_SIZE.
+    pub fn auto_compound(&mut self, seed_id: SeedId, from_index: u64, limit: u64) -> u64 {
+        let farmer_ids: Vec<AccountId> = match self.data().auto_compound_farmers.get(&seed_id) {
+            Some(farmers) => {
+                let keys = farmers.as_vector();
+                let limit = std::cmp::min(limit, MAX_AUTO_COMPOUND_BATCH_SIZE);
+                (from_index..std::cmp::min(from_index.saturating_add(limit), keys.len()))
+                    .map(|index| keys.get(index).unwrap())
+                    .collect()
+            },
+            None => vec![],
+        };
+        let mut settled: u64 = 0;
+        for farmer_id in farmer_ids.iter() {
+            let farmer = match self.get_farmer_wrapped(farmer_id) {
+                Some(farmer) => farmer,
+                None => {
+                    self.internal_remove_auto_compound(farmer_id, &seed_id);
+                    continue;
+                }
+            };
+            if !self.internal_covers_settle_storage(farmer.get_ref(), &seed_id) {
+                continue;
+            }
+            self.internal_claim_user_reward_by_seed_id(farmer_id, &seed_id);
+            settled += 1;
+        }
+        settled
+    }
+
     #[payable]
     pub fn claim_reward_by_farm_and_withdraw(&mut self, farm_id: FarmId) {
         assert_one_yocto();
         let sender_id = env::predecessor_account_id();
+        let (seed_id, _) = parse_farm_id(&farm_id);
+        assert!(self.internal_get_approval_staked_nfts(&sender_id, &seed_id).is_empty(), "{}", ERR56_APPROVAL_STAKED_SEED_CLAIM);
         self.internal_claim_user_reward_by_farm_id(&sender_id, &farm_id);
         self.assert_storage_usage(&sender_id);
 
@@ -99,6 +219,7 @@ impl Contract {
     pub fn claim_reward_by_seed_and_withdraw(&mut self, seed_id: SeedId) {
         assert_one_yocto();
         let sender_id = env::predecessor_account_id();
+        assert!(self.internal_get_approval_staked_nfts(&sender_id, &seed_id).is_empty(), "{}", ERR56_APPROVAL_STAKED_SEED_CLAIM);
         self.internal_claim_user_reward_by_seed_id(&sender_id, &seed_id);
         self.assert_storage_usage(&sender_id);
 
@@ -141,11 +262,13 @@ impl Contract {
     fn internal_execute_withdraw_reward(&mut self, token_id: AccountId, sender_id: AccountId, amount: Option<U128>) {
         let token_id: AccountId = token_id.into();
         let amount: u128 = amount.unwrap_or(U128(0)).into();
+        let storage_snapshot = self.internal_farmer_storage_snapshot(&sender_id);
         let mut farmer = self.get_farmer(&sender_id);
 
         // Note: subtraction, will be reverted if the promise fails.
         let amount = farmer.get_ref_mut().sub_reward(&token_id, amount);
         self.data_mut().farmers.insert(&sender_id, &farmer);
+        self.internal_update_farmer_storage(&sender_id, storage_snapshot);
         if amount != 0 {
             ext_fungible_token::ft_transfer(
                 sender_id.clone().try_into().unwrap(),
@@ -182,13 +305,11 @@ impl Contract {
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
             PromiseResult::Successful(_) => {
-                env::log(
-                    format!(
-                        "{} withdraw reward {} amount {}, Succeed.",
-                        sender_id, token_id, amount.0,
-                    )
-                    .as_bytes(),
-                );
+                NearEvent::log_withdraw_reward(WithdrawRewardData {
+                    account_id: sender_id,
+                    reward_token: token_id,
+                    amount: amount.0.to_string(),
+                });
             }
             PromiseResult::Failed => {
                 env::log(
@@ -199,23 +320,28 @@ impl Contract {
                     .as_bytes(),
                 );
                 // This reverts the changes from withdraw function.
+                let storage_snapshot = self.internal_farmer_storage_snapshot(&sender_id);
                 let mut farmer = self.get_farmer(&sender_id);
                 farmer.get_ref_mut().add_reward(&token_id, amount.0);
                 self.data_mut().farmers.insert(&sender_id, &farmer);
+                self.internal_update_farmer_storage(&sender_id, storage_snapshot);
             }
         };
     }
 }
 
+/// returns the reward amount claimed from the farm
+/// `excluded_seeds` of the farmer earn nothing, their share must be forfeited beforehand.
 fn claim_user_reward_from_farm(
     farm: &mut Farm, 
     farmer: &mut Farmer, 
     total_seeds: &Balance,
+    excluded_seeds: &Balance,
     silent: bool,
-) {
-    let user_seeds = farmer.seeds.get(&farm.get_seed_id()).unwrap_or(&0_u128);
+) -> Balance {
+    let user_seeds = farmer.seeds.get(&farm.get_seed_id()).unwrap_or(&0_u128).saturating_sub(*excluded_seeds);
     let user_rps = farmer.get_rps(&farm.get_farm_id());
-    let (new_user_rps, reward_amount) = farm.claim_user_reward(&user_rps, user_seeds, total_seeds, silent);
+    let (new_user_rps, reward_amount) = farm.claim_user_reward(&user_rps, &user_seeds, total_seeds, silent);
     if !silent {
         env::log(
             format!(
@@ -229,39 +355,278 @@ fn claim_user_reward_from_farm(
     farmer.set_rps(&farm.get_farm_id(), new_user_rps);
     if reward_amount > 0 {
         farmer.add_reward(&farm.get_reward_token(), reward_amount);
-        if !silent {
-            env::log(
-                format!(
-                    "claimed {} {} as reward from {}",
-                    reward_amount, farm.get_reward_token() , farm.get_farm_id(),
-                )
-                .as_bytes(),
-            );
-        }
+        farmer.add_claimed_reward(&farm.get_farm_id(), &farm.get_reward_token(), reward_amount);
     }
+    reward_amount
 }
 
 impl Contract {
 
+    /// stake claimed reward of the seed token of a farmer opted in for auto compound
+    /// once it reaches min_deposit of the seed. every farm of the seed must be settled for the farmer,
+    /// so no farm sees the grown seed before its rewards are claimed.
+    fn internal_auto_compound(&mut self, farmer_id: &AccountId, seed_id: &SeedId) {
+        let opted_in = self.data().auto_compound_farmers.get(seed_id)
+            .map(|farmers| farmers.contains(farmer_id))
+            .unwrap_or(false);
+        if !opted_in || self.data().staking_managers.get(&(farmer_id.clone(), seed_id.clone())).is_some() {
+            return;
+        }
+        let mut farmer = self.get_farmer(farmer_id);
+        let mut farm_seed = self.get_seed(seed_id);
+        let amount = farmer.get_ref().rewards.get(seed_id).cloned().unwrap_or(0);
+        if amount == 0 || amount < farm_seed.get_ref().min_deposit {
+            return;
+        }
+        farmer.get_ref_mut().sub_reward(seed_id, 0);
+        farmer.get_ref_mut().add_seed(seed_id, amount);
+        self.data_mut().farmers.insert(farmer_id, &farmer);
+        farm_seed.get_ref_mut().add_amount(amount);
+        self.data_mut().seeds.insert(seed_id, &farm_seed);
+        self.internal_update_seed_staker(seed_id, farmer.get_ref());
+
+        NearEvent::log_compound(CompoundData {
+            account_id: farmer_id.clone(),
+            seed_id: seed_id.clone(),
+            amount: amount.to_string(),
+        });
+    }
+
+    /// whether the storage deposit of the farmer covers what settling the seed can add.
+    fn internal_covers_settle_storage(&self, farmer: &Farmer, seed_id: &SeedId) -> bool {
+        let farms: Vec<(FarmId, AccountId)> = self.get_seed(seed_id).get_ref().farms.iter()
+            .map(|farm_id| (farm_id.clone(), self.data().farms.get(farm_id).unwrap().get_reward_token()))
+            .collect();
+        let bound = farmer.settle_storage_bound(seed_id, &farms) as Balance * env::storage_byte_cost();
+        farmer.storage_usage() + bound <= farmer.amount
+    }
+
+    pub(crate) fn internal_remove_auto_compound(&mut self, farmer_id: &AccountId, seed_id: &SeedId) {
+        if let Some(mut farmers) = self.data().auto_compound_farmers.get(seed_id) {
+            if farmers.remove(farmer_id) {
+                self.data_mut().auto_compound_farmers.insert(seed_id, &farmers);
+            }
+        }
+    }
+
+    /// move the whole claimed reward of the seed token into the seed of the farmer.
+    fn internal_compound_reward(&mut self, sender_id: &AccountId, seed_id: &SeedId) -> Balance {
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
+        let mut farmer = self.get_farmer(sender_id);
+        let amount = farmer.get_ref_mut().sub_reward(seed_id, 0);
+        self.data_mut().farmers.insert(sender_id, &farmer);
+        self.internal_seed_credit(seed_id, sender_id, amount);
+        self.internal_update_farmer_storage(sender_id, storage_snapshot);
+
+        NearEvent::log_compound(CompoundData {
+            account_id: sender_id.clone(),
+            seed_id: seed_id.clone(),
+            amount: amount.to_string(),
+        });
+        amount
+    }
+
+    /// nfts staked by approval are not verified here, their share of reward is held until they are.
     pub(crate) fn internal_claim_user_reward_by_seed_id(
         &mut self, 
         sender_id: &AccountId,
         seed_id: &SeedId) {
+        self.internal_claim_verified_user_reward_by_seed_id(sender_id, seed_id, &[]);
+    }
+
+    /// claim rewards of the seed, approval staked nfts not in `verified_nft_token_ids`
+    /// have their share of reward held, the verified ones get what was held for them.
+    pub(crate) fn internal_claim_verified_user_reward_by_seed_id(
+        &mut self, 
+        sender_id: &AccountId,
+        seed_id: &SeedId,
+        verified_nft_token_ids: &[ContractNFTTokenId]) {
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
+        self.internal_release_held_rewards(sender_id, verified_nft_token_ids, true);
+        let unverified_nfts = self.internal_unverified_approval_nfts(sender_id, seed_id, verified_nft_token_ids);
+        let unverified_seeds: Balance = unverified_nfts.iter().map(|(_, weight)| weight).sum();
+        if unverified_seeds > 0 {
+            self.internal_hold_user_reward_by_seed_id(sender_id, seed_id, &unverified_nfts);
+        }
         let mut farmer = self.get_farmer(sender_id);
         if let Some(mut farm_seed) = self.get_seed_wrapped(seed_id) {
             let amount = farm_seed.get_ref().amount;
+            let mut claim_reward_data: Vec<FarmRewardData> = vec![];
             for farm_id in &mut farm_seed.get_ref_mut().farms.iter() {
                 let mut farm = self.data().farms.get(farm_id).unwrap();
-                claim_user_reward_from_farm(
+                let prev_round = farm.get_last_round();
+                let reward_amount = claim_user_reward_from_farm(
                     &mut farm, 
                     farmer.get_ref_mut(),  
                     &amount,
+                    &unverified_seeds,
                     true,
                 );
+                self.internal_record_farm_checkpoint(&farm, prev_round, &amount);
+                if reward_amount > 0 {
+                    claim_reward_data.push(FarmRewardData {
+                        account_id: sender_id.clone(),
+                        farm_id: farm_id.clone(),
+                        reward_token: farm.get_reward_token(),
+                        amount: reward_amount.to_string(),
+                    });
+                }
                 self.data_mut().farms.insert(farm_id, &farm);
             }
             self.data_mut().seeds.insert(seed_id, &farm_seed);
             self.data_mut().farmers.insert(sender_id, &farmer);
+            if !claim_reward_data.is_empty() {
+                NearEvent::log_claim_reward(claim_reward_data);
+            }
+
+            // all farms of the seed are settled, safe to apply nft_balance changes
+            if farm_seed.get_ref().seed_type == SeedType::NFT {
+                self.internal_reprice_farmer_nft_seed(sender_id, seed_id);
+            }
+            // and to grow the seed of farmers opted in for auto compound
+            self.internal_auto_compound(sender_id, seed_id);
+            self.internal_update_farmer_storage(sender_id, storage_snapshot);
+        }
+    }
+
+    /// approval staked nfts of the farmer not in `verified_nft_token_ids` with their seed weight,
+    /// bounded by the farmer balance as weight not repriced yet.
+    fn internal_unverified_approval_nfts(
+        &self,
+        farmer_id: &AccountId,
+        seed_id: &SeedId,
+        verified_nft_token_ids: &[ContractNFTTokenId]
+    ) -> Vec<(ContractNFTTokenId, Balance)> {
+        let mut farmer_balance = match self.get_farmer_wrapped(farmer_id) {
+            Some(farmer) => farmer.get_ref().get_balance(seed_id),
+            None => return vec![],
+        };
+        self.internal_get_approval_staked_nfts(farmer_id, seed_id).into_iter()
+            .filter(|contract_nft_token_id| !verified_nft_token_ids.contains(contract_nft_token_id))
+            .map(|contract_nft_token_id| {
+                let weight = self.internal_get_nft_balance_equivalent(seed_id, &contract_nft_token_id)
+                    .unwrap_or(0)
+                    .min(farmer_balance);
+                farmer_balance -= weight;
+                (contract_nft_token_id, weight)
+            })
+            .collect()
+    }
+
+    /// Hold user unclaimed reward of unverified approval staked nfts in each farm of the seed,
+    /// must be called before the user rps is moved by the claim of the other seeds.
+    fn internal_hold_user_reward_by_seed_id(
+        &mut self,
+        sender_id: &AccountId,
+        seed_id: &SeedId,
+        unverified_nfts: &[(ContractNFTTokenId, Balance)]) {
+        let farmer = self.get_farmer(sender_id);
+        if let Some(farm_seed) = self.get_seed_wrapped(seed_id) {
+            let amount = farm_seed.get_ref().amount;
+            for farm_id in farm_seed.get_ref().farms.iter() {
+                let mut farm = self.data().farms.get(farm_id).unwrap();
+                let prev_round = farm.get_last_round();
+                let user_rps = farmer.get_ref().get_rps(farm_id);
+                for (contract_nft_token_id, weight) in unverified_nfts.iter() {
+                    let (_, held) = farm.claim_user_reward(&user_rps, weight, &amount, true);
+                    if held > 0 {
+                        let mut held_rewards = self.data().nft_approval_held_rewards.get(contract_nft_token_id).unwrap_or_default();
+                        *held_rewards.entry(farm_id.clone()).or_insert(0) += held;
+                        self.data_mut().nft_approval_held_rewards.insert(contract_nft_token_id, &held_rewards);
+                    }
+                }
+                self.internal_record_farm_checkpoint(&farm, prev_round, &amount);
+                self.data_mut().farms.insert(farm_id, &farm);
+            }
+        }
+    }
+
+    /// Pay reward held for approval staked nfts to the farmer once their ownership is verified,
+    /// or forfeit it to beneficiary of each farm once it is disproved.
+    pub(crate) fn internal_release_held_rewards(
+        &mut self,
+        farmer_id: &AccountId,
+        contract_nft_token_ids: &[ContractNFTTokenId],
+        is_verified: bool) {
+        let mut farmer = self.get_farmer(farmer_id);
+        let mut reward_data: Vec<FarmRewardData> = vec![];
+        for contract_nft_token_id in contract_nft_token_ids.iter() {
+            let held_rewards = match self.data_mut().nft_approval_held_rewards.remove(contract_nft_token_id) {
+                Some(held_rewards) => held_rewards,
+                None => continue,
+            };
+            for (farm_id, amount) in held_rewards.into_iter() {
+                let (mut farm, is_outdated) = match self.data().farms.get(&farm_id) {
+                    Some(farm) => (farm, false),
+                    None => match self.data().outdated_farms.get(&farm_id) {
+                        Some(farm) => (farm, true),
+                        None => continue,
+                    },
+                };
+                let reward_token = farm.get_reward_token();
+                if is_verified {
+                    farmer.get_ref_mut().add_reward(&reward_token, amount);
+                    farmer.get_ref_mut().add_claimed_reward(&farm_id, &reward_token, amount);
+                } else {
+                    farm.forfeit_held_reward(&amount);
+                    if is_outdated {
+                        self.data_mut().outdated_farms.insert(&farm_id, &farm);
+                    } else {
+                        self.data_mut().farms.insert(&farm_id, &farm);
+                    }
+                }
+                reward_data.push(FarmRewardData {
+                    account_id: farmer_id.clone(),
+                    farm_id,
+                    reward_token,
+                    amount: amount.to_string(),
+                });
+            }
+        }
+        if reward_data.is_empty() {
+            return;
+        }
+        if is_verified {
+            self.data_mut().farmers.insert(farmer_id, &farmer);
+            NearEvent::log_claim_reward(reward_data);
+        } else {
+            NearEvent::log_forfeit_reward(reward_data);
+        }
+    }
+
+    /// Forfeit user unclaimed reward of given seeds in each farm of the seed to its beneficiary,
+    /// must be called before the seeds are removed from the farmer.
+    pub(crate) fn internal_forfeit_user_reward_by_seed_id(
+        &mut self, 
+        sender_id: &AccountId,
+        seed_id: &SeedId,
+        forfeit_seeds: Balance) {
+        let farmer = self.get_farmer(sender_id);
+        if let Some(farm_seed) = self.get_seed_wrapped(seed_id) {
+            let amount = farm_seed.get_ref().amount;
+            let mut forfeit_reward_data: Vec<FarmRewardData> = vec![];
+            for farm_id in farm_seed.get_ref().farms.iter() {
+                let mut farm = self.data().farms.get(farm_id).unwrap();
+                let prev_round = farm.get_last_round();
+                let forfeited = farm.forfeit_user_reward(
+                    &farmer.get_ref().get_rps(farm_id),
+                    &forfeit_seeds,
+                    &amount,
+                );
+                self.internal_record_farm_checkpoint(&farm, prev_round, &amount);
+                self.data_mut().farms.insert(farm_id, &farm);
+                if forfeited > 0 {
+                    forfeit_reward_data.push(FarmRewardData {
+                        account_id: sender_id.clone(),
+                        farm_id: farm_id.clone(),
+                        reward_token: farm.get_reward_token(),
+                        amount: forfeited.to_string(),
+                    });
+                }
+            }
+            if !forfeit_reward_data.is_empty() {
+                NearEvent::log_forfeit_reward(forfeit_reward_data);
+            }
         }
     }
 
@@ -269,6 +634,7 @@ impl Contract {
         &mut self, 
         sender_id: &AccountId, 
         farm_id: &FarmId) {
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
         let mut farmer = self.get_farmer(sender_id);
 
         let (seed_id, _) = parse_farm_id(farm_id);
@@ -276,14 +642,26 @@ impl Contract {
         if let Some(farm_seed) = self.get_seed_wrapped(&seed_id) {
             let amount = farm_seed.get_ref().amount;
             if let Some(mut farm) = self.data().farms.get(farm_id) {
-                claim_user_reward_from_farm(
+                let prev_round = farm.get_last_round();
+                let reward_amount = claim_user_reward_from_farm(
                     &mut farm, 
                     farmer.get_ref_mut(), 
                     &amount,
+                    &0,
                     false,
                 );
+                self.internal_record_farm_checkpoint(&farm, prev_round, &amount);
                 self.data_mut().farms.insert(farm_id, &farm);
                 self.data_mut().farmers.insert(sender_id, &farmer);
+                self.internal_update_farmer_storage(sender_id, storage_snapshot);
+                if reward_amount > 0 {
+                    NearEvent::log_claim_reward(vec![FarmRewardData {
+                        account_id: sender_id.clone(),
+                        farm_id: farm_id.clone(),
+                        reward_token: farm.get_reward_token(),
+                        amount: reward_amount.to_string(),
+                    }]);
+                }
             }
         }
     }
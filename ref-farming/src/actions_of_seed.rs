@@ -1,13 +1,13 @@
 
 use std::collections::HashMap;
-use std::convert::TryInto;
 use near_sdk::json_types::U128;
-use near_sdk::{AccountId, Balance, PromiseResult};
+use near_sdk::{AccountId, Balance, Promise, PromiseResult};
+use near_contract_standards::non_fungible_token::Token;
 
-use crate::event::{NearEvent, UnlockFTBalanceData, LockFTBalanceData};
-use crate::utils::{assert_one_yocto, ext_multi_fungible_token, ext_fungible_token, ext_non_fungible_token, ext_self, wrap_mft_token_id, parse_seed_id, GAS_FOR_FT_TRANSFER, GAS_FOR_RESOLVE_TRANSFER, GAS_FOR_NFT_TRANSFER, FT_INDEX_TAG, get_nft_balance_equivalent, to_sec, is_paras_farming_mainnet};
+use crate::event::{NearEvent, UnlockFTBalanceData, LockFTBalanceData, EvictNftData, SeedData, RepriceNftSeedData, TransferSeedData};
+use crate::utils::{assert_one_yocto, ext_multi_fungible_token, ext_fungible_token, ext_non_fungible_token, ext_self, wrap_mft_token_id, parse_seed_id, GAS_FOR_FT_TRANSFER, GAS_FOR_RESOLVE_TRANSFER, GAS_FOR_NFT_TRANSFER, GAS_FOR_NFT_TOKEN, GAS_FOR_RESOLVE_NFT_BATCH, MAX_NFT_BATCH_SIZE, FT_INDEX_TAG, parse_contract_nft_token_id, get_nft_balance_equivalent, get_nft_trait_multiplier, NFT_TRAIT_MULTIPLIER_DENOM, to_sec, is_paras_farming_mainnet};
 use crate::errors::*;
-use crate::farm_seed::SeedType;
+use crate::farm_seed::{SeedType, NftApproval};
 use crate::*;
 use crate::simple_farm::{NFTTokenId, ContractNFTTokenId};
 use crate::utils::NFT_DELIMETER;
@@ -21,31 +21,179 @@ impl Contract {
         self.data_mut().seeds.insert(&seed_id, &seed);
     }
 
+    /// nft staked by approval is withdrawn after its ownership is verified,
+    /// or slashed if it has been moved out of the farmer wallet.
     #[payable]
     pub fn withdraw_nft(&mut self, seed_id: SeedId, nft_contract_id: String, nft_token_id: NFTTokenId) {
         assert_one_yocto();
         let sender_id = env::predecessor_account_id();
 
-        self.internal_nft_withdraw(&seed_id, &sender_id, &nft_contract_id, &nft_token_id);
+        self.internal_assert_not_managed(&sender_id, &seed_id);
 
-        // transfer nft back to the owner
-        ext_non_fungible_token::nft_transfer(
-            sender_id.clone(),
-            nft_token_id.clone(),
-            None,
-            None,
-            &nft_contract_id,
-            1,
-            GAS_FOR_NFT_TRANSFER
-        )
-        .then(ext_self::callback_post_withdraw_nft(
+        let contract_nft_token_id : ContractNFTTokenId = format!("{}{}{}", nft_contract_id, NFT_DELIMETER, nft_token_id);
+        if self.internal_is_approval_staked(&sender_id, &seed_id, &contract_nft_token_id) {
+            self.internal_verify_approval_stakes(seed_id, sender_id, vec![contract_nft_token_id], true);
+            return;
+        }
+
+        let receiver_id = sender_id.clone();
+        self.internal_withdraw_nft_to(seed_id, sender_id, receiver_id, nft_contract_id, nft_token_id);
+    }
+
+    /// withdraw nft of a position staked on behalf of the farmer,
+    /// only by its staking manager, the nft is sent to the staking manager.
+    #[payable]
+    pub fn withdraw_nft_for(&mut self, farmer_id: ValidAccountId, seed_id: SeedId, nft_contract_id: String, nft_token_id: NFTTokenId) {
+        assert_one_yocto();
+        let farmer_id: AccountId = farmer_id.into();
+        let receiver_id = env::predecessor_account_id();
+        self.internal_assert_staking_manager(&farmer_id, &seed_id, &receiver_id);
+
+        self.internal_withdraw_nft_to(seed_id, farmer_id, receiver_id, nft_contract_id, nft_token_id);
+    }
+
+    /// withdraw seed of a position staked on behalf of the farmer,
+    /// only by its staking manager, the seed is sent to the staking manager.
+    #[payable]
+    pub fn withdraw_seed_for(&mut self, farmer_id: ValidAccountId, seed_id: SeedId, amount: U128) {
+        assert_one_yocto();
+        let farmer_id: AccountId = farmer_id.into();
+        let receiver_id = env::predecessor_account_id();
+        self.internal_assert_staking_manager(&farmer_id, &seed_id, &receiver_id);
+
+        self.internal_withdraw_seed_to(seed_id, farmer_id, receiver_id, amount.into());
+    }
+
+    /// farmer opts in a staking manager for deposits made on his behalf,
+    /// only while the position is empty or has no staking manager yet.
+    #[payable]
+    pub fn set_staking_manager(&mut self, seed_id: SeedId, staking_manager_id: Option<ValidAccountId>) {
+        assert_one_yocto();
+        let farmer_id = env::predecessor_account_id();
+        self.get_seed(&seed_id);
+        if self.internal_has_position(&farmer_id, &seed_id) {
+            self.internal_assert_not_managed(&farmer_id, &seed_id);
+        }
+        let storage_snapshot = self.internal_farmer_storage_snapshot(&farmer_id);
+        let position_key = (farmer_id.clone(), seed_id);
+        match staking_manager_id {
+            Some(staking_manager_id) => self.data_mut().staking_managers.insert(&position_key, &staking_manager_id.into()),
+            None => self.data_mut().staking_managers.remove(&position_key),
+        };
+        self.internal_update_farmer_storage(&farmer_id, storage_snapshot);
+        self.assert_storage_usage(&farmer_id);
+    }
+
+    /// give control of the withdrawals of the position back to its farmer.
+    #[payable]
+    pub fn release_staking_manager(&mut self, farmer_id: ValidAccountId, seed_id: SeedId) {
+        assert_one_yocto();
+        let farmer_id: AccountId = farmer_id.into();
+        self.internal_assert_staking_manager(&farmer_id, &seed_id, &env::predecessor_account_id());
+        let storage_snapshot = self.internal_farmer_storage_snapshot(&farmer_id);
+        self.data_mut().staking_managers.remove(&(farmer_id.clone(), seed_id));
+        self.internal_update_farmer_storage(&farmer_id, storage_snapshot);
+    }
+
+    /// withdraw several nfts of a seed with one reward settlement,
+    /// nfts are given as `{nft_contract_id}@{token_id}` like in `list_user_nft_seeds`.
+    #[payable]
+    pub fn withdraw_nfts(&mut self, seed_id: SeedId, contract_nft_token_ids: Vec<ContractNFTTokenId>) {
+        assert_one_yocto();
+        assert!(
+            !contract_nft_token_ids.is_empty() && contract_nft_token_ids.len() <= MAX_NFT_BATCH_SIZE,
+            "{}", ERR53_INVALID_NFT_BATCH_SIZE
+        );
+        let sender_id = env::predecessor_account_id();
+        self.internal_assert_not_managed(&sender_id, &seed_id);
+        for contract_nft_token_id in contract_nft_token_ids.iter() {
+            assert!(
+                !self.internal_is_approval_staked(&sender_id, &seed_id, contract_nft_token_id),
+                "{}", ERR57_APPROVAL_STAKED_NFT_IN_BATCH
+            );
+        }
+
+        self.internal_nfts_withdraw(&seed_id, &sender_id, &contract_nft_token_ids);
+
+        // transfer nfts back to the owner in one batch
+        let mut transfers: Option<Promise> = None;
+        for contract_nft_token_id in contract_nft_token_ids.iter() {
+            let (nft_contract_id, nft_token_id) = parse_contract_nft_token_id(contract_nft_token_id);
+            let transfer = ext_non_fungible_token::nft_transfer(
+                sender_id.clone(),
+                nft_token_id,
+                None,
+                None,
+                &nft_contract_id,
+                1,
+                GAS_FOR_NFT_TRANSFER
+            );
+            transfers = Some(match transfers {
+                Some(promise) => promise.and(transfer),
+                None => transfer,
+            });
+        }
+        transfers.unwrap().then(ext_self::callback_post_withdraw_nfts(
             seed_id,
             sender_id,
-            nft_contract_id,
-            nft_token_id,
+            contract_nft_token_ids,
             &env::current_account_id(),
             0,
-            GAS_FOR_RESOLVE_TRANSFER
+            GAS_FOR_RESOLVE_NFT_BATCH
+        ));
+    }
+
+    /// stake nfts approved to this contract by `nft_approve` with seed_id as msg,
+    /// they are transferred to this contract in one batch and credited with one reward settlement.
+    #[payable]
+    pub fn stake_approved_nfts(&mut self, seed_id: SeedId, contract_nft_token_ids: Vec<ContractNFTTokenId>) {
+        assert_one_yocto();
+        assert!(
+            !contract_nft_token_ids.is_empty() && contract_nft_token_ids.len() <= MAX_NFT_BATCH_SIZE,
+            "{}", ERR53_INVALID_NFT_BATCH_SIZE
+        );
+        assert!(
+            self.data().nft_trait_rules.get(&seed_id).is_none(),
+            "Paras(farming): seed with trait rules only accepts nft_transfer_call"
+        );
+        let sender_id = env::predecessor_account_id();
+        // make sure the farmer is registered before taking the nfts
+        self.get_farmer(&sender_id);
+        self.internal_drop_stale_nft_approvals(&sender_id, &contract_nft_token_ids);
+
+        let mut transfers: Option<Promise> = None;
+        for contract_nft_token_id in contract_nft_token_ids.iter() {
+            let approval = self.internal_unpark_nft_approval(contract_nft_token_id)
+                .expect(ERR54_NFT_APPROVAL_NOT_EXIST);
+            assert_eq!(approval.owner_id, sender_id, "Paras(farming): owner_id should be predecessor_id");
+            assert_eq!(approval.seed_id, seed_id, "Paras(farming): nft is approved for another seed");
+            assert!(self.internal_check_approval_stake(contract_nft_token_id, &sender_id).is_none(), "{}", ERR59_NFT_ALREADY_STAKED);
+            if self.internal_get_nft_balance_equivalent(&seed_id, contract_nft_token_id).is_none() {
+                panic!("Paras(farming): nft token does not exist on seed");
+            }
+
+            let (nft_contract_id, nft_token_id) = parse_contract_nft_token_id(contract_nft_token_id);
+            let transfer = ext_non_fungible_token::nft_transfer(
+                env::current_account_id(),
+                nft_token_id,
+                Some(approval.approval_id),
+                None,
+                &nft_contract_id,
+                1,
+                GAS_FOR_NFT_TRANSFER
+            );
+            transfers = Some(match transfers {
+                Some(promise) => promise.and(transfer),
+                None => transfer,
+            });
+        }
+        transfers.unwrap().then(ext_self::callback_post_stake_approved_nfts(
+            seed_id,
+            sender_id,
+            contract_nft_token_ids,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_NFT_BATCH
         ));
     }
 
@@ -53,56 +201,35 @@ impl Contract {
     pub fn withdraw_seed(&mut self, seed_id: SeedId, amount: U128) {
         assert_one_yocto();
         let sender_id = env::predecessor_account_id();
+        self.internal_assert_not_managed(&sender_id, &seed_id);
 
-        let seed_contract_id: AccountId = seed_id.split(FT_INDEX_TAG).next().unwrap().to_string();
-        let amount: Balance = amount.into();
+        let receiver_id = sender_id.clone();
+        self.internal_withdraw_seed_to(seed_id, sender_id, receiver_id, amount.into());
+    }
 
-        // update inner state
-        let seed_type = self.internal_seed_withdraw(&seed_id, &sender_id, amount);
+    /// move staked seed to another farmer without withdrawing it,
+    /// rewards of both farmers are settled first and locked balance stays with the sender.
+    #[payable]
+    pub fn transfer_seed(&mut self, seed_id: SeedId, receiver_id: ValidAccountId, amount: U128) {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        assert!(!self.is_seed_type(&seed_id, SeedType::NFT), "{}", ERR66_NFT_SEED_TRANSFER);
 
-        match seed_type {
-            SeedType::FT => {
-                ext_fungible_token::ft_transfer(
-                    sender_id.clone().try_into().unwrap(),
-                    amount.into(),
-                    None,
-                    &seed_contract_id,
-                    1,  // one yocto near
-                    GAS_FOR_FT_TRANSFER,
-                )
-                    .then(ext_self::callback_post_withdraw_ft_seed(
-                        seed_id,
-                        sender_id,
-                        amount.into(),
-                        &env::current_account_id(),
-                        0,
-                        GAS_FOR_RESOLVE_TRANSFER,
-                    ));
-            },
-            SeedType::NFT => {
-                panic!("Use withdraw_nft for this");
-            },
-            SeedType::MFT => {
-                let (receiver_id, token_id) = parse_seed_id(&seed_id);
-                ext_multi_fungible_token::mft_transfer(
-                    wrap_mft_token_id(&token_id),
-                    sender_id.clone().try_into().unwrap(),
-                    amount.into(),
-                    None,
-                    &receiver_id,
-                    1,  // one yocto near
-                    GAS_FOR_FT_TRANSFER,
-                )
-                    .then(ext_self::callback_post_withdraw_mft_seed(
-                        seed_id,
-                        sender_id,
-                        amount.into(),
-                        &env::current_account_id(),
-                        0,
-                        GAS_FOR_RESOLVE_TRANSFER,
-                    ));
-            }
-        }
+        self.internal_transfer_seed(&seed_id, &sender_id, &receiver_id.into(), amount.into(), None);
+    }
+
+    /// move staked nft to another farmer together with its balance equivalent.
+    #[payable]
+    pub fn transfer_staked_nft(&mut self, seed_id: SeedId, receiver_id: ValidAccountId, nft_contract_id: String, nft_token_id: NFTTokenId) {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        let contract_nft_token_id : ContractNFTTokenId = format!("{}{}{}", nft_contract_id, NFT_DELIMETER, nft_token_id);
+        assert!(
+            !self.internal_is_approval_staked(&sender_id, &seed_id, &contract_nft_token_id),
+            "{}", ERR65_APPROVAL_STAKED_NFT_TRANSFER
+        );
+
+        self.internal_transfer_seed(&seed_id, &sender_id, &receiver_id.into(), 0, Some(contract_nft_token_id));
     }
 
     #[payable]
@@ -191,34 +318,286 @@ impl Contract {
                 );
 
                 // revert withdraw
+                let contract_nft_token_id : ContractNFTTokenId = format!("{}{}{}", nft_contract_id, NFT_DELIMETER, nft_token_id);
+                self.internal_nfts_credit(&seed_id, &sender_id, &[contract_nft_token_id]);
+            },
+            PromiseResult::Successful(_) => {
+                let contract_nft_token_id : ContractNFTTokenId = format!("{}{}{}", nft_contract_id, NFT_DELIMETER, nft_token_id);
+                NearEvent::log_withdraw_seed(vec![
+                    self.internal_nft_seed_data(&seed_id, &sender_id, &contract_nft_token_id)
+                ]);
+                self.data_mut().nft_trait_multipliers.remove(&(seed_id, contract_nft_token_id));
+            }
+        }
+    }
+
+    #[private]
+    pub fn callback_post_withdraw_nfts(
+        &mut self,
+        seed_id: SeedId,
+        sender_id: AccountId,
+        contract_nft_token_ids: Vec<ContractNFTTokenId>
+    ) {
+        assert_eq!(
+            env::promise_results_count(),
+            contract_nft_token_ids.len() as u64,
+            "{}",
+            ERR27_CALLBACK_POST_NFT_BATCH_INVALID
+        );
 
-                let mut farmer = self.get_farmer(&sender_id);
-                let mut farm_seed = self.get_seed(&seed_id);
+        let mut failed_nft_token_ids: Vec<ContractNFTTokenId> = vec![];
+        let mut withdraw_seed_data: Vec<SeedData> = vec![];
+        for (index, contract_nft_token_id) in contract_nft_token_ids.into_iter().enumerate() {
+            match env::promise_result(index as u64) {
+                PromiseResult::NotReady => unreachable!(),
+                PromiseResult::Failed => {
+                    env::log(
+                        format!(
+                            "{} withdraw {} nft, Callback failed.",
+                            sender_id, contract_nft_token_id
+                        ).as_bytes()
+                    );
+                    failed_nft_token_ids.push(contract_nft_token_id);
+                },
+                PromiseResult::Successful(_) => {
+                    withdraw_seed_data.push(self.internal_nft_seed_data(&seed_id, &sender_id, &contract_nft_token_id));
+                    self.data_mut().nft_trait_multipliers.remove(&(seed_id.clone(), contract_nft_token_id));
+                }
+            }
+        }
 
-                let contract_nft_token_id : ContractNFTTokenId = format!("{}{}{}", nft_contract_id, NFT_DELIMETER, nft_token_id);
-                let nft_balance = self.data().nft_balance_seeds.get(&seed_id).unwrap();
-                if let Some(nft_balance_equivalent) = get_nft_balance_equivalent(nft_balance, contract_nft_token_id.clone()) {
-                    self.internal_claim_user_reward_by_seed_id(&sender_id, &seed_id);
+        if !withdraw_seed_data.is_empty() {
+            NearEvent::log_withdraw_seed(withdraw_seed_data);
+        }
 
-                    farmer.get_ref_mut().add_nft(&seed_id, contract_nft_token_id);
+        // revert withdraw of the nfts still held by this contract
+        if !failed_nft_token_ids.is_empty() {
+            self.internal_nfts_credit(&seed_id, &sender_id, &failed_nft_token_ids);
+        }
+    }
 
-                    farmer.get_ref_mut().add_seed(&seed_id, nft_balance_equivalent);
-                    self.data_mut().farmers.insert(&sender_id, &farmer);
+    #[private]
+    pub fn callback_post_stake_approved_nfts(
+        &mut self,
+        seed_id: SeedId,
+        sender_id: AccountId,
+        contract_nft_token_ids: Vec<ContractNFTTokenId>
+    ) {
+        assert_eq!(
+            env::promise_results_count(),
+            contract_nft_token_ids.len() as u64,
+            "{}",
+            ERR27_CALLBACK_POST_NFT_BATCH_INVALID
+        );
 
-                    // **** update seed (new version)
-                    farm_seed.get_ref_mut().add_amount(nft_balance_equivalent);
-                    self.data_mut().seeds.insert(&seed_id, &farm_seed);
+        let mut staked_nft_token_ids: Vec<ContractNFTTokenId> = vec![];
+        for (index, contract_nft_token_id) in contract_nft_token_ids.into_iter().enumerate() {
+            match env::promise_result(index as u64) {
+                PromiseResult::NotReady => unreachable!(),
+                PromiseResult::Failed => {
+                    env::log(
+                        format!(
+                            "{} stake {} nft, Callback failed.",
+                            sender_id, contract_nft_token_id
+                        ).as_bytes()
+                    );
+                },
+                PromiseResult::Successful(_) => {
+                    staked_nft_token_ids.push(contract_nft_token_id);
                 }
+            }
+        }
+
+        if staked_nft_token_ids.is_empty() {
+            return;
+        }
+        self.internal_nfts_credit(&seed_id, &sender_id, &staked_nft_token_ids);
+        NearEvent::log_deposit_seed(
+            staked_nft_token_ids.iter()
+                .map(|contract_nft_token_id| self.internal_nft_seed_data(&seed_id, &sender_id, contract_nft_token_id))
+                .collect()
+        );
+
+        let farmer = self.get_farmer(&sender_id);
+        let farm_seed = self.get_seed(&seed_id);
+        let mut reward_tokens: Vec<AccountId> = vec![];
+        for farm_id in farm_seed.get_ref().farms.iter() {
+            let reward_token = self.data().farms.get(farm_id).unwrap().get_reward_token();
+            if !reward_tokens.contains(&reward_token) {
+                if farmer.get_ref().rewards.contains_key(&reward_token) {
+                    self.private_withdraw_reward(reward_token.clone(), sender_id.to_string(), None);
+                }
+                reward_tokens.push(reward_token);
+            }
+        };
+    }
+
+    /// Slash approval staked nfts moved out of the farmer wallet,
+    /// then withdraw the rest if `is_withdraw`, or claim rewards of the seed.
+    #[private]
+    pub fn callback_post_verify_approval_stakes(
+        &mut self,
+        seed_id: SeedId,
+        sender_id: AccountId,
+        contract_nft_token_ids: Vec<ContractNFTTokenId>,
+        is_withdraw: bool
+    ) {
+        assert_eq!(
+            env::promise_results_count(),
+            contract_nft_token_ids.len() as u64,
+            "{}",
+            ERR27_CALLBACK_POST_NFT_BATCH_INVALID
+        );
+
+        let mut moved_nft_token_ids: Vec<ContractNFTTokenId> = vec![];
+        let mut owned_nft_token_ids: Vec<ContractNFTTokenId> = vec![];
+        for (index, contract_nft_token_id) in contract_nft_token_ids.into_iter().enumerate() {
+            // stake could be changed while waiting for nft_token
+            if !self.internal_is_approval_staked(&sender_id, &seed_id, &contract_nft_token_id) {
+                continue;
+            }
+            match env::promise_result(index as u64) {
+                PromiseResult::NotReady => unreachable!(),
+                PromiseResult::Successful(value) => {
+                    let token = near_sdk::serde_json::from_slice::<Option<Token>>(&value).ok().flatten();
+                    if token.map(|token| token.owner_id == sender_id).unwrap_or(false) {
+                        owned_nft_token_ids.push(contract_nft_token_id);
+                    } else {
+                        moved_nft_token_ids.push(contract_nft_token_id);
+                    }
+                },
+                // unknown ownership is not a proof of moving
+                PromiseResult::Failed => owned_nft_token_ids.push(contract_nft_token_id),
+            }
+        }
+
+        if !moved_nft_token_ids.is_empty() {
+            self.internal_evict_nfts(&seed_id, &sender_id, &moved_nft_token_ids);
+        }
+
+        // only nfts still in the farmer wallet earn their share
+        self.internal_claim_verified_user_reward_by_seed_id(&sender_id, &seed_id, &owned_nft_token_ids);
+        if is_withdraw && !owned_nft_token_ids.is_empty() {
+            let withdraw_seed_data: Vec<SeedData> = owned_nft_token_ids.iter()
+                .map(|contract_nft_token_id| self.internal_nft_seed_data(&seed_id, &sender_id, contract_nft_token_id))
+                .collect();
+            self.internal_nfts_withdraw(&seed_id, &sender_id, &owned_nft_token_ids);
+            for contract_nft_token_id in owned_nft_token_ids.iter() {
+                self.data_mut().nft_approval_stakes.remove(contract_nft_token_id);
+                self.data_mut().nft_trait_multipliers.remove(&(seed_id.clone(), contract_nft_token_id.clone()));
+            }
+            NearEvent::log_withdraw_seed(withdraw_seed_data);
+        }
+    }
+
+    /// Evict staked nfts of the farmer that are no longer held by this contract,
+    /// or by the farmer for nfts staked by approval.
+    #[private]
+    pub fn callback_post_verify_staked_nfts(
+        &mut self,
+        seed_id: SeedId,
+        farmer_id: AccountId,
+        contract_nft_token_ids: Vec<ContractNFTTokenId>
+    ) {
+        assert_eq!(
+            env::promise_results_count(),
+            contract_nft_token_ids.len() as u64,
+            "{}",
+            ERR27_CALLBACK_POST_NFT_BATCH_INVALID
+        );
+
+        let farmer = self.get_farmer(&farmer_id);
+        let mut evicted_nft_token_ids: Vec<ContractNFTTokenId> = vec![];
+        let mut verified_nft_token_ids: Vec<ContractNFTTokenId> = vec![];
+        for (index, contract_nft_token_id) in contract_nft_token_ids.into_iter().enumerate() {
+            // nft could be withdrawn while waiting for nft_token
+            let is_staked = farmer.get_ref().nft_seeds.get(&seed_id)
+                .map(|nft_contract_seed| nft_contract_seed.contains(&contract_nft_token_id))
+                .unwrap_or(false);
+            if !is_staked {
+                continue;
+            }
+            let is_approval_staked = self.internal_is_approval_staked(&farmer_id, &seed_id, &contract_nft_token_id);
+            let expected_owner_id = if is_approval_staked {
+                farmer_id.clone()
+            } else {
+                env::current_account_id()
+            };
+            match env::promise_result(index as u64) {
+                PromiseResult::NotReady => unreachable!(),
+                PromiseResult::Successful(value) => {
+                    let token = near_sdk::serde_json::from_slice::<Option<Token>>(&value).ok().flatten();
+                    if !token.map(|token| token.owner_id == expected_owner_id).unwrap_or(false) {
+                        evicted_nft_token_ids.push(contract_nft_token_id);
+                    } else if is_approval_staked {
+                        verified_nft_token_ids.push(contract_nft_token_id);
+                    }
+                },
+                // unknown ownership is not a proof of moving
+                PromiseResult::Failed => {},
+            }
+        }
+
+        if !evicted_nft_token_ids.is_empty() {
+            self.internal_evict_nfts(&seed_id, &farmer_id, &evicted_nft_token_ids);
+        }
+        // reward held for approval stakes found in the farmer wallet is paid
+        if !verified_nft_token_ids.is_empty() {
+            let storage_snapshot = self.internal_farmer_storage_snapshot(&farmer_id);
+            self.internal_release_held_rewards(&farmer_id, &verified_nft_token_ids, true);
+            self.internal_update_farmer_storage(&farmer_id, storage_snapshot);
+        }
+    }
+
+    /// Credit a nft of seed with trait rules after reading its metadata,
+    /// return true to send the nft back to the owner.
+    #[private]
+    pub fn callback_post_nft_token_traits(
+        &mut self,
+        seed_id: SeedId,
+        sender_id: AccountId,
+        nft_contract_id: String,
+        nft_token_id: String
+    ) -> bool {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            ERR26_CALLBACK_POST_NFT_TOKEN_INVALID
+        );
+
+        let token = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => {
+                near_sdk::serde_json::from_slice::<Option<Token>>(&value).ok().flatten()
             },
-            PromiseResult::Successful(_) => {
-                env::log(
-                    format!(
-                        "{} withdraw {} nft from {}, Succeed.",
-                        sender_id, nft_token_id, nft_contract_id
-                    ).as_bytes()
-                );
+            PromiseResult::Failed => None,
+        };
+
+        if let Some(token) = token {
+            let rules = self.data().nft_trait_rules.get(&seed_id).unwrap_or_default();
+            let multiplier = get_nft_trait_multiplier(&rules, &token.metadata);
+            let contract_nft_token_id : ContractNFTTokenId = format!("{}{}{}", nft_contract_id, NFT_DELIMETER, nft_token_id);
+            if multiplier != NFT_TRAIT_MULTIPLIER_DENOM {
+                self.data_mut().nft_trait_multipliers.insert(&(seed_id.clone(), contract_nft_token_id.clone()), &multiplier);
             }
+
+            if self.internal_nft_deposit(&seed_id, &sender_id, &nft_contract_id, &nft_token_id) {
+                return false;
+            }
+            self.data_mut().nft_trait_multipliers.remove(&(seed_id.clone(), contract_nft_token_id));
         }
+        // drop the staking manager recorded for a position that was not opened
+        let farmer = self.get_farmer(&sender_id);
+        self.internal_update_seed_staker(&seed_id, farmer.get_ref());
+
+        env::log(
+            format!(
+                "{} deposit {} nft from {}, Callback Failed.",
+                sender_id, nft_token_id, nft_contract_id
+            ).as_bytes()
+        );
+        true
     }
     #[private]
     pub fn callback_post_withdraw_ft_seed(
@@ -245,24 +624,15 @@ impl Contract {
                     .as_bytes(),
                 );
                 // revert withdraw, equal to deposit, claim reward to update user reward_per_seed
-                self.internal_claim_user_reward_by_seed_id(&sender_id, &seed_id);
-                // **** update seed (new version)
-                let mut farm_seed = self.get_seed(&seed_id);
-                farm_seed.get_ref_mut().add_amount(amount);
-                self.data_mut().seeds.insert(&seed_id, &farm_seed);
-
-                let mut farmer = self.get_farmer(&sender_id);
-                farmer.get_ref_mut().add_seed(&seed_id, amount);
-                self.data_mut().farmers.insert(&sender_id, &farmer);
+                self.internal_seed_credit(&seed_id, &sender_id, amount);
             },
             PromiseResult::Successful(_) => {
-                env::log(
-                    format!(
-                        "{} withdraw {} ft seed with amount {}, Succeed.",
-                        sender_id, seed_id, amount,
-                    )
-                    .as_bytes(),
-                );
+                NearEvent::log_withdraw_seed(vec![SeedData {
+                    account_id: sender_id,
+                    seed_id,
+                    amount: amount.to_string(),
+                    contract_nft_token_id: None,
+                }]);
             }
         };
     }
@@ -292,25 +662,15 @@ impl Contract {
                     .as_bytes(),
                 );
                 // revert withdraw, equal to deposit, claim reward to update user reward_per_seed
-
-                self.internal_claim_user_reward_by_seed_id(&sender_id, &seed_id);
-                // **** update seed (new version)
-                let mut farm_seed = self.get_seed(&seed_id);
-                farm_seed.get_ref_mut().add_amount(amount);
-                self.data_mut().seeds.insert(&seed_id, &farm_seed);
-
-                let mut farmer = self.get_farmer(&sender_id);
-                farmer.get_ref_mut().add_seed(&seed_id, amount);
-                self.data_mut().farmers.insert(&sender_id, &farmer);
+                self.internal_seed_credit(&seed_id, &sender_id, amount);
             },
             PromiseResult::Successful(_) => {
-                env::log(
-                    format!(
-                        "{} withdraw {} mft seed with amount {}, Succeed.",
-                        sender_id, seed_id, amount,
-                    )
-                    .as_bytes(),
-                );
+                NearEvent::log_withdraw_seed(vec![SeedData {
+                    account_id: sender_id,
+                    seed_id,
+                    amount: amount.to_string(),
+                    contract_nft_token_id: None,
+                }]);
             }
         };
     }
@@ -320,6 +680,231 @@ impl Contract {
 /// Internal methods implementation.
 impl Contract {
 
+    /// take seed out of the farmer position and send it to the receiver,
+    /// the seed is given back to the farmer if the transfer fails.
+    fn internal_withdraw_seed_to(&mut self, seed_id: SeedId, sender_id: AccountId, receiver_id: AccountId, amount: Balance) {
+        let seed_contract_id: AccountId = seed_id.split(FT_INDEX_TAG).next().unwrap().to_string();
+
+        // update inner state
+        let seed_type = self.internal_seed_withdraw(&seed_id, &sender_id, amount);
+
+        match seed_type {
+            SeedType::FT => {
+                ext_fungible_token::ft_transfer(
+                    receiver_id,
+                    amount.into(),
+                    None,
+                    &seed_contract_id,
+                    1,  // one yocto near
+                    GAS_FOR_FT_TRANSFER,
+                )
+                    .then(ext_self::callback_post_withdraw_ft_seed(
+                        seed_id,
+                        sender_id,
+                        amount.into(),
+                        &env::current_account_id(),
+                        0,
+                        GAS_FOR_RESOLVE_TRANSFER,
+                    ));
+            },
+            SeedType::NFT => {
+                panic!("Use withdraw_nft for this");
+            },
+            SeedType::MFT => {
+                let (seed_contract_id, token_id) = parse_seed_id(&seed_id);
+                ext_multi_fungible_token::mft_transfer(
+                    wrap_mft_token_id(&token_id),
+                    receiver_id,
+                    amount.into(),
+                    None,
+                    &seed_contract_id,
+                    1,  // one yocto near
+                    GAS_FOR_FT_TRANSFER,
+                )
+                    .then(ext_self::callback_post_withdraw_mft_seed(
+                        seed_id,
+                        sender_id,
+                        amount.into(),
+                        &env::current_account_id(),
+                        0,
+                        GAS_FOR_RESOLVE_TRANSFER,
+                    ));
+            }
+        }
+    }
+
+    /// take nft out of the farmer position and send it to the receiver,
+    /// the nft is given back to the farmer if the transfer fails.
+    fn internal_withdraw_nft_to(
+        &mut self,
+        seed_id: SeedId,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        nft_contract_id: String,
+        nft_token_id: NFTTokenId
+    ) {
+        self.internal_nft_withdraw(&seed_id, &sender_id, &nft_contract_id, &nft_token_id);
+
+        ext_non_fungible_token::nft_transfer(
+            receiver_id,
+            nft_token_id.clone(),
+            None,
+            None,
+            &nft_contract_id,
+            1,
+            GAS_FOR_NFT_TRANSFER
+        )
+        .then(ext_self::callback_post_withdraw_nft(
+            seed_id,
+            sender_id,
+            nft_contract_id,
+            nft_token_id,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER
+        ));
+    }
+
+    /// move seed, or a nft with its balance equivalent, from one farmer to another,
+    /// an unregistered receiver is registered with storage deposit of the sender.
+    pub(crate) fn internal_transfer_seed(
+        &mut self,
+        seed_id: &SeedId,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: Balance,
+        contract_nft_token_id: Option<ContractNFTTokenId>
+    ) {
+        let paid_by_sender = self.get_farmer_wrapped(receiver_id).is_none();
+        if paid_by_sender {
+            self.internal_register_account_paid_by(sender_id, receiver_id);
+        }
+        self.internal_move_seed(seed_id, sender_id, receiver_id, amount, contract_nft_token_id);
+        self.assert_storage_usage(receiver_id);
+        if paid_by_sender {
+            self.assert_storage_usage(sender_id);
+        }
+    }
+
+    /// move seed, or a nft with its balance equivalent, from one farmer to another.
+    /// farmers are settled one after another so each is charged only for his own storage.
+    pub(crate) fn internal_move_seed(
+        &mut self,
+        seed_id: &SeedId,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: Balance,
+        contract_nft_token_id: Option<ContractNFTTokenId>
+    ) {
+        assert_ne!(sender_id, receiver_id, "{}", ERR64_TRANSFER_TO_SELF);
+        self.internal_assert_not_managed(sender_id, seed_id);
+        self.internal_assert_not_managed(receiver_id, seed_id);
+        let farm_seed = self.get_seed(seed_id);
+
+        // take the seed out of the sender
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
+        self.internal_claim_user_reward_by_seed_id(sender_id, seed_id);
+        let mut farmer = self.get_farmer(sender_id);
+        let amount = if let Some(contract_nft_token_id) = &contract_nft_token_id {
+            farmer.get_ref_mut().sub_nft(seed_id, contract_nft_token_id.clone());
+            self.internal_get_nft_balance_equivalent(seed_id, contract_nft_token_id).unwrap_or(0)
+        } else {
+            amount
+        };
+        let farmer_seed_remain = if amount > 0 || contract_nft_token_id.is_none() {
+            farmer.get_ref_mut().sub_seed(seed_id, amount)
+        } else {
+            farmer.get_ref().get_balance(seed_id)
+        };
+        farmer.get_ref_mut().delete_expired_locked_seed(seed_id);
+        if farmer_seed_remain == 0 {
+            // remove farmer rps of relative farm
+            for farm_id in farm_seed.get_ref().farms.iter() {
+                farmer.get_ref_mut().remove_rps(farm_id);
+            }
+        }
+        self.data_mut().farmers.insert(sender_id, &farmer);
+        self.internal_update_seed_staker(seed_id, farmer.get_ref());
+        self.internal_update_farmer_storage(sender_id, storage_snapshot);
+
+        // and credit it to the receiver, total amount of the seed is unchanged
+        let storage_snapshot = self.internal_farmer_storage_snapshot(receiver_id);
+        self.internal_claim_user_reward_by_seed_id(receiver_id, seed_id);
+        let mut farmer = self.get_farmer(receiver_id);
+        if let Some(contract_nft_token_id) = &contract_nft_token_id {
+            farmer.get_ref_mut().add_nft(seed_id, contract_nft_token_id.clone());
+            self.internal_move_nft_receipt(sender_id, receiver_id, seed_id, contract_nft_token_id);
+        }
+        farmer.get_ref_mut().add_seed(seed_id, amount);
+        self.data_mut().farmers.insert(receiver_id, &farmer);
+        self.internal_update_seed_staker(seed_id, farmer.get_ref());
+        self.internal_update_farmer_storage(receiver_id, storage_snapshot);
+
+        NearEvent::log_transfer_seed(TransferSeedData {
+            sender_id: sender_id.clone(),
+            receiver_id: receiver_id.clone(),
+            seed_id: seed_id.clone(),
+            amount: amount.to_string(),
+            contract_nft_token_id,
+        });
+    }
+
+    /// make sure a seed deposit can be credited to the farmer, registering him if the seed allows,
+    /// and record the staking manager of the position.
+    /// a position keeps the staking manager it was opened with until it is closed or released.
+    /// a staking manager of a new position is only taken from the farmer depositing for himself,
+    /// others have to use the one set by the farmer with `set_staking_manager`.
+    pub(crate) fn internal_prepare_seed_deposit(
+        &mut self,
+        farmer_id: &AccountId,
+        seed_id: &SeedId,
+        sender_id: &AccountId,
+        staking_manager_id: Option<AccountId>
+    ) -> Result<(), &'static str> {
+        if !self.internal_auto_register_account(farmer_id, seed_id) {
+            return Err(ERR10_ACC_NOT_REGISTERED);
+        }
+        let position_key = (farmer_id.clone(), seed_id.clone());
+        let current_staking_manager_id = self.data().staking_managers.get(&position_key);
+        if current_staking_manager_id == staking_manager_id {
+            return Ok(());
+        }
+        if self.internal_has_position(farmer_id, seed_id) || current_staking_manager_id.is_some() {
+            return Err(ERR62_STAKING_MANAGER_MISMATCH);
+        }
+        if sender_id != farmer_id {
+            return Err(ERR82_STAKING_MANAGER_NOT_APPROVED);
+        }
+        let storage_snapshot = self.internal_farmer_storage_snapshot(farmer_id);
+        self.data_mut().staking_managers.insert(&position_key, &staking_manager_id.unwrap());
+        self.internal_update_farmer_storage(farmer_id, storage_snapshot);
+        Ok(())
+    }
+
+    /// farmer holds seed or nfts of the seed
+    fn internal_has_position(&self, farmer_id: &AccountId, seed_id: &SeedId) -> bool {
+        let farmer = self.get_farmer(farmer_id);
+        let has_nfts = farmer.get_ref().nft_seeds.get(seed_id)
+            .map(|nft_contract_seed| !nft_contract_seed.is_empty())
+            .unwrap_or(false);
+        farmer.get_ref().get_balance(seed_id) > 0 || has_nfts
+    }
+
+    pub(crate) fn internal_assert_not_managed(&self, farmer_id: &AccountId, seed_id: &SeedId) {
+        assert!(
+            self.data().staking_managers.get(&(farmer_id.clone(), seed_id.clone())).is_none(),
+            "{}", ERR61_POSITION_HAS_STAKING_MANAGER
+        );
+    }
+
+    fn internal_assert_staking_manager(&self, farmer_id: &AccountId, seed_id: &SeedId, staking_manager_id: &AccountId) {
+        assert_eq!(
+            self.data().staking_managers.get(&(farmer_id.clone(), seed_id.clone())).as_ref(),
+            Some(staking_manager_id),
+            "{}", ERR63_NOT_STAKING_MANAGER
+        );
+    }
+
     #[inline]
     pub(crate) fn get_seed_and_upgrade(&mut self, seed_id: &String) -> VersionedFarmSeed {
         let orig = self.data().seeds.get(seed_id).expect(&format!("{}", ERR31_SEED_NOT_EXIST));
@@ -353,6 +938,153 @@ impl Contract {
         }
     }
 
+    /// balance equivalent of a nft in the seed, 
+    /// scaled by the trait multiplier recorded when it was staked.
+    pub(crate) fn internal_get_nft_balance_equivalent(
+        &self,
+        seed_id: &SeedId,
+        contract_nft_token_id: &ContractNFTTokenId
+    ) -> Option<Balance> {
+        let nft_balance = self.data().nft_balance_seeds.get(seed_id).unwrap();
+        get_nft_balance_equivalent(nft_balance, contract_nft_token_id.clone()).map(|balance| {
+            let multiplier = self.data().nft_trait_multipliers
+                .get(&(seed_id.clone(), contract_nft_token_id.clone()))
+                .unwrap_or(NFT_TRAIT_MULTIPLIER_DENOM);
+            balance * multiplier as u128 / NFT_TRAIT_MULTIPLIER_DENOM as u128
+        })
+    }
+
+    /// park the approval of a nft until it is staked, its storage is charged to the owner.
+    /// an approval parked by a previous owner of the nft is dropped first.
+    pub(crate) fn internal_park_nft_approval(&mut self, contract_nft_token_id: &ContractNFTTokenId, approval: NftApproval) {
+        self.internal_unpark_nft_approval(contract_nft_token_id);
+        let owner_id = approval.owner_id.clone();
+        let storage_snapshot = self.internal_farmer_storage_snapshot(&owner_id);
+        self.data_mut().nft_approvals.insert(contract_nft_token_id, &approval);
+        let mut approval_ids = self.data().nft_approval_ids.get(&owner_id).unwrap_or_default();
+        approval_ids.push(contract_nft_token_id.clone());
+        self.data_mut().nft_approval_ids.insert(&owner_id, &approval_ids);
+        self.internal_update_farmer_storage(&owner_id, storage_snapshot);
+        self.assert_storage_usage(&owner_id);
+    }
+
+    /// remove a parked approval, its storage is released to the owner.
+    pub(crate) fn internal_unpark_nft_approval(&mut self, contract_nft_token_id: &ContractNFTTokenId) -> Option<NftApproval> {
+        let approval = self.data().nft_approvals.get(contract_nft_token_id)?;
+        let storage_snapshot = self.internal_farmer_storage_snapshot(&approval.owner_id);
+        self.data_mut().nft_approvals.remove(contract_nft_token_id);
+        if let Some(mut approval_ids) = self.data().nft_approval_ids.get(&approval.owner_id) {
+            approval_ids.retain(|id| id != contract_nft_token_id);
+            if approval_ids.is_empty() {
+                self.data_mut().nft_approval_ids.remove(&approval.owner_id);
+            } else {
+                self.data_mut().nft_approval_ids.insert(&approval.owner_id, &approval_ids);
+            }
+        }
+        self.internal_update_farmer_storage(&approval.owner_id, storage_snapshot);
+        Some(approval)
+    }
+
+    /// a parked approval is stale when its nft is staked already
+    /// or its seed does not take it by `stake_approved_nfts` anymore.
+    fn internal_is_stale_nft_approval(&self, contract_nft_token_id: &ContractNFTTokenId, approval: &NftApproval) -> bool {
+        self.data().nft_approval_stakes.get(contract_nft_token_id).is_some()
+            || self.data().nft_receipt_owners.get(contract_nft_token_id).is_some()
+            || self.data().nft_trait_rules.get(&approval.seed_id).is_some()
+            || self.data().nft_balance_seeds.get(&approval.seed_id)
+                .and_then(|nft_balance| get_nft_balance_equivalent(nft_balance, contract_nft_token_id.clone()))
+                .is_none()
+    }
+
+    /// drop the stale approvals parked by the account, except the given ones.
+    pub(crate) fn internal_drop_stale_nft_approvals(&mut self, account_id: &AccountId, kept_ids: &[ContractNFTTokenId]) {
+        let approval_ids = self.data().nft_approval_ids.get(account_id).unwrap_or_default();
+        for contract_nft_token_id in approval_ids.iter().filter(|id| !kept_ids.contains(id)) {
+            let approval = self.data().nft_approvals.get(contract_nft_token_id).unwrap();
+            if self.internal_is_stale_nft_approval(contract_nft_token_id, &approval) {
+                self.internal_unpark_nft_approval(contract_nft_token_id);
+            }
+        }
+    }
+
+    /// keep the staker index of the seed in line with the farmer balance,
+    /// must be called each time the farmer seed balance is changed.
+    pub(crate) fn internal_update_seed_staker(&mut self, seed_id: &SeedId, farmer: &Farmer) {
+        let mut stakers = self.data().seed_stakers.get(seed_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKeys::SeedStakers {
+                seed_id: seed_id.clone(),
+            })
+        });
+        let balance = farmer.get_balance(seed_id);
+        self.internal_sync_receipt(seed_id, &farmer.farmer_id, balance);
+        // the staker index is shared, its storage is not charged to the farmer
+        let prev_storage = env::storage_usage();
+        if balance > 0 {
+            stakers.insert(&farmer.farmer_id);
+        } else {
+            stakers.remove(&farmer.farmer_id);
+        }
+        self.data_mut().seed_stakers.insert(seed_id, &stakers);
+        self.internal_track_shared_storage(prev_storage);
+
+        // closed position leaves no staking manager behind
+        let has_nfts = farmer.nft_seeds.get(seed_id).map(|nft_contract_seed| !nft_contract_seed.is_empty()).unwrap_or(false);
+        if balance == 0 && !has_nfts {
+            self.data_mut().staking_managers.remove(&(farmer.farmer_id.clone(), seed_id.clone()));
+        }
+    }
+
+    /// event data of a staked nft with its current balance equivalent
+    fn internal_nft_seed_data(
+        &self,
+        seed_id: &SeedId,
+        account_id: &AccountId,
+        contract_nft_token_id: &ContractNFTTokenId
+    ) -> SeedData {
+        SeedData {
+            account_id: account_id.clone(),
+            seed_id: seed_id.clone(),
+            amount: self.internal_get_nft_balance_equivalent(seed_id, contract_nft_token_id).unwrap_or(0).to_string(),
+            contract_nft_token_id: Some(contract_nft_token_id.clone()),
+        }
+    }
+
+    /// Reprice staked nfts of the farmer with the current nft_balance of the seed,
+    /// must be called right after the farmer rewards of the seed are claimed.
+    pub(crate) fn internal_reprice_farmer_nft_seed(&mut self, sender_id: &AccountId, seed_id: &SeedId) {
+        let mut farmer = self.get_farmer(sender_id);
+        let staked_nfts = match farmer.get_ref().nft_seeds.get(seed_id) {
+            Some(nft_contract_seed) => nft_contract_seed.to_vec(),
+            None => return,
+        };
+        let expected_balance: Balance = staked_nfts.iter()
+            .map(|contract_nft_token_id| self.internal_get_nft_balance_equivalent(seed_id, contract_nft_token_id).unwrap_or(0))
+            .sum();
+        let current_balance = farmer.get_ref().get_balance(seed_id);
+        if expected_balance == current_balance {
+            return;
+        }
+
+        let mut farm_seed = self.get_seed(seed_id);
+        if expected_balance > current_balance {
+            farmer.get_ref_mut().add_seed(seed_id, expected_balance - current_balance);
+            farm_seed.get_ref_mut().add_amount(expected_balance - current_balance);
+        } else {
+            farmer.get_ref_mut().sub_seed(seed_id, current_balance - expected_balance);
+            farm_seed.get_ref_mut().sub_amount(current_balance - expected_balance);
+        }
+        self.data_mut().farmers.insert(sender_id, &farmer);
+        self.internal_update_seed_staker(seed_id, farmer.get_ref());
+        self.data_mut().seeds.insert(seed_id, &farm_seed);
+
+        NearEvent::log_reprice_nft_seed(RepriceNftSeedData {
+            account_id: sender_id.clone(),
+            seed_id: seed_id.clone(),
+            old_amount: current_balance.to_string(),
+            new_amount: expected_balance.to_string(),
+        });
+    }
+
     #[inline]
     pub(crate) fn is_seed_type(&self, seed_id: &String, seed_type: SeedType) -> bool {
         if let Some(farm_seed) = self.data().seeds.get(seed_id) {
@@ -369,6 +1101,7 @@ impl Contract {
         sender_id: &AccountId, 
         amount: Balance, 
         _seed_type: SeedType) {
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
 
         // first claim all reward of the user for this seed farms
         // to update user reward_per_seed in each farm
@@ -384,6 +1117,14 @@ impl Contract {
 
         farmer.get_ref_mut().add_seed(&seed_id, amount);
         self.data_mut().farmers.insert(sender_id, &farmer);
+        self.internal_update_seed_staker(seed_id, farmer.get_ref());
+
+        NearEvent::log_deposit_seed(vec![SeedData {
+            account_id: sender_id.clone(),
+            seed_id: seed_id.clone(),
+            amount: amount.to_string(),
+            contract_nft_token_id: None,
+        }]);
 
         let mut reward_tokens: Vec<AccountId> = vec![];
         for farm_id in farm_seed.get_ref().farms.iter() {
@@ -395,6 +1136,7 @@ impl Contract {
                 reward_tokens.push(reward_token);
             }
         };
+        self.internal_update_farmer_storage(sender_id, storage_snapshot);
     }
 
     pub(crate) fn internal_seed_redeposit(
@@ -403,6 +1145,7 @@ impl Contract {
         sender_id: &AccountId,
         _is_deposit_seed_reward: bool,
     ) {
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
         self.internal_claim_user_reward_by_seed_id(&sender_id, seed_id);
 
         let mut farm_seed = self.get_seed(seed_id);
@@ -420,6 +1163,16 @@ impl Contract {
 
         farmer.get_ref_mut().add_seed(&seed_id, amount);
         self.data_mut().farmers.insert(sender_id, &farmer);
+        self.internal_update_seed_staker(seed_id, farmer.get_ref());
+
+        if amount > 0 {
+            NearEvent::log_deposit_seed(vec![SeedData {
+                account_id: sender_id.clone(),
+                seed_id: seed_id.clone(),
+                amount: amount.to_string(),
+                contract_nft_token_id: None,
+            }]);
+        }
 
         let mut reward_tokens: Vec<AccountId> = vec![];
 
@@ -432,6 +1185,23 @@ impl Contract {
                 reward_tokens.push(reward_token);
             }
         };
+        self.internal_update_farmer_storage(sender_id, storage_snapshot);
+    }
+
+    /// Give ft seed back to the farmer whose withdraw failed, without withdrawing his rewards.
+    pub(crate) fn internal_seed_credit(&mut self, seed_id: &SeedId, sender_id: &AccountId, amount: Balance) {
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
+        self.internal_claim_user_reward_by_seed_id(sender_id, seed_id);
+        // **** update seed (new version)
+        let mut farm_seed = self.get_seed(seed_id);
+        farm_seed.get_ref_mut().add_amount(amount);
+        self.data_mut().seeds.insert(seed_id, &farm_seed);
+
+        let mut farmer = self.get_farmer(sender_id);
+        farmer.get_ref_mut().add_seed(seed_id, amount);
+        self.data_mut().farmers.insert(sender_id, &farmer);
+        self.internal_update_seed_staker(seed_id, farmer.get_ref());
+        self.internal_update_farmer_storage(sender_id, storage_snapshot);
     }
 
     fn internal_validate_lock_ft_balance_duration(&self, duration: &u32){
@@ -452,6 +1222,7 @@ impl Contract {
         seed_id: &SeedId, 
         sender_id: &AccountId, 
         amount: Balance) -> SeedType {
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
 
         // first claim all reward of the user for this seed farms
         // to update user reward_per_seed in each farm
@@ -473,6 +1244,7 @@ impl Contract {
             }
         }
         self.data_mut().farmers.insert(sender_id, &farmer);
+        self.internal_update_seed_staker(seed_id, farmer.get_ref());
         self.data_mut().seeds.insert(seed_id, &farm_seed);
 
         let mut reward_tokens: Vec<AccountId> = vec![];
@@ -485,6 +1257,7 @@ impl Contract {
                 reward_tokens.push(reward_token);
             }
         };
+        self.internal_update_farmer_storage(sender_id, storage_snapshot);
 
         farm_seed.get_ref().seed_type.clone()
     }
@@ -496,22 +1269,33 @@ impl Contract {
         nft_contract_id: &String,
         nft_token_id: &String,
     ) -> bool {
-        let mut farm_seed = self.get_seed(seed_id);
-
-        assert_eq!(farm_seed.get_ref().seed_type, SeedType::NFT, "Cannot deposit NFT to this farm");
+        assert_eq!(self.get_seed(seed_id).get_ref().seed_type, SeedType::NFT, "Cannot deposit NFT to this farm");
 
         // update farmer seed
         let contract_nft_token_id = format!("{}{}{}", nft_contract_id, NFT_DELIMETER, nft_token_id);
-        let nft_balance = self.data().nft_balance_seeds.get(&seed_id).unwrap();
-        return if let Some(nft_balance_equivalent) = get_nft_balance_equivalent(nft_balance, contract_nft_token_id.clone()) {
+        return if let Some(nft_balance_equivalent) = self.internal_get_nft_balance_equivalent(seed_id, &contract_nft_token_id) {
+            let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
             // first claim all reward of the user for this seed farms
             // to update user reward_per_seed in each farm
             self.internal_claim_user_reward_by_seed_id(sender_id, seed_id);
+            let mut farm_seed = self.get_seed(seed_id);
             let mut farmer = self.get_farmer(sender_id);
-            farmer.get_ref_mut().add_nft(seed_id, contract_nft_token_id);
+            farmer.get_ref_mut().add_nft(seed_id, contract_nft_token_id.clone());
+            // nfts of approval staking seeds stay in the owner wallet and get no receipt
+            if !self.data().nft_approval_stake_seeds.contains(seed_id) {
+                self.internal_mint_nft_receipt(sender_id, seed_id, &contract_nft_token_id);
+            }
 
             farmer.get_ref_mut().add_seed(seed_id, nft_balance_equivalent);
             self.data_mut().farmers.insert(sender_id, &farmer);
+            self.internal_update_seed_staker(seed_id, farmer.get_ref());
+
+            NearEvent::log_deposit_seed(vec![SeedData {
+                account_id: sender_id.clone(),
+                seed_id: seed_id.clone(),
+                amount: nft_balance_equivalent.to_string(),
+                contract_nft_token_id: Some(contract_nft_token_id),
+            }]);
 
             // **** update seed (new version)
             farm_seed.get_ref_mut().add_amount(nft_balance_equivalent);
@@ -527,6 +1311,7 @@ impl Contract {
                     reward_tokens.push(reward_token);
                 }
             };
+            self.internal_update_farmer_storage(sender_id, storage_snapshot);
 
             true
         } else {
@@ -541,18 +1326,36 @@ impl Contract {
         nft_contract_id: &String,
         nft_token_id: &String
     ) -> ContractNFTTokenId {
+        let contract_nft_token_id : ContractNFTTokenId = format!("{}{}{}", nft_contract_id, NFT_DELIMETER, nft_token_id);
+        self.internal_nfts_withdraw(seed_id, sender_id, std::slice::from_ref(&contract_nft_token_id));
+        contract_nft_token_id
+    }
+
+    fn internal_nfts_withdraw(
+        &mut self,
+        seed_id: &String,
+        sender_id: &AccountId,
+        contract_nft_token_ids: &[ContractNFTTokenId]
+    ) {
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
         self.internal_claim_user_reward_by_seed_id(sender_id, seed_id);
 
         let mut farm_seed = self.get_seed(seed_id);
         let mut farmer = self.get_farmer(sender_id);
 
-        // sub nft
-        let contract_nft_token_id : ContractNFTTokenId = format!("{}{}{}", nft_contract_id, NFT_DELIMETER, nft_token_id);
-        farmer.get_ref_mut().sub_nft(seed_id, contract_nft_token_id.clone());
-        let nft_balance = self.data().nft_balance_seeds.get(&seed_id).unwrap();
-        let nft_balance_equivalent: Balance = get_nft_balance_equivalent(nft_balance, contract_nft_token_id.clone()).unwrap();
+        // sub nfts, the ones removed from nft_balance of the seed have no weight left
+        let mut nft_balance_equivalent: Balance = 0;
+        for contract_nft_token_id in contract_nft_token_ids.iter() {
+            farmer.get_ref_mut().sub_nft(seed_id, contract_nft_token_id.clone());
+            self.internal_burn_nft_receipt(contract_nft_token_id);
+            nft_balance_equivalent += self.internal_get_nft_balance_equivalent(seed_id, contract_nft_token_id).unwrap_or(0);
+        }
 
-        let farmer_seed_remain = farmer.get_ref_mut().sub_seed(seed_id, nft_balance_equivalent);
+        let farmer_seed_remain = if nft_balance_equivalent > 0 {
+            farmer.get_ref_mut().sub_seed(seed_id, nft_balance_equivalent)
+        } else {
+            farmer.get_ref().get_balance(seed_id)
+        };
 
         // calculate farm_seed after multiplier get removed
         farm_seed.get_ref_mut().sub_amount(nft_balance_equivalent);
@@ -565,6 +1368,7 @@ impl Contract {
         }
 
         self.data_mut().farmers.insert(sender_id, &farmer);
+        self.internal_update_seed_staker(seed_id, farmer.get_ref());
         self.data_mut().seeds.insert(seed_id, &farm_seed);
 
         let mut reward_tokens: Vec<AccountId> = vec![];
@@ -577,8 +1381,217 @@ impl Contract {
                 reward_tokens.push(reward_token);
             }
         };
+        self.internal_update_farmer_storage(sender_id, storage_snapshot);
+    }
 
-        contract_nft_token_id
+    /// nfts of the farmer staked by approval in the seed
+    pub(crate) fn internal_get_approval_staked_nfts(&self, farmer_id: &AccountId, seed_id: &SeedId) -> Vec<ContractNFTTokenId> {
+        let farmer = match self.get_farmer_wrapped(farmer_id) {
+            Some(farmer) => farmer,
+            None => return vec![],
+        };
+        match farmer.get_ref().nft_seeds.get(seed_id) {
+            Some(nft_contract_seed) => nft_contract_seed.iter()
+                .filter(|contract_nft_token_id| self.internal_is_approval_staked(farmer_id, seed_id, contract_nft_token_id))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    pub(crate) fn internal_is_approval_staked(
+        &self,
+        farmer_id: &AccountId,
+        seed_id: &SeedId,
+        contract_nft_token_id: &ContractNFTTokenId
+    ) -> bool {
+        self.data().nft_approval_stakes.get(contract_nft_token_id)
+            .map(|stake| &stake.owner_id == farmer_id && &stake.seed_id == seed_id)
+            .unwrap_or(false)
+    }
+
+    /// Check approval stake of a nft now owned by `owner_id`,
+    /// slash it if it belongs to previous owner, else return the stake.
+    pub(crate) fn internal_check_approval_stake(
+        &mut self,
+        contract_nft_token_id: &ContractNFTTokenId,
+        owner_id: &AccountId
+    ) -> Option<NftApproval> {
+        let stake = self.data().nft_approval_stakes.get(contract_nft_token_id)?;
+        if &stake.owner_id == owner_id {
+            return Some(stake);
+        }
+        self.internal_evict_nfts(&stake.seed_id, &stake.owner_id, std::slice::from_ref(contract_nft_token_id));
+        None
+    }
+
+    /// Query the nft contracts for owner of approval staked nfts,
+    /// result goes to `callback_post_verify_approval_stakes`.
+    pub(crate) fn internal_verify_approval_stakes(
+        &mut self,
+        seed_id: SeedId,
+        sender_id: AccountId,
+        contract_nft_token_ids: Vec<ContractNFTTokenId>,
+        is_withdraw: bool
+    ) {
+        let mut queries: Option<Promise> = None;
+        for contract_nft_token_id in contract_nft_token_ids.iter() {
+            let (nft_contract_id, nft_token_id) = parse_contract_nft_token_id(contract_nft_token_id);
+            let query = ext_non_fungible_token::nft_token(
+                nft_token_id,
+                &nft_contract_id,
+                0,
+                GAS_FOR_NFT_TOKEN
+            );
+            queries = Some(match queries {
+                Some(promise) => promise.and(query),
+                None => query,
+            });
+        }
+        queries.unwrap().then(ext_self::callback_post_verify_approval_stakes(
+            seed_id,
+            sender_id,
+            contract_nft_token_ids,
+            is_withdraw,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_NFT_BATCH
+        ));
+    }
+
+    /// Remove staked nfts no longer held by this contract or by the farmer for approval stakes,
+    /// their unclaimed rewards are forfeited to beneficiary of each farm.
+    pub(crate) fn internal_evict_nfts(
+        &mut self,
+        seed_id: &SeedId,
+        farmer_id: &AccountId,
+        contract_nft_token_ids: &[ContractNFTTokenId]
+    ) {
+        let storage_snapshot = self.internal_farmer_storage_snapshot(farmer_id);
+        // weight not repriced yet is bounded by the farmer balance
+        let mut farmer_balance = self.get_farmer(farmer_id).get_ref().get_balance(seed_id);
+        let mut evicted_balance: Balance = 0;
+        let mut evict_nft_data: Vec<EvictNftData> = vec![];
+        for contract_nft_token_id in contract_nft_token_ids.iter() {
+            let nft_balance_equivalent = self.internal_get_nft_balance_equivalent(seed_id, contract_nft_token_id)
+                .unwrap_or(0)
+                .min(farmer_balance);
+            farmer_balance -= nft_balance_equivalent;
+            evicted_balance += nft_balance_equivalent;
+            evict_nft_data.push(EvictNftData {
+                account_id: farmer_id.clone(),
+                seed_id: seed_id.clone(),
+                contract_nft_token_id: contract_nft_token_id.clone(),
+                amount: nft_balance_equivalent.to_string(),
+            });
+        }
+        if evicted_balance > 0 {
+            self.internal_forfeit_user_reward_by_seed_id(farmer_id, seed_id, evicted_balance);
+        }
+        self.internal_release_held_rewards(farmer_id, contract_nft_token_ids, false);
+
+        let mut farm_seed = self.get_seed(seed_id);
+        let mut farmer = self.get_farmer(farmer_id);
+        for contract_nft_token_id in contract_nft_token_ids.iter() {
+            farmer.get_ref_mut().sub_nft(seed_id, contract_nft_token_id.clone());
+            self.internal_burn_nft_receipt(contract_nft_token_id);
+            self.data_mut().nft_approval_stakes.remove(contract_nft_token_id);
+            self.data_mut().nft_trait_multipliers.remove(&(seed_id.clone(), contract_nft_token_id.clone()));
+        }
+
+        if evicted_balance > 0 {
+            let farmer_seed_remain = farmer.get_ref_mut().sub_seed(seed_id, evicted_balance);
+            farm_seed.get_ref_mut().sub_amount(evicted_balance);
+            if farmer_seed_remain == 0 {
+                // remove farmer rps of relative farm
+                for farm_id in farm_seed.get_ref().farms.iter() {
+                    farmer.get_ref_mut().remove_rps(farm_id);
+                }
+            }
+        }
+
+        self.data_mut().farmers.insert(farmer_id, &farmer);
+        self.internal_update_seed_staker(seed_id, farmer.get_ref());
+        self.data_mut().seeds.insert(seed_id, &farm_seed);
+        self.internal_update_farmer_storage(farmer_id, storage_snapshot);
+
+        NearEvent::log_evict_nft(evict_nft_data);
+    }
+
+    /// Query the nft contracts for owner of a page of staked nfts of the farmer,
+    /// result goes to `callback_post_verify_staked_nfts`.
+    pub(crate) fn internal_verify_staked_nfts(
+        &mut self,
+        farmer_id: AccountId,
+        seed_id: SeedId,
+        from_index: u64,
+        limit: u64
+    ) {
+        let farmer = self.get_farmer(&farmer_id);
+        let contract_nft_token_ids: Vec<ContractNFTTokenId> = match farmer.get_ref().nft_seeds.get(&seed_id) {
+            Some(nft_contract_seed) => nft_contract_seed.as_vector().iter()
+                .skip(from_index as usize)
+                .take(limit.min(MAX_NFT_BATCH_SIZE as u64) as usize)
+                .collect(),
+            None => vec![],
+        };
+        if contract_nft_token_ids.is_empty() {
+            return;
+        }
+
+        let mut queries: Option<Promise> = None;
+        for contract_nft_token_id in contract_nft_token_ids.iter() {
+            let (nft_contract_id, nft_token_id) = parse_contract_nft_token_id(contract_nft_token_id);
+            let query = ext_non_fungible_token::nft_token(
+                nft_token_id,
+                &nft_contract_id,
+                0,
+                GAS_FOR_NFT_TOKEN
+            );
+            queries = Some(match queries {
+                Some(promise) => promise.and(query),
+                None => query,
+            });
+        }
+        queries.unwrap().then(ext_self::callback_post_verify_staked_nfts(
+            seed_id,
+            farmer_id,
+            contract_nft_token_ids,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_NFT_BATCH
+        ));
+    }
+
+    /// Give nfts held by this contract to the farmer with one reward settlement,
+    /// nfts without balance equivalent in the seed are kept withdrawable with no weight.
+    pub(crate) fn internal_nfts_credit(
+        &mut self,
+        seed_id: &SeedId,
+        sender_id: &AccountId,
+        contract_nft_token_ids: &[ContractNFTTokenId]
+    ) {
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
+        self.internal_claim_user_reward_by_seed_id(sender_id, seed_id);
+
+        let mut farmer = self.get_farmer(sender_id);
+        let mut farm_seed = self.get_seed(seed_id);
+
+        let mut nft_balance_equivalent: Balance = 0;
+        for contract_nft_token_id in contract_nft_token_ids.iter() {
+            nft_balance_equivalent += self.internal_get_nft_balance_equivalent(seed_id, contract_nft_token_id).unwrap_or(0);
+            farmer.get_ref_mut().add_nft(seed_id, contract_nft_token_id.clone());
+            self.internal_mint_nft_receipt(sender_id, seed_id, contract_nft_token_id);
+        }
+        if nft_balance_equivalent > 0 {
+            farmer.get_ref_mut().add_seed(seed_id, nft_balance_equivalent);
+
+            // **** update seed (new version)
+            farm_seed.get_ref_mut().add_amount(nft_balance_equivalent);
+        }
+        self.data_mut().farmers.insert(sender_id, &farmer);
+        self.internal_update_seed_staker(seed_id, farmer.get_ref());
+        self.data_mut().seeds.insert(seed_id, &farm_seed);
+        self.internal_update_farmer_storage(sender_id, storage_snapshot);
     }
 
 
@@ -588,6 +1601,7 @@ impl Contract {
 
         assert!(self.is_seed_type(&seed_id, SeedType::FT), "{}", ERR36_SEED_TYPE_IS_NOT_FT);
 
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
         let mut farmer = self.get_farmer(&sender_id);
         
         let user_balance = &farmer.get_ref().get_available_balance(&seed_id);
@@ -599,6 +1613,7 @@ impl Contract {
 
         farmer.get_ref_mut().add_or_create_locked_seed(&seed_id, *amount, current_block_time, ended_at);
         self.data_mut().farmers.insert(&sender_id, &farmer);
+        self.internal_update_farmer_storage(sender_id, storage_snapshot);
     }
 
 
@@ -609,12 +1624,14 @@ impl Contract {
 
         assert!(self.is_seed_type(&seed_id, SeedType::FT), "{}", ERR36_SEED_TYPE_IS_NOT_FT);
 
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
         let mut farmer = self.get_farmer(&sender_id);
         if let Some(locked_seed) = farmer.get_ref().get_locked_seed_with_retention_wrapped(seed_id){
             assert!(locked_seed.ended_at <= current_block_time, "{}", ERR39_USER_CANNOT_UNLOCK_SEED);
 
             farmer.get_ref_mut().sub_locked_seed_balance(seed_id, *amount);
             self.data_mut().farmers.insert(&sender_id, &farmer);
+            self.internal_update_farmer_storage(sender_id, storage_snapshot);
         } else {
             farmer.get_ref_mut().delete_expired_locked_seed(seed_id);
             self.data_mut().farmers.insert(&sender_id, &farmer);
@@ -4,12 +4,18 @@ pub const ERR11_INSUFFICIENT_STORAGE: &str = "E11: insufficient $NEAR storage de
 pub const ERR12_STORAGE_UNREGISTER_REWARDS_NOT_EMPTY: &str = "E12: still has rewards when unregister";
 pub const ERR13_STORAGE_UNREGISTER_SEED_NOT_EMPTY: &str = "E13: still has staked seed when unregister";
 pub const ERR14_ACC_ALREADY_REGISTERED: &str = "E14: account already registered";
+pub const ERR15_INSUFFICIENT_STORAGE_SPONSORSHIP: &str = "E15: storage sponsorship balance is not enough";
+pub const ERR16_TOO_MANY_POSITIONS_TO_UNREGISTER: &str = "E16: too many rewards, seeds and nfts to send back, withdraw some first";
+pub const ERR17_STORAGE_UNREGISTER_NFTS_NOT_EMPTY: &str = "E17: still has staked nft when unregister";
 
 // Reward errors //
 pub const ERR21_TOKEN_NOT_REG: &str = "E21: token not registered";
 pub const ERR22_NOT_ENOUGH_TOKENS: &str = "E22: not enough tokens in deposit";
 
 pub const ERR25_CALLBACK_POST_WITHDRAW_INVALID: &str = "E25: expected 1 promise result from withdraw";
+pub const ERR28_CALLBACK_POST_STORAGE_TRANSFER_INVALID: &str = "E28: expected 1 promise result from storage transfer";
+pub const ERR26_CALLBACK_POST_NFT_TOKEN_INVALID: &str = "E26: expected 1 promise result from nft_token";
+pub const ERR27_CALLBACK_POST_NFT_BATCH_INVALID: &str = "E27: expected 1 promise result per nft in batch";
 
 // Seed errors //
 pub const ERR31_SEED_NOT_EXIST: &str = "E31: seed not exist";
@@ -29,9 +35,59 @@ pub const ERR41_FARM_NOT_EXIST: &str = "E41: farm not exist";
 pub const ERR42_INVALID_FARM_ID: &str = "E42: invalid farm id";
 pub const ERR43_INVALID_FARM_STATUS: &str = "E43: invalid farm status";
 pub const ERR44_INVALID_FARM_REWARD: &str = "E44: invalid reward token for this farm";
+pub const ERR45_INVALID_FARM_CHECKPOINT_CAPACITY: &str = "E45: farm checkpoint capacity is too large";
 
 // nft errors //
 pub const ERR51_SUB_NFT_IS_NOT_EXIST: &str = "E51: sub nft is not exist";
+pub const ERR52_INVALID_NFT_TRAIT_MULTIPLIER: &str = "E52: nft trait multiplier should be positive";
+pub const ERR53_INVALID_NFT_BATCH_SIZE: &str = "E53: nft batch is empty or too large";
+pub const ERR54_NFT_APPROVAL_NOT_EXIST: &str = "E54: nft approval not exist";
+pub const ERR55_INVALID_CONTRACT_NFT_TOKEN_ID: &str = "E55: invalid contract nft token id";
+pub const ERR56_APPROVAL_STAKED_SEED_CLAIM: &str = "E56: seed has approval staked nfts, claim it by claim_reward_by_seed";
+pub const ERR57_APPROVAL_STAKED_NFT_IN_BATCH: &str = "E57: approval staked nft can only be withdrawn by withdraw_nft";
+pub const ERR58_TOO_MANY_APPROVAL_STAKED_NFTS: &str = "E58: too many approval staked nfts in the seed";
+pub const ERR59_NFT_ALREADY_STAKED: &str = "E59: nft is already staked";
+
+// delegation errors //
+pub const ERR60_INVALID_SEED_DEPOSIT_MSG: &str = "E60: invalid seed deposit msg";
+pub const ERR61_POSITION_HAS_STAKING_MANAGER: &str = "E61: seed position is withdrawn by its staking manager";
+pub const ERR62_STAKING_MANAGER_MISMATCH: &str = "E62: staking manager differs from the one of the seed position";
+pub const ERR63_NOT_STAKING_MANAGER: &str = "E63: predecessor is not the staking manager of the seed position";
+pub const ERR82_STAKING_MANAGER_NOT_APPROVED: &str = "E82: staking manager is not set by the farmer of the seed position";
+
+// position transfer errors //
+pub const ERR64_TRANSFER_TO_SELF: &str = "E64: seed position can not be transferred to its own farmer";
+pub const ERR65_APPROVAL_STAKED_NFT_TRANSFER: &str = "E65: approval staked nft can not be transferred";
+pub const ERR66_NFT_SEED_TRANSFER: &str = "E66: nft seed is transferred by transfer_staked_nft";
+
+// receipt token errors //
+pub const ERR67_RECEIPT_TOKEN_NOT_ENABLED: &str = "E67: seed has no receipt token";
+pub const ERR68_NFT_SEED_RECEIPT_TOKEN: &str = "E68: nft seed can not have a receipt token";
+pub const ERR69_RECEIPT_TRANSFER_ZERO_AMOUNT: &str = "E69: receipt transfer amount should be positive";
+pub const ERR70_NOT_RECEIPT_TOKEN: &str = "E70: predecessor is not the receipt token of the seed";
+pub const ERR71_NFT_RECEIPT_NOT_EXIST: &str = "E71: staked nft receipt not exist";
+pub const ERR72_NOT_NFT_RECEIPT_OWNER: &str = "E72: predecessor does not own the staked nft receipt";
+pub const ERR73_NFT_RECEIPT_RESOLVE_TRANSFER_INVALID: &str = "E73: expected 1 promise result from nft_on_transfer";
+pub const ERR90_NFT_RECEIPT_TRANSFER_TO_SELF: &str = "E90: staked nft receipt can not be transferred to this contract";
+
+// compound errors //
+pub const ERR74_COMPOUND_TARGET_NOT_FT_SEED: &str = "E74: compound target should be a ft seed";
+pub const ERR75_NOTHING_TO_COMPOUND: &str = "E75: no reward of the target seed token to compound";
+pub const ERR76_COMPOUND_BELOW_MIN_AMOUNT: &str = "E76: compounded amount below min_amount";
+
+// zap errors //
+pub const ERR77_ZAP_TARGET_NOT_MFT_SEED: &str = "E77: zap target should be a seed of pool shares";
+pub const ERR78_INVALID_ZAP_POOL_TOKENS: &str = "E78: pool tokens should be the two tokens of the pool in pool order, one of them the reward token";
+pub const ERR79_NOTHING_TO_ZAP: &str = "E79: no reward of the token to zap";
+pub const ERR80_INVALID_ZAP_SWAP_AMOUNT: &str = "E80: swap amount should be positive and less than the zapped reward";
+pub const ERR81_CALLBACK_POST_ZAP_INVALID: &str = "E81: expected 1 promise result from ref exchange";
+pub const ERR83_ZAP_TOKEN_NOT_REGISTERED: &str = "E83: pool token is not registered for zaps through the exchange";
+pub const ERR84_ZAP_IN_PROGRESS: &str = "E84: a zap through the exchange is in progress";
+pub const ERR85_NO_ZAP_REFUND: &str = "E85: no zap refund of the token";
+pub const ERR86_NO_ZAP_IN_PROGRESS: &str = "E86: no zap through the exchange in progress";
+pub const ERR87_ZAP_NOT_STALLED: &str = "E87: the zap through the exchange is still running";
+pub const ERR88_ZAP_STALLED: &str = "E88: the zap through the exchange is stalled, resume it instead";
+pub const ERR89_STORAGE_UNREGISTER_ZAP_REFUNDS_NOT_EMPTY: &str = "E89: still has zap refunds when unregister";
 
 // validation errors //
 pub const ERR401_LOCK_FT_BALANCE_DURATION_IS_NOT_VALID: &str = "E401: lock ft balance duration is not valid";
@@ -8,6 +8,7 @@ use serde_with::skip_serializing_none;
 #[serde(rename_all = "snake_case")]
 pub enum NearEvent {
     ParasFarming(ParasFarmingEvent),
+    Nep171(Nep171Event),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -23,7 +24,21 @@ pub struct ParasFarmingEvent {
 #[allow(clippy::enum_variant_names)]
 pub enum ParasFarmingEventKind {
     LockFtBalance(Vec<LockFTBalanceData>),
-    UnlockFtBalance(Vec<UnlockFTBalanceData>)
+    UnlockFtBalance(Vec<UnlockFTBalanceData>),
+    EvictNft(Vec<EvictNftData>),
+    DepositSeed(Vec<SeedData>),
+    WithdrawSeed(Vec<SeedData>),
+    RepriceNftSeed(Vec<RepriceNftSeedData>),
+    ClaimReward(Vec<FarmRewardData>),
+    ForfeitReward(Vec<FarmRewardData>),
+    WithdrawReward(Vec<WithdrawRewardData>),
+    CreateFarm(Vec<CreateFarmData>),
+    AddReward(Vec<AddRewardData>),
+    ClearFarm(Vec<ClearFarmData>),
+    RefundDeposit(Vec<RefundDepositData>),
+    TransferSeed(Vec<TransferSeedData>),
+    Compound(Vec<CompoundData>),
+    Zap(Vec<ZapData>),
 }
 
 #[skip_serializing_none]
@@ -48,6 +63,161 @@ pub struct UnlockFTBalanceData {
     pub ended_at: Option<u32>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EvictNftData {
+    pub account_id: String,
+    pub seed_id: String,
+    pub contract_nft_token_id: String,
+    pub amount: String,
+}
+
+/// seed deposited or withdrawn, amount of a nft is its balance equivalent
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SeedData {
+    pub account_id: String,
+    pub seed_id: String,
+    pub amount: String,
+    pub contract_nft_token_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RepriceNftSeedData {
+    pub account_id: String,
+    pub seed_id: String,
+    pub old_amount: String,
+    pub new_amount: String,
+}
+
+/// reward of a farm claimed by the farmer, or forfeited to beneficiary of the farm
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FarmRewardData {
+    pub account_id: String,
+    pub farm_id: String,
+    pub reward_token: String,
+    pub amount: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WithdrawRewardData {
+    pub account_id: String,
+    pub reward_token: String,
+    pub amount: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateFarmData {
+    pub farm_id: String,
+    pub seed_id: String,
+    pub reward_token: String,
+    pub start_at: u32,
+    pub reward_per_session: String,
+    pub session_interval: u32,
+    pub min_deposit: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AddRewardData {
+    pub account_id: String,
+    pub farm_id: String,
+    pub reward_token: String,
+    pub amount: String,
+    pub undistributed: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ClearFarmData {
+    pub farm_id: String,
+    pub seed_id: String,
+    pub reward_token: String,
+    pub beneficiary_reward: String,
+}
+
+/// claimed reward staked into the seed of the same token
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CompoundData {
+    pub account_id: String,
+    pub seed_id: String,
+    pub amount: String,
+}
+
+/// claimed reward swapped and added as liquidity, the pool shares staked into the seed
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ZapData {
+    pub account_id: String,
+    pub seed_id: String,
+    pub reward_token: String,
+    pub amount: String,
+    pub shares: String,
+}
+
+/// NEP-171 events of receipts of staked nfts
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Nep171Event {
+    pub version: String,
+    #[serde(flatten)]
+    pub event_kind: Nep171EventKind,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+#[allow(clippy::enum_variant_names)]
+pub enum Nep171EventKind {
+    NftMint(Vec<NftMintData>),
+    NftTransfer(Vec<NftTransferData>),
+    NftBurn(Vec<NftBurnData>),
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NftMintData {
+    pub owner_id: String,
+    pub token_ids: Vec<String>,
+    pub memo: Option<String>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NftTransferData {
+    pub authorized_id: Option<String>,
+    pub old_owner_id: String,
+    pub new_owner_id: String,
+    pub token_ids: Vec<String>,
+    pub memo: Option<String>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NftBurnData {
+    pub authorized_id: Option<String>,
+    pub owner_id: String,
+    pub token_ids: Vec<String>,
+    pub memo: Option<String>,
+}
+
+/// seed deposit sent back to its owner instead of being staked
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RefundDepositData {
+    pub account_id: String,
+    pub seed_id: String,
+    pub amount: String,
+    pub contract_nft_token_id: Option<String>,
+    pub reason: String,
+}
+
+/// staked seed moved between farmers, amount of a nft is its balance equivalent
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TransferSeedData {
+    pub sender_id: String,
+    pub receiver_id: String,
+    pub seed_id: String,
+    pub amount: String,
+    pub contract_nft_token_id: Option<String>,
+}
+
 impl Display for NearEvent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&format!("EVENT_JSON:{}", self.to_json_string()))
@@ -63,6 +233,10 @@ impl NearEvent {
         NearEvent::new("1.0.0".to_string(), event_kind)
     }
 
+    pub fn new_nep171_v1(event_kind: Nep171EventKind) -> Self {
+        NearEvent::Nep171(Nep171Event { version: "1.0.0".to_string(), event_kind })
+    }
+
     pub(crate) fn to_json_string(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
@@ -79,6 +253,74 @@ impl NearEvent {
         NearEvent::new_v1(ParasFarmingEventKind::UnlockFtBalance(data))
     }
 
+    pub fn evict_nft(data: Vec<EvictNftData>) -> Self {
+        NearEvent::new_v1(ParasFarmingEventKind::EvictNft(data))
+    }
+
+    pub fn deposit_seed(data: Vec<SeedData>) -> Self {
+        NearEvent::new_v1(ParasFarmingEventKind::DepositSeed(data))
+    }
+
+    pub fn withdraw_seed(data: Vec<SeedData>) -> Self {
+        NearEvent::new_v1(ParasFarmingEventKind::WithdrawSeed(data))
+    }
+
+    pub fn reprice_nft_seed(data: Vec<RepriceNftSeedData>) -> Self {
+        NearEvent::new_v1(ParasFarmingEventKind::RepriceNftSeed(data))
+    }
+
+    pub fn claim_reward(data: Vec<FarmRewardData>) -> Self {
+        NearEvent::new_v1(ParasFarmingEventKind::ClaimReward(data))
+    }
+
+    pub fn forfeit_reward(data: Vec<FarmRewardData>) -> Self {
+        NearEvent::new_v1(ParasFarmingEventKind::ForfeitReward(data))
+    }
+
+    pub fn withdraw_reward(data: Vec<WithdrawRewardData>) -> Self {
+        NearEvent::new_v1(ParasFarmingEventKind::WithdrawReward(data))
+    }
+
+    pub fn create_farm(data: Vec<CreateFarmData>) -> Self {
+        NearEvent::new_v1(ParasFarmingEventKind::CreateFarm(data))
+    }
+
+    pub fn add_reward(data: Vec<AddRewardData>) -> Self {
+        NearEvent::new_v1(ParasFarmingEventKind::AddReward(data))
+    }
+
+    pub fn clear_farm(data: Vec<ClearFarmData>) -> Self {
+        NearEvent::new_v1(ParasFarmingEventKind::ClearFarm(data))
+    }
+
+    pub fn refund_deposit(data: Vec<RefundDepositData>) -> Self {
+        NearEvent::new_v1(ParasFarmingEventKind::RefundDeposit(data))
+    }
+
+    pub fn transfer_seed(data: Vec<TransferSeedData>) -> Self {
+        NearEvent::new_v1(ParasFarmingEventKind::TransferSeed(data))
+    }
+
+    pub fn compound(data: Vec<CompoundData>) -> Self {
+        NearEvent::new_v1(ParasFarmingEventKind::Compound(data))
+    }
+
+    pub fn zap(data: Vec<ZapData>) -> Self {
+        NearEvent::new_v1(ParasFarmingEventKind::Zap(data))
+    }
+
+    pub fn nft_mint(data: Vec<NftMintData>) -> Self {
+        NearEvent::new_nep171_v1(Nep171EventKind::NftMint(data))
+    }
+
+    pub fn nft_transfer(data: Vec<NftTransferData>) -> Self {
+        NearEvent::new_nep171_v1(Nep171EventKind::NftTransfer(data))
+    }
+
+    pub fn nft_burn(data: Vec<NftBurnData>) -> Self {
+        NearEvent::new_nep171_v1(Nep171EventKind::NftBurn(data))
+    }
+
     pub fn log_lock_ft_balance(data: LockFTBalanceData){
         NearEvent::lock_ft_balance(vec![data]).log();
     }
@@ -86,6 +328,74 @@ impl NearEvent {
     pub fn log_unlock_ft_balance(data: UnlockFTBalanceData){
         NearEvent::unlock_ft_balance(vec![data]).log();
     }
+
+    pub fn log_evict_nft(data: Vec<EvictNftData>){
+        NearEvent::evict_nft(data).log();
+    }
+
+    pub fn log_deposit_seed(data: Vec<SeedData>){
+        NearEvent::deposit_seed(data).log();
+    }
+
+    pub fn log_withdraw_seed(data: Vec<SeedData>){
+        NearEvent::withdraw_seed(data).log();
+    }
+
+    pub fn log_reprice_nft_seed(data: RepriceNftSeedData){
+        NearEvent::reprice_nft_seed(vec![data]).log();
+    }
+
+    pub fn log_claim_reward(data: Vec<FarmRewardData>){
+        NearEvent::claim_reward(data).log();
+    }
+
+    pub fn log_forfeit_reward(data: Vec<FarmRewardData>){
+        NearEvent::forfeit_reward(data).log();
+    }
+
+    pub fn log_withdraw_reward(data: WithdrawRewardData){
+        NearEvent::withdraw_reward(vec![data]).log();
+    }
+
+    pub fn log_create_farm(data: CreateFarmData){
+        NearEvent::create_farm(vec![data]).log();
+    }
+
+    pub fn log_add_reward(data: AddRewardData){
+        NearEvent::add_reward(vec![data]).log();
+    }
+
+    pub fn log_clear_farm(data: ClearFarmData){
+        NearEvent::clear_farm(vec![data]).log();
+    }
+
+    pub fn log_refund_deposit(data: RefundDepositData){
+        NearEvent::refund_deposit(vec![data]).log();
+    }
+
+    pub fn log_transfer_seed(data: TransferSeedData){
+        NearEvent::transfer_seed(vec![data]).log();
+    }
+
+    pub fn log_compound(data: CompoundData){
+        NearEvent::compound(vec![data]).log();
+    }
+
+    pub fn log_zap(data: ZapData){
+        NearEvent::zap(vec![data]).log();
+    }
+
+    pub fn log_nft_mint(data: NftMintData){
+        NearEvent::nft_mint(vec![data]).log();
+    }
+
+    pub fn log_nft_transfer(data: NftTransferData){
+        NearEvent::nft_transfer(vec![data]).log();
+    }
+
+    pub fn log_nft_burn(data: NftBurnData){
+        NearEvent::nft_burn(vec![data]).log();
+    }
 }
 
 #[cfg(test)]
@@ -145,4 +455,252 @@ mod tests {
             r#"{"standard":"paras_farming","version":"1.0.0","event":"unlock_ft_balance","data":[{"account_id":"darmaji","seed_id":"seed_id_1","amount":"1"}]}"#
         );
     }
+
+    #[test]
+    fn evict_nft() {
+        let evict_nft_log = EvictNftData{
+            account_id: "darmaji".to_string(),
+            seed_id: "seed_id_1".to_string(),
+            contract_nft_token_id: "paras-token-v1.testnet@177:5".to_string(),
+            amount: "1".to_string(),
+        };
+        let event_log = NearEvent::evict_nft(vec![evict_nft_log]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"paras_farming","version":"1.0.0","event":"evict_nft","data":[{"account_id":"darmaji","seed_id":"seed_id_1","contract_nft_token_id":"paras-token-v1.testnet@177:5","amount":"1"}]}"#
+        );
+    }
+
+    #[test]
+    fn deposit_seed() {
+        let event_log = NearEvent::deposit_seed(vec![
+            SeedData{
+                account_id: "darmaji".to_string(),
+                seed_id: "seed_id_1".to_string(),
+                amount: "1".to_string(),
+                contract_nft_token_id: None,
+            },
+            SeedData{
+                account_id: "darmaji".to_string(),
+                seed_id: "paras-token-v1.testnet@177".to_string(),
+                amount: "2".to_string(),
+                contract_nft_token_id: Some("paras-token-v1.testnet@177:5".to_string()),
+            },
+        ]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"paras_farming","version":"1.0.0","event":"deposit_seed","data":[{"account_id":"darmaji","seed_id":"seed_id_1","amount":"1"},{"account_id":"darmaji","seed_id":"paras-token-v1.testnet@177","amount":"2","contract_nft_token_id":"paras-token-v1.testnet@177:5"}]}"#
+        );
+    }
+
+    #[test]
+    fn withdraw_seed() {
+        let event_log = NearEvent::withdraw_seed(vec![SeedData{
+            account_id: "darmaji".to_string(),
+            seed_id: "seed_id_1".to_string(),
+            amount: "1".to_string(),
+            contract_nft_token_id: None,
+        }]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"paras_farming","version":"1.0.0","event":"withdraw_seed","data":[{"account_id":"darmaji","seed_id":"seed_id_1","amount":"1"}]}"#
+        );
+    }
+
+    #[test]
+    fn reprice_nft_seed() {
+        let event_log = NearEvent::reprice_nft_seed(vec![RepriceNftSeedData{
+            account_id: "darmaji".to_string(),
+            seed_id: "seed_id_1".to_string(),
+            old_amount: "1".to_string(),
+            new_amount: "2".to_string(),
+        }]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"paras_farming","version":"1.0.0","event":"reprice_nft_seed","data":[{"account_id":"darmaji","seed_id":"seed_id_1","old_amount":"1","new_amount":"2"}]}"#
+        );
+    }
+
+    #[test]
+    fn claim_reward() {
+        let event_log = NearEvent::claim_reward(vec![
+            FarmRewardData{
+                account_id: "darmaji".to_string(),
+                farm_id: "seed_id_1#0".to_string(),
+                reward_token: "token_1".to_string(),
+                amount: "1".to_string(),
+            },
+            FarmRewardData{
+                account_id: "darmaji".to_string(),
+                farm_id: "seed_id_1#1".to_string(),
+                reward_token: "token_2".to_string(),
+                amount: "2".to_string(),
+            },
+        ]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"paras_farming","version":"1.0.0","event":"claim_reward","data":[{"account_id":"darmaji","farm_id":"seed_id_1#0","reward_token":"token_1","amount":"1"},{"account_id":"darmaji","farm_id":"seed_id_1#1","reward_token":"token_2","amount":"2"}]}"#
+        );
+    }
+
+    #[test]
+    fn forfeit_reward() {
+        let event_log = NearEvent::forfeit_reward(vec![FarmRewardData{
+            account_id: "darmaji".to_string(),
+            farm_id: "seed_id_1#0".to_string(),
+            reward_token: "token_1".to_string(),
+            amount: "1".to_string(),
+        }]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"paras_farming","version":"1.0.0","event":"forfeit_reward","data":[{"account_id":"darmaji","farm_id":"seed_id_1#0","reward_token":"token_1","amount":"1"}]}"#
+        );
+    }
+
+    #[test]
+    fn withdraw_reward() {
+        let event_log = NearEvent::withdraw_reward(vec![WithdrawRewardData{
+            account_id: "darmaji".to_string(),
+            reward_token: "token_1".to_string(),
+            amount: "1".to_string(),
+        }]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"paras_farming","version":"1.0.0","event":"withdraw_reward","data":[{"account_id":"darmaji","reward_token":"token_1","amount":"1"}]}"#
+        );
+    }
+
+    #[test]
+    fn create_farm() {
+        let event_log = NearEvent::create_farm(vec![CreateFarmData{
+            farm_id: "seed_id_1#0".to_string(),
+            seed_id: "seed_id_1".to_string(),
+            reward_token: "token_1".to_string(),
+            start_at: 1,
+            reward_per_session: "10".to_string(),
+            session_interval: 60,
+            min_deposit: "1".to_string(),
+        }]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"paras_farming","version":"1.0.0","event":"create_farm","data":[{"farm_id":"seed_id_1#0","seed_id":"seed_id_1","reward_token":"token_1","start_at":1,"reward_per_session":"10","session_interval":60,"min_deposit":"1"}]}"#
+        );
+    }
+
+    #[test]
+    fn add_reward() {
+        let event_log = NearEvent::add_reward(vec![AddRewardData{
+            account_id: "darmaji".to_string(),
+            farm_id: "seed_id_1#0".to_string(),
+            reward_token: "token_1".to_string(),
+            amount: "10".to_string(),
+            undistributed: "15".to_string(),
+        }]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"paras_farming","version":"1.0.0","event":"add_reward","data":[{"account_id":"darmaji","farm_id":"seed_id_1#0","reward_token":"token_1","amount":"10","undistributed":"15"}]}"#
+        );
+    }
+
+    #[test]
+    fn clear_farm() {
+        let event_log = NearEvent::clear_farm(vec![ClearFarmData{
+            farm_id: "seed_id_1#0".to_string(),
+            seed_id: "seed_id_1".to_string(),
+            reward_token: "token_1".to_string(),
+            beneficiary_reward: "5".to_string(),
+        }]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"paras_farming","version":"1.0.0","event":"clear_farm","data":[{"farm_id":"seed_id_1#0","seed_id":"seed_id_1","reward_token":"token_1","beneficiary_reward":"5"}]}"#
+        );
+    }
+
+    #[test]
+    fn refund_deposit() {
+        let event_log = NearEvent::refund_deposit(vec![RefundDepositData{
+            account_id: "darmaji".to_string(),
+            seed_id: "paras-token-v1.testnet@177".to_string(),
+            amount: "1".to_string(),
+            contract_nft_token_id: Some("paras-token-v1.testnet@177:5".to_string()),
+            reason: "E10: account not registered".to_string(),
+        }]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"paras_farming","version":"1.0.0","event":"refund_deposit","data":[{"account_id":"darmaji","seed_id":"paras-token-v1.testnet@177","amount":"1","contract_nft_token_id":"paras-token-v1.testnet@177:5","reason":"E10: account not registered"}]}"#
+        );
+    }
+
+    #[test]
+    fn transfer_seed() {
+        let event_log = NearEvent::transfer_seed(vec![TransferSeedData{
+            sender_id: "darmaji".to_string(),
+            receiver_id: "cymac".to_string(),
+            seed_id: "dev-1631277489384-75412609538902".to_string(),
+            amount: "10".to_string(),
+            contract_nft_token_id: None,
+        }]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"paras_farming","version":"1.0.0","event":"transfer_seed","data":[{"sender_id":"darmaji","receiver_id":"cymac","seed_id":"dev-1631277489384-75412609538902","amount":"10"}]}"#
+        );
+    }
+
+    #[test]
+    fn nft_transfer() {
+        let event_log = NearEvent::nft_transfer(vec![NftTransferData{
+            authorized_id: None,
+            old_owner_id: "darmaji".to_string(),
+            new_owner_id: "cymac".to_string(),
+            token_ids: vec!["paras-token-v1.testnet@177:5".to_string()],
+            memo: None,
+        }]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"nep171","version":"1.0.0","event":"nft_transfer","data":[{"old_owner_id":"darmaji","new_owner_id":"cymac","token_ids":["paras-token-v1.testnet@177:5"]}]}"#
+        );
+    }
+
+    #[test]
+    fn compound() {
+        let event_log = NearEvent::compound(vec![CompoundData{
+            account_id: "darmaji".to_string(),
+            seed_id: "paras-token-v1.testnet".to_string(),
+            amount: "10".to_string(),
+        }]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"paras_farming","version":"1.0.0","event":"compound","data":[{"account_id":"darmaji","seed_id":"paras-token-v1.testnet","amount":"10"}]}"#
+        );
+    }
+
+    #[test]
+    fn zap() {
+        let event_log = NearEvent::zap(vec![ZapData{
+            account_id: "darmaji".to_string(),
+            seed_id: "ref-finance.testnet@0".to_string(),
+            reward_token: "paras-token-v1.testnet".to_string(),
+            amount: "10".to_string(),
+            shares: "5".to_string(),
+        }]);
+
+        assert_eq!(
+            serde_json::to_string(&event_log).unwrap(),
+            r#"{"standard":"paras_farming","version":"1.0.0","event":"zap","data":[{"account_id":"darmaji","seed_id":"ref-finance.testnet@0","reward_token":"paras-token-v1.testnet","amount":"10","shares":"5"}]}"#
+        );
+    }
 }
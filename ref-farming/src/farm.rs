@@ -1,10 +1,11 @@
 //! Wrapper of different types of farms 
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::Vector;
 use near_sdk::{AccountId, Balance};
 
-use crate::simple_farm::{SimpleFarm, RPS};
-use crate::SeedId;
+use crate::simple_farm::{SimpleFarm, FarmEmission, FarmCheckpoint, RPS};
+use crate::{SeedId, StorageKeys};
 
 pub(crate) type FarmId = String;
 
@@ -79,6 +80,47 @@ impl Farm {
         }
     }
 
+    /// forfeit user reward of given seeds to beneficiary,
+    /// return amount of reward forfeited
+    pub fn forfeit_user_reward(&mut self, 
+        user_rps: &RPS,
+        user_seeds: &Balance, 
+        total_seeds: &Balance, 
+    ) -> Balance {
+        match self {
+            Farm::SimpleFarm(farm) 
+                => farm.forfeit_user_reward(user_rps, user_seeds, total_seeds),
+        }
+    }
+
+    /// forfeit reward already claimed from the farm and held for a user to beneficiary
+    pub fn forfeit_held_reward(&mut self, amount: &Balance) {
+        match self {
+            Farm::SimpleFarm(farm) => farm.forfeit_held_reward(amount),
+        }
+    }
+
+    /// Returns the last distributed round.
+    pub fn get_last_round(&self) -> u32 {
+        match self {
+            Farm::SimpleFarm(farm) => farm.last_distribution.rr,
+        }
+    }
+
+    /// Returns checkpoint of the last distribution.
+    pub fn checkpoint(&self, total_seeds: &Balance) -> FarmCheckpoint {
+        match self {
+            Farm::SimpleFarm(farm) => farm.checkpoint(total_seeds),
+        }
+    }
+
+    /// Returns reward emission of the farm given total seeds staked.
+    pub fn emission(&self, total_seeds: &Balance) -> FarmEmission {
+        match self {
+            Farm::SimpleFarm(farm) => farm.emission(total_seeds),
+        }
+    }
+
     pub fn can_be_removed(&self, total_seeds: &Balance) -> bool {
         match self {
             Farm::SimpleFarm(farm) => farm.can_be_removed(total_seeds),
@@ -92,3 +134,49 @@ impl Farm {
     }
 
 }
+
+/// Bounded ring buffer of farm checkpoints,
+/// the oldest checkpoint is overwritten once it is full.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct FarmCheckpoints {
+    pub capacity: u64,
+    /// total checkpoints pushed by far
+    pub count: u64,
+    pub items: Vector<FarmCheckpoint>,
+}
+
+impl FarmCheckpoints {
+    pub fn new(farm_id: &FarmId, capacity: u64) -> Self {
+        Self {
+            capacity,
+            count: 0,
+            items: Vector::new(StorageKeys::FarmCheckpoint { farm_id: farm_id.clone() }),
+        }
+    }
+
+    pub fn push(&mut self, checkpoint: &FarmCheckpoint) {
+        if self.items.len() < self.capacity {
+            self.items.push(checkpoint);
+        } else {
+            self.items.replace(self.count % self.capacity, checkpoint);
+        }
+        self.count += 1;
+    }
+
+    pub fn len(&self) -> u64 {
+        self.items.len()
+    }
+
+    /// Returns checkpoint at given index, from the oldest one kept.
+    pub fn get(&self, index: u64) -> Option<FarmCheckpoint> {
+        if index >= self.items.len() {
+            return None;
+        }
+        let oldest = if self.items.len() < self.capacity { 0 } else { self.count % self.capacity };
+        self.items.get((oldest + index) % self.items.len())
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+}
@@ -3,9 +3,9 @@
 
 use std::collections::HashSet;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::Balance;
+use near_sdk::{AccountId, Balance};
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::json_types::U128;
+use near_sdk::json_types::{U128, U64};
 use crate::errors::*;
 use crate::farm::FarmId;
 use crate::utils::parse_seed_id;
@@ -22,6 +22,26 @@ pub(crate) type NFTTokenId = String; //paras-comic-dev.testnet@6
 
 pub(crate) type NftBalance = HashMap<NFTTokenId, U128>; //paras-comic-dev.testnet@6
 
+/// Weight rule of a NFT seed, matched against the traits in token metadata.
+/// e.g. rarity=legendary with multiplier 1000 makes the nft worth 10x
+/// of its balance equivalent in `NftBalance`
+#[derive(Serialize, Deserialize, BorshSerialize, BorshDeserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftTraitRule {
+    pub trait_type: String,
+    pub value: String,
+    /// in percent of the balance equivalent, 100 means 1x
+    pub multiplier: u32,
+}
+
+/// Nft approved to this contract by its owner, staked later with `stake_approved_nfts`.
+#[derive(BorshSerialize, BorshDeserialize, Clone)]
+pub struct NftApproval {
+    pub owner_id: AccountId,
+    pub approval_id: u64,
+    pub seed_id: SeedId,
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Clone, PartialEq, Debug)]
 pub enum SeedType {
     FT,
@@ -192,6 +212,7 @@ pub struct SeedInfo {
     pub amount: U128,
     pub min_deposit: U128,
     pub nft_balance: Option<NftBalance>,
+    pub staker_count: U64,
     pub title: Option<String>,
     pub media: Option<String>
 }
@@ -214,6 +235,7 @@ impl From<&FarmSeed> for SeedInfo {
                 title: Some(seed_metadata.title.unwrap_or("".to_string())),
                 media: Some(seed_metadata.media.unwrap_or("".to_string())),
                 nft_balance: None,
+                staker_count: U64(0),
             }
         } else {
             Self {
@@ -225,7 +247,8 @@ impl From<&FarmSeed> for SeedInfo {
                 farms: fs.farms.iter().map(|key| key.clone()).collect(),
                 title: Some("".to_string()),
                 media: Some("".to_string()),
-                nft_balance: None
+                nft_balance: None,
+                staker_count: U64(0),
             }
         }
     }
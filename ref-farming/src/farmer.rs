@@ -2,6 +2,7 @@
 //! * all claimed reward tokens, 
 //! * all seeds he staked,
 //! * user_rps per farm,
+//! * lifetime claimed rewards per token and per farm,
 //! and the deposited near amount prepaid as storage fee
 
 
@@ -9,7 +10,7 @@ use std::collections::HashMap;
 use near_sdk::collections::LookupMap;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::serde::Serialize;
-use near_sdk::{env, AccountId, Balance};
+use near_sdk::{env, AccountId, Balance, StorageUsage};
 use crate::{SeedId, FarmId, RPS};
 use crate::simple_farm::ContractNFTTokenId;
 use crate::errors::*;
@@ -23,6 +24,10 @@ use near_sdk::collections::UnorderedSet;
 /// each empty hashmap cost 4 bytes
 pub const MIN_FARMER_LENGTH: u128 = MAX_ACCOUNT_LENGTH + 16 + 4 * 3;
 
+/// estimated bytes of each staked nft and locked seed of legacy farmers
+pub const NFT_ENTRY_LENGTH: u128 = 2 * (4 + 2 * MAX_ACCOUNT_LENGTH + 8);
+pub const LOCKED_SEED_ENTRY_LENGTH: u128 = 4 + MAX_ACCOUNT_LENGTH + 16 + 4 + 4;
+
 /// retention is used to invalidate the locked_seed when the user forgot to unlock the balance 
 pub const LOCKED_SEED_RETENTION: TimestampSec = 60 * 60 * 24;
 
@@ -58,9 +63,107 @@ impl From<FarmerV101> for Farmer{
             nft_seeds,
 
             // added new locked seeds 
-            locked_seeds: HashMap::new()
-        }
+            locked_seeds: HashMap::new(),
+
+            // added lifetime claimed rewards
+            claimed_rewards: HashMap::new(),
+            farm_claimed_rewards: HashMap::new(),
+
+            // added measured storage
+            storage_used: 0,
+        }.with_estimated_storage()
+
+    }
+}
+
+/// Account deposits information and storage cost (LEGACY).
+#[derive(BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "test", derive(Clone))]
+pub struct FarmerV102 {
+    pub farmer_id: AccountId,
+    /// Native NEAR amount sent to this contract.
+    /// Used for storage.
+    pub amount: Balance,
+    /// Amounts of various reward tokens the farmer claimed.
+    pub rewards: HashMap<AccountId, Balance>,
+    /// Amounts of various seed tokens the farmer staked.
+    pub seeds: HashMap<SeedId, Balance>,
+    /// record user_last_rps of farms
+    pub user_rps: LookupMap<FarmId, RPS>,
+    pub rps_count: u32,
+    pub nft_seeds: HashMap<SeedId, UnorderedSet<ContractNFTTokenId>>,
+    pub locked_seeds: HashMap<SeedId, LockedSeed>,
+}
+
+impl From<FarmerV102> for Farmer{
+    fn from (f: FarmerV102) -> Self{
+        let FarmerV102 { farmer_id, amount, rewards, seeds, user_rps, rps_count, nft_seeds, locked_seeds } = f;
+
+        Self{
+            farmer_id,
+            amount,
+            rewards,
+            seeds,
+            user_rps,
+            rps_count,
+            nft_seeds,
+            locked_seeds,
+
+            // added lifetime claimed rewards
+            claimed_rewards: HashMap::new(),
+            farm_claimed_rewards: HashMap::new(),
+
+            // added measured storage
+            storage_used: 0,
+        }.with_estimated_storage()
+    }
+}
+
+/// Account deposits information and storage cost (LEGACY).
+#[derive(BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "test", derive(Clone))]
+pub struct FarmerV103 {
+    pub farmer_id: AccountId,
+    /// Native NEAR amount sent to this contract.
+    /// Used for storage.
+    pub amount: Balance,
+    /// Amounts of various reward tokens the farmer claimed.
+    pub rewards: HashMap<AccountId, Balance>,
+    /// Amounts of various seed tokens the farmer staked.
+    pub seeds: HashMap<SeedId, Balance>,
+    /// record user_last_rps of farms
+    pub user_rps: LookupMap<FarmId, RPS>,
+    pub rps_count: u32,
+    pub nft_seeds: HashMap<SeedId, UnorderedSet<ContractNFTTokenId>>,
+    pub locked_seeds: HashMap<SeedId, LockedSeed>,
+    /// Lifetime amounts of various reward tokens the farmer claimed.
+    pub claimed_rewards: HashMap<AccountId, Balance>,
+    /// Lifetime amounts of reward the farmer claimed from each farm.
+    pub farm_claimed_rewards: HashMap<FarmId, Balance>,
+}
+
+impl From<FarmerV103> for Farmer{
+    fn from (f: FarmerV103) -> Self{
+        let FarmerV103 {
+            farmer_id, amount, rewards, seeds, user_rps, rps_count, nft_seeds, locked_seeds,
+            claimed_rewards, farm_claimed_rewards,
+        } = f;
+
+        Self{
+            farmer_id,
+            amount,
+            rewards,
+            seeds,
+            user_rps,
+            rps_count,
+            nft_seeds,
+            locked_seeds,
+            claimed_rewards,
+            farm_claimed_rewards,
 
+            // added measured storage
+            storage_used: 0,
+        }.with_estimated_storage()
     }
 }
 
@@ -90,6 +193,13 @@ pub struct Farmer {
     pub rps_count: u32,
     pub nft_seeds: HashMap<SeedId, UnorderedSet<ContractNFTTokenId>>,
     pub locked_seeds: HashMap<SeedId, LockedSeed>,
+    /// Lifetime amounts of various reward tokens the farmer claimed.
+    pub claimed_rewards: HashMap<AccountId, Balance>,
+    /// Lifetime amounts of reward the farmer claimed from each farm.
+    pub farm_claimed_rewards: HashMap<FarmId, Balance>,
+    /// Bytes of contract storage used by this farmer,
+    /// measured around each mutation of the farmer.
+    pub storage_used: StorageUsage,
 }
 
 impl Farmer {
@@ -103,6 +213,15 @@ impl Farmer {
         }
     }
 
+    /// Adds amount claimed from the farm to lifetime totals,
+    /// the farm total is kept only while the farmer has rps in the farm.
+    pub(crate) fn add_claimed_reward(&mut self, farm_id: &FarmId, token: &AccountId, amount: Balance) {
+        *self.claimed_rewards.entry(token.clone()).or_insert(0) += amount;
+        if self.user_rps.contains_key(farm_id) {
+            *self.farm_claimed_rewards.entry(farm_id.clone()).or_insert(0) += amount;
+        }
+    }
+
     /// Subtract from `reward` balance.
     /// if amount == 0, subtract all reward balance.
     /// Panics if `amount` is bigger than the current balance.
@@ -178,7 +297,9 @@ impl Farmer {
         self.user_rps.insert(farm_id, &rps);
     }
 
+    /// also drops the lifetime claimed reward of the farm, the farmer is done with it.
     pub fn remove_rps(&mut self, farm_id: &FarmId) {
+        self.farm_claimed_rewards.remove(farm_id);
         if self.user_rps.contains_key(farm_id) {
             self.user_rps.remove(farm_id);
             self.rps_count -= 1;
@@ -187,13 +308,45 @@ impl Farmer {
 
     /// Returns amount of yocto near necessary to cover storage used by this data structure.
     pub fn storage_usage(&self) -> Balance {
-        (
+        self.storage_used as u128 * env::storage_byte_cost()
+    }
+
+    /// Legacy farmers were never measured, start them from an estimate of what they hold.
+    fn with_estimated_storage(mut self) -> Self {
+        let nft_count: u64 = self.nft_seeds.values().map(|nfts| nfts.len()).sum();
+        self.storage_used = (
             MIN_FARMER_LENGTH 
             + self.rewards.len() as u128 * (4 + MAX_ACCOUNT_LENGTH + 16)
             + self.seeds.len() as u128 * (4 + MAX_ACCOUNT_LENGTH + 16)
             + self.rps_count as u128 * (4 + 1 + 2 * MAX_ACCOUNT_LENGTH + 32)
-        )
-        * env::storage_byte_cost()
+            + self.locked_seeds.len() as u128 * LOCKED_SEED_ENTRY_LENGTH
+            + self.claimed_rewards.len() as u128 * (4 + MAX_ACCOUNT_LENGTH + 16)
+            + self.farm_claimed_rewards.len() as u128 * (4 + MAX_ACCOUNT_LENGTH + 16)
+            + nft_count as u128 * NFT_ENTRY_LENGTH
+        ) as StorageUsage;
+        self
+    }
+
+    /// Upper bound of the storage settling the farms of the seed and growing the seed adds,
+    /// from the records the farmer does not hold yet. farms are given with their reward token.
+    pub fn settle_storage_bound(&self, seed_id: &SeedId, farms: &[(FarmId, AccountId)]) -> StorageUsage {
+        let entry_length = 4 + MAX_ACCOUNT_LENGTH + 16;
+        let mut bound = if self.seeds.contains_key(seed_id) { 0 } else { entry_length };
+        for (farm_id, reward_token) in farms {
+            if !self.user_rps.contains_key(farm_id) {
+                bound += 4 + 1 + 2 * MAX_ACCOUNT_LENGTH + 32;
+            }
+            if !self.farm_claimed_rewards.contains_key(farm_id) {
+                bound += entry_length;
+            }
+            if !self.rewards.contains_key(reward_token) {
+                bound += entry_length;
+            }
+            if !self.claimed_rewards.contains_key(reward_token) {
+                bound += entry_length;
+            }
+        }
+        bound as StorageUsage
     }
 
     pub fn add_nft(&mut self, seed_id: &SeedId, contract_nft_token_id: ContractNFTTokenId) {
@@ -221,6 +374,11 @@ impl Farmer {
         contract_nft_token_id
     }
 
+    /// any nft still staked in any seed
+    pub fn has_nfts(&self) -> bool {
+        self.nft_seeds.values().any(|nft_contract_seed| !nft_contract_seed.is_empty())
+    }
+
     /// Return current balance - locked balanced 
     pub fn get_available_balance(&self, seed_id: &SeedId) -> Balance {
         let balance = self.seeds.get(seed_id).unwrap_or(&0).clone();
@@ -286,13 +444,15 @@ impl Farmer {
 #[derive(BorshSerialize, BorshDeserialize)]
 pub enum VersionedFarmer {
     V101(FarmerV101),
-    V102(Farmer),
+    V102(FarmerV102),
+    V103(FarmerV103),
+    V104(Farmer),
 }
 
 impl VersionedFarmer {
 
     pub fn new(farmer_id: AccountId, amount: Balance) -> Self {
-        VersionedFarmer::V102(Farmer {
+        VersionedFarmer::V104(Farmer {
             farmer_id: farmer_id.clone(),
             amount,
             rewards: HashMap::new(),
@@ -302,7 +462,10 @@ impl VersionedFarmer {
             }),
             rps_count: 0,
             nft_seeds: HashMap::new(),
-            locked_seeds: HashMap::new()
+            locked_seeds: HashMap::new(),
+            claimed_rewards: HashMap::new(),
+            farm_claimed_rewards: HashMap::new(),
+            storage_used: 0,
         })
     }
 
@@ -310,9 +473,15 @@ impl VersionedFarmer {
     pub fn upgrade(self) -> Self {
         match self {
             VersionedFarmer::V101(farmer_v101) => {
-                VersionedFarmer::V102(Farmer::from(farmer_v101))
+                VersionedFarmer::V104(Farmer::from(farmer_v101))
+            },
+            VersionedFarmer::V102(farmer_v102) => {
+                VersionedFarmer::V104(Farmer::from(farmer_v102))
+            },
+            VersionedFarmer::V103(farmer_v103) => {
+                VersionedFarmer::V104(Farmer::from(farmer_v103))
             },
-            VersionedFarmer::V102(farmer) => VersionedFarmer::V102(farmer),
+            VersionedFarmer::V104(farmer) => VersionedFarmer::V104(farmer),
         }
     }
 
@@ -320,7 +489,7 @@ impl VersionedFarmer {
     #[allow(unreachable_patterns)]
     pub fn need_upgrade(&self) -> bool {
         match self {
-            VersionedFarmer::V102(_) => false,
+            VersionedFarmer::V104(_) => false,
             _ => true,
         }
     }
@@ -329,7 +498,7 @@ impl VersionedFarmer {
     #[allow(unreachable_patterns)]
     pub fn get_ref(&self) -> &Farmer {
         match self {
-            VersionedFarmer::V102(farmer) => farmer,
+            VersionedFarmer::V104(farmer) => farmer,
             _ => unimplemented!(),
         }
     }
@@ -338,7 +507,7 @@ impl VersionedFarmer {
     #[allow(unreachable_patterns)]
     pub fn get(self) -> Farmer {
         match self {
-            VersionedFarmer::V102(farmer) => farmer,
+            VersionedFarmer::V104(farmer) => farmer,
             _ => unimplemented!(),
         }
     }
@@ -347,7 +516,7 @@ impl VersionedFarmer {
     #[allow(unreachable_patterns)]
     pub fn get_ref_mut(&mut self) -> &mut Farmer {
         match self {
-            VersionedFarmer::V102(farmer) => farmer,
+            VersionedFarmer::V104(farmer) => farmer,
             _ => unimplemented!(),
         }
     }
@@ -3,20 +3,27 @@
 *
 * lib.rs is the main entry point.
 */
+use std::collections::HashMap;
+
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::ValidAccountId;
-use near_sdk::collections::{LookupMap, UnorderedMap};
+use near_sdk::collections::{LookupMap, LookupSet, UnorderedMap, UnorderedSet};
 use near_sdk::{env, near_bindgen, Balance, AccountId, PanicOnDefault};
 use near_sdk::BorshStorageKey;
 
-use crate::farm::{Farm, FarmId};
+use crate::farm::{Farm, FarmId, FarmCheckpoints};
 use crate::simple_farm::RPS;
-use crate::farm_seed::{VersionedFarmSeed, SeedId, NftBalance};
+use crate::farm_seed::{VersionedFarmSeed, SeedId, NftBalance, NftTraitRule, NftApproval};
 use crate::farmer::{VersionedFarmer, Farmer};
+use crate::storage_impl::StorageSponsorship;
+use crate::receipt_token::ReceiptToken;
+use crate::zap::ZapState;
+use crate::simple_farm::ContractNFTTokenId;
 
 // for simulator test
 pub use crate::simple_farm::HRSimpleFarmTerms;
 pub use crate::view::FarmInfo;
+pub use crate::zap::ZapArgs;
 
 
 mod utils;
@@ -33,6 +40,9 @@ mod actions_of_seed;
 mod actions_of_reward;
 mod view;
 mod event;
+mod receipt_token;
+mod nft_receipt;
+mod zap;
 
 mod owner;
 
@@ -49,6 +59,30 @@ pub enum StorageKeys {
     UserRps { account_id: AccountId },
     AccountSeedId { account_seed_id: String },
     NftBalanceSeed,
+    NftTraitRules,
+    NftTraitMultiplier,
+    NftApproval,
+    NftApprovalStakeSeeds,
+    NftApprovalStakes,
+    FarmerIds,
+    SeedStakers { seed_id: SeedId },
+    SeedStakersIndex,
+    FarmCheckpoints,
+    FarmCheckpoint { farm_id: FarmId },
+    SponsoredStorage,
+    AutoRegisterSeeds,
+    StakingManagers,
+    ReceiptTokens,
+    NftReceiptOwners,
+    AutoCompoundFarmers { seed_id: SeedId },
+    AutoCompoundIndex,
+    ZapTokens,
+    ZapRefunds,
+    ZapRefundTotals,
+    ZappingExchanges,
+    ZapRefundIds,
+    NftApprovalHeldRewards,
+    NftApprovalIds,
 }
 
 #[derive(BorshDeserialize, BorshSerialize)]
@@ -73,6 +107,59 @@ pub struct OldContractData {
     reward_info: UnorderedMap<AccountId, Balance>,
 }
 
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractDataV2 {
+
+    // owner of this contract
+    owner_id: AccountId,
+
+    // record seeds and the farms under it.
+    // seeds: UnorderedMap<SeedId, FarmSeed>,
+    seeds: UnorderedMap<SeedId, VersionedFarmSeed>,
+
+    // each farmer has a structure to describe
+    // farmers: LookupMap<AccountId, Farmer>,
+    farmers: LookupMap<AccountId, VersionedFarmer>,
+
+    farms: UnorderedMap<FarmId, Farm>,
+    outdated_farms: UnorderedMap<FarmId, Farm>,
+
+    nft_balance_seeds: LookupMap<SeedId, NftBalance>,
+
+    // for statistic
+    farmer_count: u64,
+    reward_info: UnorderedMap<AccountId, Balance>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractDataV3 {
+
+    // owner of this contract
+    owner_id: AccountId,
+
+    // record seeds and the farms under it.
+    // seeds: UnorderedMap<SeedId, FarmSeed>,
+    seeds: UnorderedMap<SeedId, VersionedFarmSeed>,
+
+    // each farmer has a structure to describe
+    // farmers: LookupMap<AccountId, Farmer>,
+    farmers: LookupMap<AccountId, VersionedFarmer>,
+
+    farms: UnorderedMap<FarmId, Farm>,
+    outdated_farms: UnorderedMap<FarmId, Farm>,
+
+    nft_balance_seeds: LookupMap<SeedId, NftBalance>,
+
+    // for statistic
+    farmer_count: u64,
+    reward_info: UnorderedMap<AccountId, Balance>,
+
+    // trait based weights of nft seeds, applied on top of nft_balance_seeds
+    nft_trait_rules: LookupMap<SeedId, Vec<NftTraitRule>>,
+    // multiplier credited to each staked nft when its traits matched a rule
+    nft_trait_multipliers: LookupMap<(SeedId, ContractNFTTokenId), u32>,
+}
+
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct ContractData {
 
@@ -95,13 +182,60 @@ pub struct ContractData {
     // for statistic
     farmer_count: u64,
     reward_info: UnorderedMap<AccountId, Balance>,
+
+    // trait based weights of nft seeds, applied on top of nft_balance_seeds
+    nft_trait_rules: LookupMap<SeedId, Vec<NftTraitRule>>,
+    // multiplier credited to each staked nft when its traits matched a rule
+    nft_trait_multipliers: LookupMap<(SeedId, ContractNFTTokenId), u32>,
+    // nfts approved to this contract and waiting to be staked in batch
+    nft_approvals: LookupMap<ContractNFTTokenId, NftApproval>,
+    // seeds staking nfts by approval, the nfts stay in owner wallet
+    nft_approval_stake_seeds: LookupSet<SeedId>,
+    // nfts staked by approval, re-checked against the owner on claim and withdraw
+    nft_approval_stakes: LookupMap<ContractNFTTokenId, NftApproval>,
+    // registered farmer accounts, makes farmers iterable
+    farmer_ids: UnorderedSet<AccountId>,
+    // accounts with non zero balance of each seed
+    seed_stakers: LookupMap<SeedId, UnorderedSet<AccountId>>,
+    // bounded history of rps and total seeds of farms with checkpoints enabled
+    farm_checkpoints: LookupMap<FarmId, FarmCheckpoints>,
+    // pool funded by the owner to cover registration deposit of new farmers
+    storage_sponsorship: StorageSponsorship,
+    // storage deposit sponsored to each farmer, returned to the pool on unregister
+    sponsored_storage: LookupMap<AccountId, Balance>,
+    // seeds registering first time depositors, covered by the sponsorship pool
+    auto_register_seeds: LookupSet<SeedId>,
+    // accounts controlling withdrawals of seed positions staked on behalf of their farmer
+    staking_managers: LookupMap<(AccountId, SeedId), AccountId>,
+    // metadata of seeds exposing staked balance as receipt token
+    receipt_tokens: LookupMap<SeedId, ReceiptToken>,
+    // farmer and seed of each staked nft, owner of its receipt
+    nft_receipt_owners: LookupMap<ContractNFTTokenId, (AccountId, SeedId)>,
+    // farmers of each seed restaking its token rewards whenever the seed is settled
+    auto_compound_farmers: LookupMap<SeedId, UnorderedSet<AccountId>>,
+    // pool tokens of each exchange this contract is registered with for zaps
+    zap_tokens: LookupSet<(AccountId, AccountId)>,
+    // zapped tokens that failed to be withdrawn from the exchange, by farmer, exchange and token
+    zap_refunds: LookupMap<(AccountId, AccountId, AccountId), Balance>,
+    // zap refunds of each exchange and token, kept out of the leftovers of zaps
+    zap_refund_totals: LookupMap<(AccountId, AccountId), Balance>,
+    // exchanges with a zap in flight, deposits of this contract there belong to it
+    zapping_exchanges: LookupMap<AccountId, ZapState>,
+    // exchange and token of the zap refunds of each farmer
+    zap_refund_ids: LookupMap<AccountId, Vec<(AccountId, AccountId)>>,
+    // reward of each farm held for approval staked nfts until their ownership is verified
+    nft_approval_held_rewards: LookupMap<ContractNFTTokenId, HashMap<FarmId, Balance>>,
+    // nfts of the approvals parked by each account until they are staked
+    nft_approval_ids: LookupMap<AccountId, Vec<ContractNFTTokenId>>,
 }
 
 /// Versioned contract data. Allows to easily upgrade contracts.
 #[derive(BorshSerialize, BorshDeserialize)]
 pub enum VersionedContractData {
     Current(OldContractData),
-    CurrentV2(ContractData)
+    CurrentV2(ContractDataV2),
+    CurrentV3(ContractDataV3),
+    CurrentV4(ContractData),
 }
 
 impl VersionedContractData {}
@@ -110,6 +244,10 @@ impl VersionedContractData {}
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
     data: VersionedContractData,
+
+    // storage grown or released by shared records within the current call, not charged to farmers.
+    #[borsh_skip]
+    shared_storage_usage: i64,
 }
 
 #[near_bindgen]
@@ -118,7 +256,7 @@ impl Contract {
     pub fn new(owner_id: ValidAccountId) -> Self {
         assert!(!env::state_exists(), "Already initialized");
         Self {
-            data: VersionedContractData::CurrentV2(ContractData {
+            data: VersionedContractData::CurrentV4(ContractData {
                 owner_id: owner_id.into(),
                 farmer_count: 0,
                 seeds: UnorderedMap::new(StorageKeys::Seed),
@@ -127,7 +265,30 @@ impl Contract {
                 outdated_farms: UnorderedMap::new(StorageKeys::OutdatedFarm),
                 reward_info: UnorderedMap::new(StorageKeys::RewardInfo),
                 nft_balance_seeds: LookupMap::new(StorageKeys::NftBalanceSeed),
+                nft_trait_rules: LookupMap::new(StorageKeys::NftTraitRules),
+                nft_trait_multipliers: LookupMap::new(StorageKeys::NftTraitMultiplier),
+                nft_approvals: LookupMap::new(StorageKeys::NftApproval),
+                nft_approval_stake_seeds: LookupSet::new(StorageKeys::NftApprovalStakeSeeds),
+                nft_approval_stakes: LookupMap::new(StorageKeys::NftApprovalStakes),
+                farmer_ids: UnorderedSet::new(StorageKeys::FarmerIds),
+                seed_stakers: LookupMap::new(StorageKeys::SeedStakersIndex),
+                farm_checkpoints: LookupMap::new(StorageKeys::FarmCheckpoints),
+                storage_sponsorship: StorageSponsorship::default(),
+                sponsored_storage: LookupMap::new(StorageKeys::SponsoredStorage),
+                auto_register_seeds: LookupSet::new(StorageKeys::AutoRegisterSeeds),
+                staking_managers: LookupMap::new(StorageKeys::StakingManagers),
+                receipt_tokens: LookupMap::new(StorageKeys::ReceiptTokens),
+                nft_receipt_owners: LookupMap::new(StorageKeys::NftReceiptOwners),
+                auto_compound_farmers: LookupMap::new(StorageKeys::AutoCompoundIndex),
+                zap_tokens: LookupSet::new(StorageKeys::ZapTokens),
+                zap_refunds: LookupMap::new(StorageKeys::ZapRefunds),
+                zap_refund_totals: LookupMap::new(StorageKeys::ZapRefundTotals),
+                zapping_exchanges: LookupMap::new(StorageKeys::ZappingExchanges),
+                zap_refund_ids: LookupMap::new(StorageKeys::ZapRefundIds),
+                nft_approval_held_rewards: LookupMap::new(StorageKeys::NftApprovalHeldRewards),
+                nft_approval_ids: LookupMap::new(StorageKeys::NftApprovalIds),
             }),
+            shared_storage_usage: 0,
         }
     }
 }
@@ -135,41 +296,86 @@ impl Contract {
 impl Contract {
 
     fn upgrade(self) -> ContractData {
-        match self.data {
-            VersionedContractData::CurrentV2(data) => data,
-            VersionedContractData::Current(data) => {
-                return ContractData {
-                    owner_id: data.owner_id,
-                    seeds: data.seeds,
-                    farmers: data.farmers,
-                    farms: data.farms,
-                    outdated_farms: data.outdated_farms,
-                    nft_balance_seeds: LookupMap::new(StorageKeys::NftBalanceSeed),
-                    farmer_count: data.farmer_count,
-                    reward_info: data.reward_info,
-                };
-            }
+        let data = match self.data {
+            VersionedContractData::CurrentV4(data) => return data,
+            VersionedContractData::CurrentV3(data) => data,
+            VersionedContractData::CurrentV2(data) => ContractDataV3 {
+                owner_id: data.owner_id,
+                seeds: data.seeds,
+                farmers: data.farmers,
+                farms: data.farms,
+                outdated_farms: data.outdated_farms,
+                nft_balance_seeds: data.nft_balance_seeds,
+                farmer_count: data.farmer_count,
+                reward_info: data.reward_info,
+                nft_trait_rules: LookupMap::new(StorageKeys::NftTraitRules),
+                nft_trait_multipliers: LookupMap::new(StorageKeys::NftTraitMultiplier),
+            },
+            VersionedContractData::Current(data) => ContractDataV3 {
+                owner_id: data.owner_id,
+                seeds: data.seeds,
+                farmers: data.farmers,
+                farms: data.farms,
+                outdated_farms: data.outdated_farms,
+                nft_balance_seeds: LookupMap::new(StorageKeys::NftBalanceSeed),
+                farmer_count: data.farmer_count,
+                reward_info: data.reward_info,
+                nft_trait_rules: LookupMap::new(StorageKeys::NftTraitRules),
+                nft_trait_multipliers: LookupMap::new(StorageKeys::NftTraitMultiplier),
+            },
+        };
+        ContractData {
+            owner_id: data.owner_id,
+            seeds: data.seeds,
+            farmers: data.farmers,
+            farms: data.farms,
+            outdated_farms: data.outdated_farms,
+            nft_balance_seeds: data.nft_balance_seeds,
+            farmer_count: data.farmer_count,
+            reward_info: data.reward_info,
+            nft_trait_rules: data.nft_trait_rules,
+            nft_trait_multipliers: data.nft_trait_multipliers,
+            nft_approvals: LookupMap::new(StorageKeys::NftApproval),
+            nft_approval_stake_seeds: LookupSet::new(StorageKeys::NftApprovalStakeSeeds),
+            nft_approval_stakes: LookupMap::new(StorageKeys::NftApprovalStakes),
+            farmer_ids: UnorderedSet::new(StorageKeys::FarmerIds),
+            seed_stakers: LookupMap::new(StorageKeys::SeedStakersIndex),
+            farm_checkpoints: LookupMap::new(StorageKeys::FarmCheckpoints),
+            storage_sponsorship: StorageSponsorship::default(),
+            sponsored_storage: LookupMap::new(StorageKeys::SponsoredStorage),
+            auto_register_seeds: LookupSet::new(StorageKeys::AutoRegisterSeeds),
+            staking_managers: LookupMap::new(StorageKeys::StakingManagers),
+            receipt_tokens: LookupMap::new(StorageKeys::ReceiptTokens),
+            nft_receipt_owners: LookupMap::new(StorageKeys::NftReceiptOwners),
+            auto_compound_farmers: LookupMap::new(StorageKeys::AutoCompoundIndex),
+            zap_tokens: LookupSet::new(StorageKeys::ZapTokens),
+            zap_refunds: LookupMap::new(StorageKeys::ZapRefunds),
+            zap_refund_totals: LookupMap::new(StorageKeys::ZapRefundTotals),
+            zapping_exchanges: LookupMap::new(StorageKeys::ZappingExchanges),
+            zap_refund_ids: LookupMap::new(StorageKeys::ZapRefundIds),
+            nft_approval_held_rewards: LookupMap::new(StorageKeys::NftApprovalHeldRewards),
+            nft_approval_ids: LookupMap::new(StorageKeys::NftApprovalIds),
         }
     }
 
     fn data(&self) -> &ContractData {
         match &self.data {
-            VersionedContractData::CurrentV2(data) => data,
-            VersionedContractData::Current(_) => unimplemented!(),
+            VersionedContractData::CurrentV4(data) => data,
+            _ => unimplemented!(),
         }
     }
 
     fn data_mut(&mut self) -> &mut ContractData {
         match &mut self.data {
-            VersionedContractData::CurrentV2(data) => data,
-            VersionedContractData::Current(_) => unimplemented!(),
+            VersionedContractData::CurrentV4(data) => data,
+            _ => unimplemented!(),
         }
     }
 
     fn need_upgrade(&self) -> bool {
         match &self.data {
-            VersionedContractData::CurrentV2(_) => false,
-            VersionedContractData::Current(_) => true
+            VersionedContractData::CurrentV4(_) => false,
+            _ => true
         }
     }
 }
@@ -177,14 +383,21 @@ impl Contract {
 #[cfg(test)]
 mod tests {
 
+    use std::collections::HashMap;
+    use std::convert::TryFrom;
     use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenReceiver;
+    use near_contract_standards::non_fungible_token::approval::NonFungibleTokenApprovalReceiver;
     use near_sdk::test_utils::{accounts, VMContextBuilder};
-    use near_sdk::{testing_env, Balance, MockedBlockchain};
+    use near_sdk::{testing_env, Balance, MockedBlockchain, PromiseOrValue, PromiseResult};
     use near_sdk::json_types::{ValidAccountId, U128};
+    use near_sdk::serde_json::json;
     use simple_farm::HRSimpleFarmTerms;
     use near_contract_standards::storage_management::{StorageBalance, StorageManagement};
 
     use super::utils::*;
+    use super::view::AprPrices;
+    use super::storage_impl::UnregisterTransfers;
     use super::*;
 
     fn setup_contract() -> (VMContextBuilder, Contract) {
@@ -336,6 +549,99 @@ mod tests {
         contract.remove_user_rps_by_farm(farm_id)
     }
 
+    /// nft seed is eugene@1, staked nft comes from contract accounts(4)
+    fn create_nft_farm(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        nft_balance: HashMap<String, U128>,
+    ) -> FarmId {
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 1000)
+            .build());
+        contract.create_simple_farm(HRSimpleFarmTerms {
+            seed_id: String::from("eugene@1"),
+            reward_token: accounts(2),
+            start_at: 0,
+            reward_per_session: U128(5000),
+            session_interval: 50,
+        }, None, Some(nft_balance), None)
+    }
+
+    fn deposit_nft(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        farmer: ValidAccountId,
+        token_id: &str,
+    ) -> PromiseOrValue<bool> {
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .signer_account_id(farmer.clone())
+            .is_view(false)
+            .attached_deposit(0)
+            .build());
+        contract.nft_on_transfer(farmer.clone().into(), farmer.into(), token_id.to_string(), String::from("eugene@1"))
+    }
+
+    fn callback_nft_token(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        farmer: ValidAccountId,
+        token_id: &str,
+        promise_result: PromiseResult,
+    ) -> bool {
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).is_view(false).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![promise_result]
+        );
+        contract.callback_post_nft_token_traits(String::from("eugene@1"), farmer.into(), accounts(4).into(), token_id.to_string())
+    }
+
+    fn approve_nft(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        farmer: ValidAccountId,
+        token_id: &str,
+        approval_id: u64,
+    ) {
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .signer_account_id(farmer.clone())
+            .is_view(false)
+            .attached_deposit(0)
+            .build());
+        contract.nft_on_approve(token_id.to_string(), farmer.into(), approval_id, String::from("eugene@1"));
+    }
+
+    fn user_nfts(contract: &Contract, farmer: ValidAccountId) -> Vec<String> {
+        let mut nfts = contract.list_user_nft_seeds(farmer).remove("eugene@1").unwrap_or_default();
+        nfts.sort();
+        nfts
+    }
+
+    fn nft_token_result(token_id: &str, extra: Option<&str>) -> PromiseResult {
+        let token = json!({
+            "token_id": token_id,
+            "owner_id": accounts(0).to_string(),
+            "metadata": {
+                "title": "Paras card",
+                "extra": extra,
+            }
+        });
+        PromiseResult::Successful(token.to_string().into_bytes())
+    }
+
+    fn nft_owner_result(token_id: &str, owner_id: AccountId) -> PromiseResult {
+        let token = json!({
+            "token_id": token_id,
+            "owner_id": owner_id,
+        });
+        PromiseResult::Successful(token.to_string().into_bytes())
+    }
+
     fn to_yocto(value: &str) -> u128 {
         let vals: Vec<_> = value.split('.').collect();
         let part1 = vals[0].parse::<u128>().unwrap() * 10u128.pow(24);
@@ -532,7 +838,7 @@ mod tests {
         testing_env!(context.predecessor_account_id(accounts(0))
             .block_timestamp(to_nano(780)).is_view(true).build());
         let post_available = contract.storage_balance_of(accounts(0)).expect("Error").available.0;
-        assert_eq!(post_available - prev_available, 165*10_u128.pow(19));
+        assert_eq!(post_available - prev_available, 116*10_u128.pow(19));
 
         // withdraw seed
         println!("----> accounts(0) and accounts(3) withdraw seed");
@@ -703,4 +1009,1657 @@ mod tests {
 
         deposit_seed(&mut context, &mut contract, accounts(0), 60, 10);
     }
+
+    #[test]
+    fn test_nft_trait_rules() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+        let seed_id = String::from("eugene@1");
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        contract.set_nft_trait_rules(seed_id.clone(), vec![
+            NftTraitRule { trait_type: String::from("rarity"), value: String::from("legendary"), multiplier: 1000 },
+            NftTraitRule { trait_type: String::from("rarity"), value: String::from("rare"), multiplier: 300 },
+        ]);
+        assert_eq!(contract.get_nft_trait_rules(seed_id.clone()).len(), 2);
+
+        register_farmer(&mut context, &mut contract, accounts(3));
+
+        // legendary in attributes list, credited 10x after metadata is read
+        let res = deposit_nft(&mut context, &mut contract, accounts(3), "1:1");
+        assert!(matches!(res, PromiseOrValue::Promise(_)));
+        assert!(contract.list_user_seeds(accounts(3)).get(&seed_id).is_none());
+        let refund = callback_nft_token(&mut context, &mut contract, accounts(3), "1:1",
+            nft_token_result("1:1", Some(r#"{"attributes":[{"trait_type":"rarity","value":"legendary"}]}"#)));
+        assert!(!refund);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("10"));
+
+        // rare as plain trait object, 3x
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:2");
+        let refund = callback_nft_token(&mut context, &mut contract, accounts(3), "1:2",
+            nft_token_result("1:2", Some(r#"{"rarity":"rare"}"#)));
+        assert!(!refund);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("13"));
+
+        // no matching traits, 1x
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:3");
+        let refund = callback_nft_token(&mut context, &mut contract, accounts(3), "1:3",
+            nft_token_result("1:3", None));
+        assert!(!refund);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("14"));
+        assert_eq!(contract.get_seed_info(seed_id.clone()).unwrap().amount.0, to_yocto("14"));
+
+        // metadata can not be read, nft goes back to the owner
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:4");
+        let refund = callback_nft_token(&mut context, &mut contract, accounts(3), "1:4", PromiseResult::Failed);
+        assert!(refund);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("14"));
+
+        // withdraw the legendary one subtracts its credited weight
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.withdraw_nft(seed_id.clone(), accounts(4).into(), String::from("1:1"));
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("4"));
+        assert_eq!(contract.get_seed_info(seed_id.clone()).unwrap().amount.0, to_yocto("4"));
+    }
+
+    #[test]
+    fn test_update_nft_balance() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+        let seed_id = String::from("eugene@1");
+
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:1");
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("1"));
+
+        // new weight is applied on the next interaction of the farmer
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("2")));
+        nft_balance.insert(String::from("eugene@2"), U128(to_yocto("5")));
+        contract.set_nft_balance(seed_id.clone(), nft_balance);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("1"));
+
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:2");
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("4"));
+        deposit_nft(&mut context, &mut contract, accounts(3), "2:1");
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("9"));
+        assert_eq!(contract.get_seed_info(seed_id.clone()).unwrap().amount.0, to_yocto("9"));
+
+        // removed series has no weight left, but its nfts are still withdrawable
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        contract.remove_nft_balance(seed_id.clone(), vec![String::from("eugene@1")]);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.claim_reward_by_seed(seed_id.clone());
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("5"));
+        assert_eq!(contract.get_seed_info(seed_id.clone()).unwrap().amount.0, to_yocto("5"));
+
+        contract.withdraw_nft(seed_id.clone(), accounts(4).into(), String::from("1:1"));
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("5"));
+        contract.withdraw_nft(seed_id.clone(), accounts(4).into(), String::from("2:1"));
+        assert!(contract.list_user_seeds(accounts(3)).get(&seed_id).is_none());
+        assert_eq!(contract.get_seed_info(seed_id.clone()).unwrap().amount.0, 0);
+    }
+
+    #[test]
+    fn test_batch_nfts() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+        let seed_id = String::from("eugene@1");
+
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:1");
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:2");
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:3");
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("3"));
+
+        // withdraw in batch, the failed transfer is given back to the farmer
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.withdraw_nfts(seed_id.clone(), vec![String::from("eugene@1:1"), String::from("eugene@1:2")]);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("1"));
+        assert_eq!(user_nfts(&contract, accounts(3)), vec![String::from("eugene@1:3")]);
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![]), PromiseResult::Failed]
+        );
+        contract.callback_post_withdraw_nfts(seed_id.clone(), accounts(3).into(), vec![String::from("eugene@1:1"), String::from("eugene@1:2")]);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("2"));
+        assert_eq!(contract.get_seed_info(seed_id.clone()).unwrap().amount.0, to_yocto("2"));
+        assert_eq!(user_nfts(&contract, accounts(3)), vec![String::from("eugene@1:2"), String::from("eugene@1:3")]);
+
+        // stake approved nfts in batch, only transferred ones are credited
+        approve_nft(&mut context, &mut contract, accounts(3), "1:4", 1);
+        approve_nft(&mut context, &mut contract, accounts(3), "1:5", 1);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.stake_approved_nfts(seed_id.clone(), vec![String::from("eugene@1:4"), String::from("eugene@1:5")]);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("2"));
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![]), PromiseResult::Failed]
+        );
+        contract.callback_post_stake_approved_nfts(seed_id.clone(), accounts(3).into(), vec![String::from("eugene@1:4"), String::from("eugene@1:5")]);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("3"));
+        assert_eq!(contract.get_seed_info(seed_id.clone()).unwrap().amount.0, to_yocto("3"));
+        assert_eq!(
+            user_nfts(&contract, accounts(3)),
+            vec![String::from("eugene@1:2"), String::from("eugene@1:3"), String::from("eugene@1:4")]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Paras(farming): owner_id should be predecessor_id")]
+    fn test_stake_approved_nfts_of_other_owner() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+
+        register_farmer(&mut context, &mut contract, accounts(1));
+        register_farmer(&mut context, &mut contract, accounts(3));
+        approve_nft(&mut context, &mut contract, accounts(3), "1:1", 1);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        contract.stake_approved_nfts(String::from("eugene@1"), vec![String::from("eugene@1:1")]);
+    }
+
+    #[test]
+    fn test_parked_nft_approvals() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+        register_farmer(&mut context, &mut contract, accounts(1));
+        register_farmer(&mut context, &mut contract, accounts(3));
+
+        // parking is charged to the owner
+        let available = contract.storage_balance_of(accounts(3)).unwrap().available.0;
+        approve_nft(&mut context, &mut contract, accounts(3), "1:1", 1);
+        approve_nft(&mut context, &mut contract, accounts(3), "1:2", 1);
+        assert!(contract.storage_balance_of(accounts(3)).unwrap().available.0 < available);
+
+        // the nft moved to another owner, the stale approval is dropped and its storage released
+        let available = contract.storage_balance_of(accounts(3)).unwrap().available.0;
+        approve_nft(&mut context, &mut contract, accounts(1), "1:2", 2);
+        assert!(contract.storage_balance_of(accounts(3)).unwrap().available.0 > available);
+        assert_eq!(contract.data().nft_approvals.get(&String::from("eugene@1:2")).unwrap().owner_id, accounts(1).to_string());
+        assert_eq!(contract.data().nft_approval_ids.get(&accounts(3).into()).unwrap(), vec![String::from("eugene@1:1")]);
+
+        // the nft left the seed, its approval is dropped on the next stake of the owner
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1:2"), U128(to_yocto("1")));
+        contract.set_nft_balance(String::from("eugene@1"), nft_balance);
+        contract.remove_nft_balance(String::from("eugene@1"), vec![String::from("eugene@1")]);
+        approve_nft(&mut context, &mut contract, accounts(3), "1:2", 3);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.stake_approved_nfts(String::from("eugene@1"), vec![String::from("eugene@1:2")]);
+        assert!(contract.data().nft_approvals.get(&String::from("eugene@1:1")).is_none());
+        assert!(contract.data().nft_approval_ids.get(&accounts(3).into()).is_none());
+
+        // unregister drops the parked approvals
+        approve_nft(&mut context, &mut contract, accounts(1), "1:2", 4);
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        assert!(contract.storage_unregister(None));
+        assert!(contract.data().nft_approvals.get(&String::from("eugene@1:2")).is_none());
+        assert!(contract.data().nft_approval_ids.get(&accounts(1).into()).is_none());
+    }
+
+    #[test]
+    fn test_nft_approval_staking() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        let farm_id = create_nft_farm(&mut context, &mut contract, nft_balance);
+        let seed_id = String::from("eugene@1");
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        contract.set_nft_approval_staking(seed_id.clone(), true);
+        assert!(contract.is_nft_approval_staking(seed_id.clone()));
+
+        register_farmer(&mut context, &mut contract, accounts(1));
+        register_farmer(&mut context, &mut contract, accounts(3));
+
+        // staked in place, nfts stay in owner wallet
+        approve_nft(&mut context, &mut contract, accounts(3), "1:1", 1);
+        approve_nft(&mut context, &mut contract, accounts(3), "1:2", 1);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("2"));
+        assert_eq!(
+            contract.list_user_approval_staked_nfts(accounts(3), seed_id.clone()),
+            vec![String::from("eugene@1:1"), String::from("eugene@1:2")]
+        );
+        // approval staked nfts get no receipt
+        assert!(contract.nft_token(String::from("eugene@1:1")).is_none());
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.mint_staked_nft_receipts(seed_id.clone());
+        assert!(contract.nft_token(String::from("eugene@1:2")).is_none());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(to_nano(0))
+            .attached_deposit(1)
+            .build());
+        contract.ft_on_transfer(accounts(0), U128(50000), farm_id.clone());
+
+        // claim verifies ownership first, moved nft is slashed with its unclaimed reward
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(110)).attached_deposit(0).build());
+        contract.claim_reward_by_seed(seed_id.clone());
+        assert_eq!(contract.get_reward(accounts(3), accounts(2)).0, 0);
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![nft_owner_result("1:1", accounts(3).into()), nft_owner_result("1:2", accounts(1).into())]
+        );
+        contract.callback_post_verify_approval_stakes(
+            seed_id.clone(),
+            accounts(3).into(),
+            vec![String::from("eugene@1:1"), String::from("eugene@1:2")],
+            false
+        );
+        assert_eq!(contract.get_reward(accounts(3), accounts(2)).0, 5000);
+        assert_eq!(contract.get_farm(farm_id.clone()).unwrap().beneficiary_reward.0, 5000);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("1"));
+        assert_eq!(contract.get_seed_info(seed_id.clone()).unwrap().amount.0, to_yocto("1"));
+        assert_eq!(contract.list_user_approval_staked_nfts(accounts(3), seed_id.clone()), vec![String::from("eugene@1:1")]);
+
+        // approval of a new owner replaces the stake of the previous one
+        approve_nft(&mut context, &mut contract, accounts(1), "1:1", 2);
+        assert!(contract.list_user_seeds(accounts(3)).get(&seed_id).is_none());
+        assert!(user_nfts(&contract, accounts(3)).is_empty());
+        assert_eq!(contract.list_user_seeds(accounts(1)).get(&seed_id).unwrap().0, to_yocto("1"));
+        assert_eq!(contract.get_seed_info(seed_id.clone()).unwrap().amount.0, to_yocto("1"));
+
+        // withdraw needs no transfer once ownership is verified
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        contract.withdraw_nft(seed_id.clone(), accounts(4).into(), String::from("1:1"));
+        assert_eq!(contract.list_user_seeds(accounts(1)).get(&seed_id).unwrap().0, to_yocto("1"));
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![nft_owner_result("1:1", accounts(1).into())]
+        );
+        contract.callback_post_verify_approval_stakes(seed_id.clone(), accounts(1).into(), vec![String::from("eugene@1:1")], true);
+        assert!(contract.list_user_seeds(accounts(1)).get(&seed_id).is_none());
+        assert!(contract.list_user_approval_staked_nfts(accounts(1), seed_id.clone()).is_empty());
+        assert_eq!(contract.get_seed_info(seed_id.clone()).unwrap().amount.0, 0);
+    }
+
+    #[test]
+    fn test_unverified_approval_stake_reward() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        let farm_id = create_nft_farm(&mut context, &mut contract, nft_balance);
+        let seed_id = String::from("eugene@1");
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        contract.set_nft_approval_staking(seed_id.clone(), true);
+        register_farmer(&mut context, &mut contract, accounts(3));
+        approve_nft(&mut context, &mut contract, accounts(3), "1:1", 1);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(to_nano(0))
+            .attached_deposit(1)
+            .build());
+        contract.ft_on_transfer(accounts(0), U128(50000), farm_id.clone());
+
+        // a new approval settles the seed without verifying the staked one, its reward is held
+        testing_env!(context.block_timestamp(to_nano(110)).build());
+        approve_nft(&mut context, &mut contract, accounts(3), "1:2", 1);
+        assert_eq!(contract.get_reward(accounts(3), accounts(2)).0, 0);
+        assert_eq!(contract.get_farm(farm_id.clone()).unwrap().beneficiary_reward.0, 0);
+        assert_eq!(contract.get_approval_stake_held_rewards(String::from("eugene@1:1")).get(&farm_id).unwrap().0, 10000);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("2"));
+
+        // verified claim pays the share of both nfts
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).block_timestamp(to_nano(160)).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![nft_owner_result("1:1", accounts(3).into()), nft_owner_result("1:2", accounts(3).into())]
+        );
+        contract.callback_post_verify_approval_stakes(
+            seed_id.clone(),
+            accounts(3).into(),
+            vec![String::from("eugene@1:1"), String::from("eugene@1:2")],
+            false
+        );
+        assert_eq!(contract.get_reward(accounts(3), accounts(2)).0, 15000);
+        assert_eq!(contract.get_farm(farm_id.clone()).unwrap().beneficiary_reward.0, 0);
+        assert!(contract.get_approval_stake_held_rewards(String::from("eugene@1:1")).is_empty());
+
+        // held reward of a nft moved out of the wallet is forfeited,
+        // staking withdraws the claimed reward
+        testing_env!(context.block_timestamp(to_nano(210)).build());
+        approve_nft(&mut context, &mut contract, accounts(3), "1:3", 1);
+        assert_eq!(contract.get_reward(accounts(3), accounts(2)).0, 0);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).block_timestamp(to_nano(220)).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![nft_owner_result("1:1", accounts(3).into()), nft_owner_result("1:2", accounts(4).into())]
+        );
+        contract.callback_post_verify_approval_stakes(
+            seed_id.clone(),
+            accounts(3).into(),
+            vec![String::from("eugene@1:1"), String::from("eugene@1:2")],
+            false
+        );
+        assert_eq!(contract.get_reward(accounts(3), accounts(2)).0, 2500);
+        assert_eq!(contract.get_farm(farm_id.clone()).unwrap().beneficiary_reward.0, 2500);
+        assert!(contract.get_approval_stake_held_rewards(String::from("eugene@1:2")).is_empty());
+    }
+
+    #[test]
+    fn test_verify_staked_nfts() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+        let seed_id = String::from("eugene@1");
+
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:1");
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:2");
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:3");
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        contract.verify_staked_nfts(accounts(3), seed_id.clone(), 0, 2);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("3"));
+
+        // burned nft is evicted, the one still held by this contract is kept
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![nft_owner_result("1:1", env::current_account_id()), PromiseResult::Successful(b"null".to_vec())]
+        );
+        contract.callback_post_verify_staked_nfts(
+            seed_id.clone(),
+            accounts(3).into(),
+            vec![String::from("eugene@1:1"), String::from("eugene@1:2")]
+        );
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, to_yocto("2"));
+        assert_eq!(contract.get_seed_info(seed_id.clone()).unwrap().amount.0, to_yocto("2"));
+        assert_eq!(user_nfts(&contract, accounts(3)), vec![String::from("eugene@1:1"), String::from("eugene@1:3")]);
+    }
+
+    #[test]
+    fn test_list_farmers() {
+        let (mut context, mut contract) = setup_contract();
+        let farm_id = create_farm(&mut context, &mut contract,
+            accounts(1), accounts(2), to_yocto("1"), 50);
+        deposit_reward(&mut context, &mut contract, to_yocto("10"), 100);
+
+        register_farmer(&mut context, &mut contract, accounts(0));
+        register_farmer(&mut context, &mut contract, accounts(3));
+        register_farmer(&mut context, &mut contract, accounts(4));
+        deposit_seed(&mut context, &mut contract, accounts(0), 110, to_yocto("1"));
+        deposit_seed(&mut context, &mut contract, accounts(3), 110, to_yocto("1"));
+
+        // farmer without seeds leaves the index on unregister
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        assert!(contract.storage_unregister(None));
+
+        testing_env!(context.block_timestamp(to_nano(160)).is_view(true).build());
+        let farmers = contract.list_farmers(0, 10);
+        assert_eq!(farmers.len(), 2);
+        assert_eq!(farmers[0].farmer_id, accounts(0).to_string());
+        assert_eq!(farmers[0].seeds.get(&accounts(1).to_string()).unwrap().0, to_yocto("1"));
+        assert_eq!(farmers[0].unclaimed_rewards.get(&farm_id).unwrap().0, to_yocto("0.5"));
+        assert!(farmers[0].rewards.is_empty());
+        assert_eq!(contract.list_farmers(1, 10)[0].farmer_id, accounts(3).to_string());
+        assert_eq!(contract.get_metadata().farmer_count.0, 2);
+    }
+
+    #[test]
+    fn test_seed_stakers() {
+        let (mut context, mut contract) = setup_contract();
+        create_farm(&mut context, &mut contract,
+            accounts(1), accounts(2), to_yocto("1"), 50);
+        deposit_reward(&mut context, &mut contract, to_yocto("10"), 100);
+        let seed_id = accounts(1).to_string();
+
+        register_farmer(&mut context, &mut contract, accounts(0));
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_seed(&mut context, &mut contract, accounts(0), 110, to_yocto("1"));
+        deposit_seed(&mut context, &mut contract, accounts(3), 110, to_yocto("2"));
+        deposit_seed(&mut context, &mut contract, accounts(3), 120, to_yocto("1"));
+        assert_eq!(contract.get_seed_staker_count(seed_id.clone()), 2);
+        assert_eq!(contract.get_seed_info(seed_id.clone()).unwrap().staker_count.0, 2);
+        let stakers = contract.list_seed_stakers(seed_id.clone(), 1, 10);
+        assert_eq!(stakers.len(), 1);
+        assert_eq!(stakers[0].account_id, accounts(3).to_string());
+        assert_eq!(stakers[0].amount.0, to_yocto("3"));
+
+        // partial withdraw keeps the staker, full withdraw removes it
+        withdraw_seed(&mut context, &mut contract, accounts(3), 130, to_yocto("1"));
+        assert_eq!(contract.get_seed_staker_count(seed_id.clone()), 2);
+        withdraw_seed(&mut context, &mut contract, accounts(0), 130, to_yocto("1"));
+        assert_eq!(contract.get_seed_staker_count(seed_id.clone()), 1);
+        assert_eq!(contract.list_seed_stakers(seed_id.clone(), 0, 10)[0].amount.0, to_yocto("2"));
+    }
+
+    #[test]
+    fn test_farmer_overview() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        let farm_id = create_nft_farm(&mut context, &mut contract, nft_balance);
+        let seed_id = String::from("eugene@1");
+        assert!(contract.get_farmer_overview(accounts(3)).is_none());
+
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:1");
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:2");
+
+        let overview = contract.get_farmer_overview(accounts(3)).unwrap();
+        assert_eq!(overview.farmer_id, accounts(3).to_string());
+        assert!(overview.rewards.is_empty());
+        assert_eq!(overview.seeds.len(), 1);
+        let seed = &overview.seeds[0];
+        assert_eq!(seed.seed_id, seed_id);
+        assert_eq!(seed.amount.0, to_yocto("2"));
+        assert!(seed.locked_seed.is_none());
+        assert_eq!(seed.farms.len(), 1);
+        assert_eq!(seed.farms[0].farm_id, farm_id);
+        assert_eq!(seed.farms[0].reward_token, accounts(2).to_string());
+        assert_eq!(seed.farms[0].unclaimed_reward.0, 0);
+        assert_eq!(seed.nfts.len(), 2);
+        assert_eq!(seed.nfts[0].contract_nft_token_id, String::from("eugene@1:1"));
+        assert_eq!(seed.nfts[0].balance_equivalent.0, to_yocto("1"));
+    }
+
+    #[test]
+    fn test_farm_emission() {
+        let (mut context, mut contract) = setup_contract();
+        let farm_id = create_farm(&mut context, &mut contract,
+            accounts(1), accounts(2), to_yocto("1"), 50);
+        assert_eq!(contract.get_farm_emission(farm_id.clone(), None).unwrap().estimated_end_at, None);
+
+        // 10 rounds from 100
+        deposit_reward(&mut context, &mut contract, to_yocto("10"), 100);
+        let emission = contract.get_farm_emission(farm_id.clone(), None).unwrap();
+        assert_eq!(emission.emission_per_second.0, to_yocto("1") / 50);
+        assert_eq!(emission.emission_per_day.0, to_yocto("1728"));
+        assert_eq!(emission.emission_per_year.0, to_yocto("630720"));
+        assert_eq!(emission.estimated_end_at, Some(600));
+        assert_eq!(emission.reward_per_seed_per_day, "0");
+        assert_eq!(emission.apr, None);
+
+        register_farmer(&mut context, &mut contract, accounts(0));
+        deposit_seed(&mut context, &mut contract, accounts(0), 110, to_yocto("1"));
+        let emission = contract.get_farm_emission(farm_id.clone(), Some(AprPrices {
+            reward_token_price: U128(1),
+            reward_token_decimals: 24,
+            seed_price: U128(2),
+            seed_decimals: 24,
+        })).unwrap();
+        assert_eq!(emission.total_seeds.0, to_yocto("1"));
+        assert_eq!(emission.reward_per_seed_per_day, to_yocto("1728").to_string());
+        assert_eq!(emission.apr, Some(U128(630720 * 10_000 / 2)));
+
+        // topping up extends the farm
+        deposit_reward(&mut context, &mut contract, to_yocto("5"), 260);
+        assert_eq!(contract.get_farm_emission(farm_id, None).unwrap().estimated_end_at, Some(850));
+    }
+
+    #[test]
+    fn test_farm_emission_end_overflow() {
+        let (mut context, mut contract) = setup_contract();
+        let farm_id = create_farm(&mut context, &mut contract,
+            accounts(1), accounts(2), 1, 50);
+
+        // u32::MAX rounds of 50 seconds are beyond u32 seconds
+        deposit_reward(&mut context, &mut contract, u32::MAX as u128, 100);
+        assert_eq!(contract.get_farm_emission(farm_id.clone(), None).unwrap().estimated_end_at, None);
+        assert_eq!(contract.get_farm(farm_id.clone()).unwrap().estimated_end_at, None);
+
+        // more rounds than u32 are reported as u32::MAX
+        deposit_reward(&mut context, &mut contract, 1, 100);
+        assert_eq!(contract.get_farm(farm_id.clone()).unwrap().remaining_rounds, u32::MAX);
+        assert_eq!(contract.get_farm(farm_id).unwrap().estimated_end_at, None);
+    }
+
+    #[test]
+    fn test_farm_info_schedule() {
+        let (mut context, mut contract) = setup_contract();
+        let farm_id = create_farm(&mut context, &mut contract,
+            accounts(1), accounts(2), to_yocto("1"), 50);
+        let farm_info = contract.get_farm(farm_id.clone()).unwrap();
+        assert_eq!(farm_info.remaining_rounds, 0);
+        assert_eq!(farm_info.estimated_end_at, None);
+        assert_eq!(farm_info.seconds_to_next_round, None);
+
+        deposit_reward(&mut context, &mut contract, to_yocto("10"), 100);
+        let farm_info = contract.get_farm(farm_id.clone()).unwrap();
+        assert_eq!(farm_info.undistributed_reward.0, to_yocto("10"));
+        assert_eq!(farm_info.remaining_rounds, 10);
+        assert_eq!(farm_info.estimated_end_at, Some(600));
+        assert_eq!(farm_info.seconds_to_next_round, Some(50));
+
+        testing_env!(context.block_timestamp(to_nano(160)).is_view(true).build());
+        let farm_info = contract.get_farm(farm_id.clone()).unwrap();
+        assert_eq!(farm_info.undistributed_reward.0, to_yocto("9"));
+        assert_eq!(farm_info.remaining_rounds, 9);
+        assert_eq!(farm_info.estimated_end_at, Some(600));
+        assert_eq!(farm_info.seconds_to_next_round, Some(40));
+
+        testing_env!(context.block_timestamp(to_nano(700)).is_view(true).build());
+        let farm_info = contract.get_farm(farm_id).unwrap();
+        assert_eq!(farm_info.farm_status, "Ended");
+        assert_eq!(farm_info.undistributed_reward.0, 0);
+        assert_eq!(farm_info.remaining_rounds, 0);
+        assert_eq!(farm_info.estimated_end_at, Some(600));
+        assert_eq!(farm_info.seconds_to_next_round, None);
+    }
+
+    #[test]
+    fn test_farm_checkpoints() {
+        let (mut context, mut contract) = setup_contract();
+        let farm_id = create_farm(&mut context, &mut contract,
+            accounts(1), accounts(2), to_yocto("1"), 50);
+        deposit_reward(&mut context, &mut contract, to_yocto("10"), 100);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        contract.set_farm_checkpoints(farm_id.clone(), 2);
+
+        register_farmer(&mut context, &mut contract, accounts(0));
+        deposit_seed(&mut context, &mut contract, accounts(0), 110, to_yocto("1"));
+        assert!(contract.list_farm_checkpoints(farm_id.clone(), 0, 10).is_empty());
+
+        // the oldest checkpoint is dropped once the buffer is full
+        claim_reward(&mut context, &mut contract, accounts(0), 160);
+        claim_reward(&mut context, &mut contract, accounts(0), 210);
+        claim_reward(&mut context, &mut contract, accounts(0), 260);
+        claim_reward(&mut context, &mut contract, accounts(0), 270);
+        let checkpoints = contract.list_farm_checkpoints(farm_id.clone(), 0, 10);
+        assert_eq!(checkpoints.len(), 2);
+        assert_eq!(checkpoints[0].round, 2);
+        assert_eq!(checkpoints[0].timestamp, 210);
+        assert_eq!(checkpoints[1].round, 3);
+        assert_eq!(checkpoints[1].rps, to_yocto("3").to_string());
+        assert_eq!(checkpoints[1].total_seeds.0, to_yocto("1"));
+        assert_eq!(contract.list_farm_checkpoints(farm_id.clone(), 1, 10)[0].round, 3);
+
+        // changing the capacity keeps the latest checkpoints
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        contract.set_farm_checkpoints(farm_id.clone(), 2);
+        assert_eq!(contract.list_farm_checkpoints(farm_id.clone(), 0, 10).len(), 2);
+        contract.set_farm_checkpoints(farm_id.clone(), 3);
+        claim_reward(&mut context, &mut contract, accounts(0), 310);
+        let rounds: Vec<u32> = contract.list_farm_checkpoints(farm_id.clone(), 0, 10).iter().map(|c| c.round).collect();
+        assert_eq!(rounds, vec![2, 3, 4]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        contract.set_farm_checkpoints(farm_id.clone(), 1);
+        let checkpoints = contract.list_farm_checkpoints(farm_id.clone(), 0, 10);
+        assert_eq!(checkpoints.len(), 1);
+        assert_eq!(checkpoints[0].round, 4);
+
+        contract.set_farm_checkpoints(farm_id.clone(), 0);
+        assert!(contract.list_farm_checkpoints(farm_id, 0, 10).is_empty());
+    }
+
+    #[test]
+    fn test_lifetime_claimed_rewards() {
+        let (mut context, mut contract) = setup_contract();
+        let farm_id = create_farm(&mut context, &mut contract,
+            accounts(1), accounts(2), to_yocto("1"), 50);
+        deposit_reward(&mut context, &mut contract, to_yocto("10"), 100);
+        register_farmer(&mut context, &mut contract, accounts(0));
+        deposit_seed(&mut context, &mut contract, accounts(0), 110, to_yocto("1"));
+        let storage_before = contract.storage_balance_of(accounts(0)).unwrap();
+
+        claim_reward(&mut context, &mut contract, accounts(0), 160);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.withdraw_reward(accounts(2), None);
+        assert!(contract.list_rewards(accounts(0)).is_empty());
+
+        // totals are kept after the reward is withdrawn
+        claim_reward(&mut context, &mut contract, accounts(0), 210);
+        assert_eq!(contract.get_reward(accounts(0), accounts(2)).0, to_yocto("1"));
+        assert_eq!(contract.list_user_claimed_rewards(accounts(0)).get(&accounts(2).to_string()).unwrap().0, to_yocto("2"));
+        assert_eq!(contract.list_user_farm_claimed_rewards(accounts(0)).get(&farm_id).unwrap().0, to_yocto("2"));
+        // one more reward entry, two more lifetime entries
+        let storage_after = contract.storage_balance_of(accounts(0)).unwrap();
+        assert_eq!(
+            storage_before.available.0 - storage_after.available.0,
+            79 * env::storage_byte_cost()
+        );
+
+        // farm total is dropped with the rps of the farm once the farmer leaves the seed
+        withdraw_seed(&mut context, &mut contract, accounts(0), 260, to_yocto("1"));
+        assert!(contract.list_user_farm_claimed_rewards(accounts(0)).is_empty());
+        assert_eq!(contract.list_user_claimed_rewards(accounts(0)).get(&accounts(2).to_string()).unwrap().0, to_yocto("3"));
+    }
+
+    #[test]
+    fn test_farmer_storage_usage() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+        let seed_id = String::from("eugene@1");
+        register_farmer(&mut context, &mut contract, accounts(3));
+        let registered = contract.storage_balance_of(accounts(3)).unwrap();
+
+        // each staked nft is kept in a farmer owned set
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:1");
+        let one_nft = contract.storage_balance_of(accounts(3)).unwrap();
+        assert!(one_nft.available.0 < registered.available.0);
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:2");
+        let two_nfts = contract.storage_balance_of(accounts(3)).unwrap();
+        assert!(two_nfts.available.0 < one_nft.available.0);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.withdraw_nft(seed_id.clone(), accounts(4).into(), String::from("1:2"));
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.callback_post_withdraw_nft(seed_id.clone(), accounts(3).into(), accounts(4).into(), String::from("1:2"));
+        assert_eq!(contract.storage_balance_of(accounts(3)).unwrap().available.0, one_nft.available.0);
+    }
+
+    #[test]
+    fn test_storage_sponsorship() {
+        let (mut context, mut contract) = setup_contract();
+        let min_storage = Contract::suggested_min_storage_usage();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(2 * min_storage).build());
+        contract.deposit_storage_sponsorship();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        contract.set_storage_sponsorship_cap(U128(min_storage));
+
+        // registration without deposit is covered by the pool
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(0).build());
+        let sb = contract.storage_deposit(None, Some(true));
+        assert_eq!(sb.total.0, min_storage);
+        assert_eq!(sb.available.0, 0);
+        assert_eq!(contract.get_sponsored_storage(accounts(3)).0, min_storage);
+        let storage_sponsorship = contract.get_storage_sponsorship();
+        assert_eq!(storage_sponsorship.balance.0, min_storage);
+        assert_eq!(storage_sponsorship.sponsored.0, min_storage);
+
+        // clawed back on unregister
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        assert!(contract.storage_unregister(None));
+        assert_eq!(contract.get_sponsored_storage(accounts(3)).0, 0);
+        let storage_sponsorship = contract.get_storage_sponsorship();
+        assert_eq!(storage_sponsorship.balance.0, 2 * min_storage);
+        assert_eq!(storage_sponsorship.sponsored.0, 0);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.withdraw_storage_sponsorship(None);
+        assert_eq!(contract.get_storage_sponsorship().balance.0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "E11: insufficient $NEAR storage deposit")]
+    fn test_storage_sponsorship_third_party() {
+        let (mut context, mut contract) = setup_contract();
+        let min_storage = Contract::suggested_min_storage_usage();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(2 * min_storage).build());
+        contract.deposit_storage_sponsorship();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        contract.set_storage_sponsorship_cap(U128(min_storage));
+
+        // registering someone else with full deposit takes nothing from the pool
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(min_storage).build());
+        contract.storage_deposit(Some(accounts(3)), Some(true));
+        assert_eq!(contract.get_sponsored_storage(accounts(3)).0, 0);
+        assert_eq!(contract.get_storage_sponsorship().balance.0, 2 * min_storage);
+
+        // and the shortfall is not sponsored
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(0).build());
+        contract.storage_deposit(Some(accounts(4)), Some(true));
+    }
+
+    #[test]
+    fn test_auto_register() {
+        let (mut context, mut contract) = setup_contract();
+        create_farm(&mut context, &mut contract, accounts(1), accounts(2), 5000, 50);
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+
+        // deposits of unregistered accounts are sent back
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        let ret = contract.ft_on_transfer(accounts(3), U128(10), String::from(""));
+        assert!(matches!(ret, PromiseOrValue::Value(U128(10))));
+        assert!(matches!(deposit_nft(&mut context, &mut contract, accounts(3), "1:1"), PromiseOrValue::Value(true)));
+        assert!(contract.get_farmer_overview(accounts(3)).is_none());
+
+        let min_storage = Contract::suggested_min_storage_usage();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(min_storage).build());
+        contract.deposit_storage_sponsorship();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        contract.set_storage_sponsorship_cap(U128(min_storage));
+        contract.set_seed_auto_register(accounts(1).into(), true);
+        assert!(contract.is_seed_auto_register(accounts(1).into()));
+
+        // first deposit registers the farmer
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        let ret = contract.ft_on_transfer(accounts(3), U128(10), String::from(""));
+        assert!(matches!(ret, PromiseOrValue::Value(U128(0))));
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&accounts(1).to_string()).unwrap().0, 10);
+        assert_eq!(contract.get_sponsored_storage(accounts(3)).0, min_storage);
+
+        // pool is empty, nft seed is not auto registering anyway
+        assert!(matches!(deposit_nft(&mut context, &mut contract, accounts(0), "1:2"), PromiseOrValue::Value(true)));
+    }
+
+    #[test]
+    fn test_delegated_staking() {
+        let (mut context, mut contract) = setup_contract();
+        create_farm(&mut context, &mut contract, accounts(1), accounts(2), 5000, 50);
+        register_farmer(&mut context, &mut contract, accounts(3));
+        let msg = format!("{{\"beneficiary_id\":\"{}\",\"staking_manager_id\":\"{}\"}}", accounts(3), accounts(0));
+
+        // danny lets alice manage the position, alice stakes on his behalf
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.set_staking_manager(accounts(1).into(), Some(accounts(0)));
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        let ret = contract.ft_on_transfer(accounts(0), U128(10), msg.clone());
+        assert!(matches!(ret, PromiseOrValue::Value(U128(0))));
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&accounts(1).to_string()).unwrap().0, 10);
+        assert_eq!(contract.get_staking_manager(accounts(3), accounts(1).into()), Some(accounts(0).into()));
+
+        // managed position can not be topped up without its staking manager
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        let ret = contract.ft_on_transfer(accounts(3), U128(10), String::from(""));
+        assert!(matches!(ret, PromiseOrValue::Value(U128(10))));
+
+        // beneficiary without storage gets the deposit refunded
+        let msg_unregistered = format!("{{\"beneficiary_id\":\"{}\"}}", accounts(4));
+        let ret = contract.ft_on_transfer(accounts(0), U128(10), msg_unregistered);
+        assert!(matches!(ret, PromiseOrValue::Value(U128(10))));
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.withdraw_seed_for(accounts(3), accounts(1).into(), U128(4));
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&accounts(1).to_string()).unwrap().0, 6);
+
+        // manager hands the position over to the farmer
+        contract.release_staking_manager(accounts(3), accounts(1).into());
+        assert!(contract.get_staking_manager(accounts(3), accounts(1).into()).is_none());
+        withdraw_seed(&mut context, &mut contract, accounts(3), 60, 6);
+        assert!(contract.list_user_seeds(accounts(3)).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "E61: seed position is withdrawn by its staking manager")]
+    fn test_delegated_staking_farmer_withdraw() {
+        let (mut context, mut contract) = setup_contract();
+        create_farm(&mut context, &mut contract, accounts(1), accounts(2), 5000, 50);
+        register_farmer(&mut context, &mut contract, accounts(3));
+        let msg = format!("{{\"staking_manager_id\":\"{}\"}}", accounts(0));
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        contract.ft_on_transfer(accounts(3), U128(10), msg);
+        withdraw_seed(&mut context, &mut contract, accounts(3), 60, 10);
+    }
+
+    #[test]
+    fn test_delegated_staking_manager_not_approved() {
+        let (mut context, mut contract) = setup_contract();
+        create_farm(&mut context, &mut contract, accounts(1), accounts(2), 5000, 50);
+        register_farmer(&mut context, &mut contract, accounts(3));
+
+        // a third party can not attach a staking manager to danny's position
+        let msg = format!("{{\"beneficiary_id\":\"{}\",\"staking_manager_id\":\"{}\"}}", accounts(3), accounts(0));
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        let ret = contract.ft_on_transfer(accounts(0), U128(10), msg);
+        assert!(matches!(ret, PromiseOrValue::Value(U128(10))));
+        assert!(contract.get_staking_manager(accounts(3), accounts(1).into()).is_none());
+        assert!(contract.list_user_seeds(accounts(3)).is_empty());
+
+        // nor one other than the manager danny opted in
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.set_staking_manager(accounts(1).into(), Some(accounts(2)));
+        let msg = format!("{{\"beneficiary_id\":\"{}\",\"staking_manager_id\":\"{}\"}}", accounts(3), accounts(0));
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        let ret = contract.ft_on_transfer(accounts(0), U128(10), msg);
+        assert!(matches!(ret, PromiseOrValue::Value(U128(10))));
+        assert_eq!(contract.get_staking_manager(accounts(3), accounts(1).into()), Some(accounts(2).into()));
+    }
+
+    #[test]
+    fn test_transfer_seed() {
+        let (mut context, mut contract) = setup_contract();
+        let farm_id = create_farm(&mut context, &mut contract, accounts(1), accounts(2), 5000, 50);
+        deposit_reward(&mut context, &mut contract, 50000, 100);
+        register_farmer(&mut context, &mut contract, accounts(0));
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_seed(&mut context, &mut contract, accounts(3), 160, 10);
+
+        // 4 of 10 locked, only the rest can be transferred
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(160)).attached_deposit(1).build());
+        contract.lock_ft_balance(accounts(1).into(), U128(4), 1000);
+
+        // both farmers are settled before the seed moves
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(210)).attached_deposit(1).build());
+        contract.transfer_seed(accounts(1).into(), accounts(0), U128(6));
+        assert_eq!(contract.get_reward(accounts(3), accounts(2)), U128(5000));
+        assert_eq!(contract.get_unclaimed_reward(accounts(0), farm_id.clone()), U128(0));
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&accounts(1).to_string()).unwrap().0, 10 - 6);
+        assert_eq!(contract.list_user_seeds(accounts(0)).get(&accounts(1).to_string()).unwrap().0, 6);
+        assert_eq!(contract.get_seed_info(accounts(1).into()).unwrap().amount, U128(10));
+
+        testing_env!(context.block_timestamp(to_nano(260)).is_view(true).build());
+        assert_eq!(contract.get_unclaimed_reward(accounts(3), farm_id.clone()), U128(2000));
+        assert_eq!(contract.get_unclaimed_reward(accounts(0), farm_id.clone()), U128(3000));
+    }
+
+    #[test]
+    #[should_panic(expected = "E32: not enough amount of seed")]
+    fn test_transfer_locked_seed() {
+        let (mut context, mut contract) = setup_contract();
+        create_farm(&mut context, &mut contract, accounts(1), accounts(2), 5000, 50);
+        register_farmer(&mut context, &mut contract, accounts(0));
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_seed(&mut context, &mut contract, accounts(3), 60, 10);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.lock_ft_balance(accounts(1).into(), U128(4), 1000);
+        contract.transfer_seed(accounts(1).into(), accounts(0), U128(7));
+    }
+
+    #[test]
+    fn test_transfer_staked_nft() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+        register_farmer(&mut context, &mut contract, accounts(0));
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:1");
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:2");
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.transfer_staked_nft(String::from("eugene@1"), accounts(0), accounts(4).into(), String::from("1:1"));
+        assert_eq!(user_nfts(&contract, accounts(3)), vec![String::from("eugene@1:2")]);
+        assert_eq!(user_nfts(&contract, accounts(0)), vec![String::from("eugene@1:1")]);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get("eugene@1").unwrap().0, to_yocto("1"));
+        assert_eq!(contract.list_user_seeds(accounts(0)).get("eugene@1").unwrap().0, to_yocto("1"));
+    }
+
+    #[test]
+    fn test_receipt_token() {
+        let (mut context, mut contract) = setup_contract();
+        create_farm(&mut context, &mut contract, accounts(1), accounts(2), 5000, 50);
+        register_farmer(&mut context, &mut contract, accounts(0));
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_seed(&mut context, &mut contract, accounts(3), 60, 10);
+        let seed_id: SeedId = accounts(1).into();
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).prepaid_gas(300_000_000_000_000).build());
+        contract.set_seed_receipt_token(seed_id.clone(), Some(accounts(5)));
+        assert_eq!(contract.get_seed_receipt_token(seed_id.clone()), Some(accounts(5).into()));
+        // balances staked before the receipt token are pushed by the owner
+        assert_eq!(contract.sync_receipt_token(seed_id.clone(), 0, 100), 1);
+
+        // receipt moves the staked position
+        testing_env!(context.predecessor_account_id(accounts(5)).attached_deposit(0).prepaid_gas(300_000_000_000_000).build());
+        contract.receipt_transfer(seed_id.clone(), accounts(3), accounts(0), U128(4), None);
+        assert_eq!(contract.list_user_seeds(accounts(0)).get(&seed_id).unwrap().0, 4);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, 6);
+
+        // unused part of transfer call comes back to the sender
+        let used = contract.receipt_refund(seed_id.clone(), accounts(3), accounts(0), U128(3), U128(1));
+        assert_eq!(used, U128(2));
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, 7);
+        assert_eq!(contract.list_user_seeds(accounts(0)).get(&seed_id).unwrap().0, 3);
+
+        // refund is capped by what the receiver still holds and never panics
+        let used = contract.receipt_refund(seed_id.clone(), accounts(3), accounts(0), U128(5), U128(5));
+        assert_eq!(used, U128(2));
+        assert!(contract.list_user_seeds(accounts(0)).get(&seed_id).is_none());
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, 10);
+        let used = contract.receipt_refund(seed_id.clone(), accounts(3), accounts(0), U128(1), U128(1));
+        assert_eq!(used, U128(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E70: predecessor is not the receipt token of the seed")]
+    fn test_receipt_transfer_not_receipt_token() {
+        let (mut context, mut contract) = setup_contract();
+        create_farm(&mut context, &mut contract, accounts(1), accounts(2), 5000, 50);
+        register_farmer(&mut context, &mut contract, accounts(0));
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_seed(&mut context, &mut contract, accounts(3), 60, 10);
+        let seed_id: SeedId = accounts(1).into();
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        contract.set_seed_receipt_token(seed_id.clone(), Some(accounts(5)));
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(0).build());
+        contract.receipt_transfer(seed_id, accounts(3), accounts(0), U128(4), None);
+    }
+
+    #[test]
+    fn test_nft_receipt() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+        register_farmer(&mut context, &mut contract, accounts(0));
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:1");
+        let token_id = String::from("eugene@1:1");
+        let receipt = contract.nft_token(token_id.clone()).unwrap();
+        assert_eq!(receipt.owner_id, accounts(3).to_string());
+        assert!(receipt.metadata.unwrap().extra.unwrap().contains("\"seed_id\":\"eugene@1\""));
+
+        // transfer call moves the stake, and back when the receiver asks for it
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).prepaid_gas(300_000_000_000_000).build());
+        let ret = contract.nft_transfer_call(accounts(0), token_id.clone(), None, None, String::from(""));
+        assert!(matches!(ret, PromiseOrValue::Promise(_)));
+        drop(ret);
+        assert_eq!(user_nfts(&contract, accounts(0)), vec![token_id.clone()]);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"true".to_vec())]
+        );
+        assert!(!contract.nft_resolve_transfer(accounts(3).into(), accounts(0).into(), token_id.clone(), None));
+        assert_eq!(contract.nft_token(token_id.clone()).unwrap().owner_id, accounts(3).to_string());
+        assert_eq!(user_nfts(&contract, accounts(3)), vec![token_id.clone()]);
+
+        // holder of the receipt withdraws the nft, burning the receipt
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.nft_transfer(accounts(0), token_id.clone(), None, None);
+        assert_eq!(contract.list_user_seeds(accounts(0)).get("eugene@1").unwrap().0, to_yocto("1"));
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.withdraw_nft(String::from("eugene@1"), accounts(4).into(), String::from("1:1"));
+        assert!(contract.nft_token(token_id).is_none());
+        assert!(user_nfts(&contract, accounts(0)).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "E90: staked nft receipt can not be transferred to this contract")]
+    fn test_nft_receipt_transfer_to_self() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:1");
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).prepaid_gas(300_000_000_000_000).build());
+        contract.nft_transfer_call(ValidAccountId::try_from(env::current_account_id()).unwrap(), String::from("eugene@1:1"), None, None, String::from(""));
+    }
+
+    #[test]
+    fn test_nft_receipt_unregistered_receiver() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+        register_farmer(&mut context, &mut contract, accounts(3));
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(env::storage_byte_cost() * 1852).build());
+        contract.storage_deposit(None, None);
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:1");
+        let token_id = String::from("eugene@1:1");
+        let deposited = contract.storage_balance_of(accounts(3)).unwrap().total.0;
+
+        // the sender pays the registration of the receiver
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.nft_transfer(accounts(0), token_id.clone(), None, None);
+        assert_eq!(contract.nft_token(token_id.clone()).unwrap().owner_id, accounts(0).to_string());
+        assert_eq!(contract.list_user_seeds(accounts(0)).get("eugene@1").unwrap().0, to_yocto("1"));
+        let registration = contract.storage_balance_of(accounts(0)).unwrap().total.0;
+        assert_eq!(registration, Contract::suggested_min_storage_usage());
+        assert_eq!(contract.storage_balance_of(accounts(3)).unwrap().total.0, deposited - registration);
+    }
+
+    #[test]
+    #[should_panic(expected = "E11: insufficient $NEAR storage deposit")]
+    fn test_nft_receipt_unregistered_receiver_no_storage() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(to_yocto("1")));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:1");
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.nft_transfer(accounts(0), String::from("eugene@1:1"), None, None);
+    }
+
+    #[test]
+    fn test_compound() {
+        let (mut context, mut contract) = setup_contract();
+        let farm_id = create_farm(&mut context, &mut contract, accounts(1), accounts(1), 5000, 50);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(to_nano(100)).attached_deposit(1).build());
+        contract.ft_on_transfer(accounts(0), U128(50000), farm_id.clone());
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_seed(&mut context, &mut contract, accounts(3), 160, 10);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(260)).attached_deposit(1).build());
+        let compounded = contract.compound(vec![accounts(1).into()], vec![], accounts(1).into(), Some(U128(10000)));
+        assert_eq!(compounded, U128(10000));
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&accounts(1).to_string()).unwrap().0, 10010);
+        assert_eq!(contract.get_reward(accounts(3), accounts(1)), U128(0));
+        assert_eq!(contract.get_seed_info(accounts(1).into()).unwrap().amount, U128(10010));
+    }
+
+    #[test]
+    #[should_panic(expected = "E76: compounded amount below min_amount")]
+    fn test_compound_min_amount() {
+        let (mut context, mut contract) = setup_contract();
+        let farm_id = create_farm(&mut context, &mut contract, accounts(1), accounts(1), 5000, 50);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(to_nano(100)).attached_deposit(1).build());
+        contract.ft_on_transfer(accounts(0), U128(50000), farm_id.clone());
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_seed(&mut context, &mut contract, accounts(3), 160, 10);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(260)).attached_deposit(1).build());
+        contract.compound(vec![], vec![farm_id], accounts(1).into(), Some(U128(10001)));
+    }
+
+    #[test]
+    fn test_auto_compound() {
+        let (mut context, mut contract) = setup_contract();
+        let farm_id = create_farm(&mut context, &mut contract, accounts(1), accounts(1), 5000, 50);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(to_nano(100)).attached_deposit(1).build());
+        contract.ft_on_transfer(accounts(0), U128(50000), farm_id.clone());
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_seed(&mut context, &mut contract, accounts(3), 160, 10);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(160)).attached_deposit(1).build());
+        contract.set_auto_compound(accounts(1).into(), true);
+        assert!(contract.is_auto_compound(accounts(3), accounts(1).into()));
+        assert_eq!(contract.get_auto_compound_farmer_count(accounts(1).into()), 1);
+
+        // keeper settles opted in farmers
+        testing_env!(context.predecessor_account_id(accounts(0)).block_timestamp(to_nano(260)).attached_deposit(0).build());
+        assert_eq!(contract.auto_compound(accounts(1).into(), 0, 10), 1);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&accounts(1).to_string()).unwrap().0, 10010);
+        assert_eq!(contract.get_reward(accounts(3), accounts(1)), U128(0));
+
+        // so does any interaction of the farmer with the seed
+        // reward per seed rounds down over the grown seed
+        deposit_seed(&mut context, &mut contract, accounts(3), 310, 10);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&accounts(1).to_string()).unwrap().0, 10010 + 4999 + 10);
+        assert_eq!(contract.get_seed_info(accounts(1).into()).unwrap().amount, U128(10010 + 4999 + 10));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(310)).attached_deposit(1).build());
+        contract.set_auto_compound(accounts(1).into(), false);
+        assert_eq!(contract.get_auto_compound_farmer_count(accounts(1).into()), 0);
+    }
+
+    #[test]
+    fn test_auto_compound_batch_size() {
+        let (mut context, mut contract) = setup_contract();
+        create_farm(&mut context, &mut contract, accounts(1), accounts(1), 5000, 50);
+        for i in 0..MAX_AUTO_COMPOUND_BATCH_SIZE + 1 {
+            let farmer = ValidAccountId::try_from(format!("farmer{}.near", i)).unwrap();
+            register_farmer(&mut context, &mut contract, farmer.clone());
+            deposit_seed(&mut context, &mut contract, farmer.clone(), 60, 10);
+            testing_env!(context.predecessor_account_id(farmer).attached_deposit(1).build());
+            contract.set_auto_compound(accounts(1).into(), true);
+        }
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        assert_eq!(contract.auto_compound(accounts(1).into(), 0, u64::MAX), MAX_AUTO_COMPOUND_BATCH_SIZE);
+        assert_eq!(contract.auto_compound(accounts(1).into(), MAX_AUTO_COMPOUND_BATCH_SIZE, u64::MAX), 1);
+    }
+
+    #[test]
+    fn test_auto_compound_storage() {
+        let (mut context, mut contract) = setup_contract();
+        let farm_id = create_farm(&mut context, &mut contract, accounts(1), accounts(1), 5000, 50);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(to_nano(100)).attached_deposit(1).build());
+        contract.ft_on_transfer(accounts(0), U128(50000), farm_id);
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_seed(&mut context, &mut contract, accounts(3), 160, 10);
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(160)).attached_deposit(1).build());
+        contract.set_auto_compound(accounts(1).into(), true);
+        storage_withdraw(&mut context, &mut contract, accounts(3));
+
+        // settling would add reward records the storage deposit does not cover
+        testing_env!(context.predecessor_account_id(accounts(0)).block_timestamp(to_nano(260)).attached_deposit(0).build());
+        assert_eq!(contract.auto_compound(accounts(1).into(), 0, 10), 0);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&accounts(1).to_string()).unwrap().0, 10);
+        assert!(contract.is_auto_compound(accounts(3), accounts(1).into()));
+    }
+
+    fn create_pool_farm(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+    ) -> SeedId {
+        let seed_id = format!("{}@0", accounts(5));
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 592)
+            .build());
+        contract.create_simple_farm(HRSimpleFarmTerms {
+            seed_id: seed_id.clone(),
+            reward_token: accounts(2).into(),
+            start_at: 0,
+            reward_per_session: U128(5000),
+            session_interval: 50,
+        }, Some(U128(10)), None, None);
+        seed_id
+    }
+
+    fn zap_promise_result(context: &mut VMContextBuilder, promise_results: Vec<PromiseResult>) {
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).prepaid_gas(300_000_000_000_000).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            promise_results
+        );
+    }
+
+    fn zap_args(seed_id: &SeedId, swap_amount: Balance, min_shares: Balance) -> ZapArgs {
+        ZapArgs {
+            target_seed_id: seed_id.clone(),
+            reward_token: accounts(1).into(),
+            pool_token_ids: vec![accounts(1).into(), accounts(2).into()],
+            swap_amount: U128(swap_amount),
+            min_amount_out: U128(1),
+            min_shares: U128(min_shares),
+        }
+    }
+
+    /// farmer danny with 10000 of bob reward to zap into the pool seed of fargo
+    fn setup_zap(context: &mut VMContextBuilder, contract: &mut Contract) -> SeedId {
+        let farm_id = create_farm(context, contract, accounts(1), accounts(1), 5000, 50);
+        let seed_id = create_pool_farm(context, contract);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(to_nano(100)).attached_deposit(1).build());
+        contract.ft_on_transfer(accounts(0), U128(50000), farm_id);
+        register_farmer(context, contract, accounts(3));
+        deposit_seed(context, contract, accounts(3), 160, 10);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).prepaid_gas(300_000_000_000_000).build());
+        contract.set_zap_tokens(accounts(5), vec![accounts(1), accounts(2)], true);
+        seed_id
+    }
+
+    fn claim_and_zap(context: &mut VMContextBuilder, contract: &mut Contract, args: ZapArgs) {
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(260)).attached_deposit(1).prepaid_gas(300_000_000_000_000).build());
+        contract.claim_and_zap(vec![accounts(1).into()], vec![], args);
+    }
+
+    fn zap_stalled(contract: &Contract) -> bool {
+        contract.data().zapping_exchanges.get(&accounts(5).into()).unwrap().stalled.is_some()
+    }
+
+    #[test]
+    fn test_claim_and_zap() {
+        let (mut context, mut contract) = setup_contract();
+        let seed_id = setup_zap(&mut context, &mut contract);
+        let args = zap_args(&seed_id, 4000, 700);
+        claim_and_zap(&mut context, &mut contract, args.clone());
+        assert_eq!(contract.get_reward(accounts(3), accounts(1)), U128(0));
+
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"\"10000\"".to_vec())]);
+        contract.callback_post_zap_deposit(accounts(3).into(), args.clone(), U128(10000));
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"\"3500\"".to_vec())]);
+        contract.callback_post_zap_swap(accounts(3).into(), args.clone(), U128(10000));
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"\"300\"".to_vec())]);
+        contract.callback_post_zap_shares_before(accounts(3).into(), args.clone(), U128(10000), U128(3500));
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"\"700\"".to_vec())]);
+        contract.callback_post_zap_add_liquidity(accounts(3).into(), args, U128(10000), U128(300));
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, 700);
+        assert_eq!(contract.get_seed_info(seed_id).unwrap().amount, U128(700));
+
+        // what add_liquidity did not use is withdrawn to the farmer, beside the zap refunds of others
+        contract.data_mut().zap_refund_totals.insert(&(accounts(5).into(), accounts(2).into()), &20);
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"{\"bob\":\"0\",\"charlie\":\"120\"}".to_vec())]);
+        contract.callback_post_zap_sweep(accounts(3).into(), accounts(5).into(), vec![accounts(1).into(), accounts(2).into()]);
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(vec![])]);
+        contract.callback_post_zap_withdraw(accounts(3).into(), accounts(5).into(), vec![accounts(2).into()], vec![U128(100)]);
+
+        // credited once the deposits confirm the withdraw left the exchange
+        assert_eq!(contract.get_reward(accounts(3), accounts(2)), U128(0));
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"{\"bob\":\"0\",\"charlie\":\"20\"}".to_vec())]);
+        contract.callback_post_zap_confirm(accounts(3).into(), accounts(5).into(), vec![accounts(2).into()], vec![U128(100)]);
+        assert_eq!(contract.get_reward(accounts(3), accounts(1)), U128(0));
+        assert_eq!(contract.get_reward(accounts(3), accounts(2)), U128(100));
+
+        // the zap is done, the next one can run
+        assert!(contract.data().zapping_exchanges.get(&accounts(5).into()).is_none());
+    }
+
+    #[test]
+    fn test_claim_and_zap_unreadable_shares() {
+        let (mut context, mut contract) = setup_contract();
+        let seed_id = setup_zap(&mut context, &mut contract);
+        let args = zap_args(&seed_id, 4000, 1);
+        claim_and_zap(&mut context, &mut contract, args.clone());
+
+        // add_liquidity succeeded without a readable value, the shares are read from the share balance
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(vec![])]);
+        contract.callback_post_zap_add_liquidity(accounts(3).into(), args.clone(), U128(10000), U128(300));
+        assert!(contract.list_user_seeds(accounts(3)).get(&seed_id).is_none());
+
+        // the zap stalls until the share balance is read again
+        zap_promise_result(&mut context, vec![PromiseResult::Failed]);
+        contract.callback_post_zap_shares_after(accounts(3).into(), args.clone(), U128(10000), U128(300));
+        assert!(zap_stalled(&contract));
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(0).prepaid_gas(300_000_000_000_000).build());
+        contract.resume_zap(accounts(5));
+        assert!(!zap_stalled(&contract));
+
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"\"1000\"".to_vec())]);
+        contract.callback_post_zap_shares_after(accounts(3).into(), args, U128(10000), U128(300));
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, 700);
+    }
+
+    #[test]
+    fn test_claim_and_zap_add_liquidity_failed() {
+        let (mut context, mut contract) = setup_contract();
+        let seed_id = setup_zap(&mut context, &mut contract);
+        let args = zap_args(&seed_id, 4000, 1);
+        claim_and_zap(&mut context, &mut contract, args.clone());
+
+        zap_promise_result(&mut context, vec![PromiseResult::Failed]);
+        contract.callback_post_zap_shares_before(accounts(3).into(), args.clone(), U128(10000), U128(3500));
+        assert!(!zap_stalled(&contract));
+        zap_promise_result(&mut context, vec![PromiseResult::Failed]);
+        contract.callback_post_zap_add_liquidity(accounts(3).into(), args.clone(), U128(10000), U128(300));
+        assert!(contract.list_user_seeds(accounts(3)).get(&seed_id).is_none());
+
+        // no shares minted since the balance read, the pool tokens are swept back
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"\"300\"".to_vec())]);
+        contract.callback_post_zap_shares_after(accounts(3).into(), args, U128(10000), U128(300));
+        assert!(contract.list_user_seeds(accounts(3)).get(&seed_id).is_none());
+    }
+
+    #[test]
+    fn test_claim_and_zap_min_shares() {
+        let (mut context, mut contract) = setup_contract();
+        let seed_id = setup_zap(&mut context, &mut contract);
+        let args = zap_args(&seed_id, 4000, 701);
+        claim_and_zap(&mut context, &mut contract, args.clone());
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"\"700\"".to_vec())]);
+        contract.callback_post_zap_add_liquidity(accounts(3).into(), args.clone(), U128(10000), U128(0));
+        assert!(contract.list_user_seeds(accounts(3)).get(&seed_id).is_none());
+
+        // removed shares are swept back to the farmer rewards
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"[\"6000\",\"3500\"]".to_vec())]);
+        contract.callback_post_zap_remove_liquidity(accounts(3).into(), args.clone(), U128(700));
+        assert!(contract.list_user_seeds(accounts(3)).get(&seed_id).is_none());
+
+        // shares that could not be removed are staked
+        zap_promise_result(&mut context, vec![PromiseResult::Failed]);
+        contract.callback_post_zap_remove_liquidity(accounts(3).into(), args, U128(700));
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&seed_id).unwrap().0, 700);
+    }
+
+    #[test]
+    fn test_claim_and_zap_refund() {
+        let (mut context, mut contract) = setup_contract();
+        let seed_id = setup_zap(&mut context, &mut contract);
+        let args = zap_args(&seed_id, 4000, 1);
+
+        // reward token refunds the deposit
+        claim_and_zap(&mut context, &mut contract, args.clone());
+        zap_promise_result(&mut context, vec![PromiseResult::Failed]);
+        contract.callback_post_zap_deposit(accounts(3).into(), args.clone(), U128(10000));
+        assert_eq!(contract.get_reward(accounts(3), accounts(1)), U128(10000));
+
+        // failed swap sweeps the reward from the exchange
+        claim_and_zap(&mut context, &mut contract, args.clone());
+        assert_eq!(contract.get_reward(accounts(3), accounts(1)), U128(0));
+        zap_promise_result(&mut context, vec![PromiseResult::Failed]);
+        contract.callback_post_zap_swap(accounts(3).into(), args.clone(), U128(10000));
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"{\"bob\":\"10000\"}".to_vec())]);
+        contract.callback_post_zap_sweep(accounts(3).into(), accounts(5).into(), vec![accounts(1).into(), accounts(2).into()]);
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(vec![])]);
+        contract.callback_post_zap_withdraw(accounts(3).into(), accounts(5).into(), vec![accounts(1).into()], vec![U128(10000)]);
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"{\"bob\":\"0\"}".to_vec())]);
+        contract.callback_post_zap_confirm(accounts(3).into(), accounts(5).into(), vec![accounts(1).into()], vec![U128(10000)]);
+        assert_eq!(contract.get_reward(accounts(3), accounts(1)), U128(10000));
+
+        // what fails to be withdrawn is kept as zap refund, and withdrawn again by the farmer
+        claim_and_zap(&mut context, &mut contract, args);
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(vec![]), PromiseResult::Failed]);
+        contract.callback_post_zap_withdraw(accounts(3).into(), accounts(5).into(), vec![accounts(1).into(), accounts(2).into()], vec![U128(6000), U128(3500)]);
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"{\"charlie\":\"3500\"}".to_vec())]);
+        contract.callback_post_zap_confirm(accounts(3).into(), accounts(5).into(), vec![accounts(1).into()], vec![U128(6000)]);
+        assert_eq!(contract.get_reward(accounts(3), accounts(1)), U128(6000));
+        assert_eq!(contract.get_zap_refund(accounts(3), accounts(5), accounts(2)), U128(3500));
+        assert!(contract.list_user_seeds(accounts(3)).get(&seed_id).is_none());
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).prepaid_gas(300_000_000_000_000).build());
+        contract.withdraw_zap_refund(accounts(5), accounts(2));
+        assert_eq!(contract.get_zap_refund(accounts(3), accounts(5), accounts(2)), U128(0));
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(vec![])]);
+        contract.callback_post_zap_withdraw(accounts(3).into(), accounts(5).into(), vec![accounts(2).into()], vec![U128(3500)]);
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"{}".to_vec())]);
+        contract.callback_post_zap_confirm(accounts(3).into(), accounts(5).into(), vec![accounts(2).into()], vec![U128(3500)]);
+        assert_eq!(contract.get_reward(accounts(3), accounts(2)), U128(3500));
+    }
+
+    #[test]
+    fn test_claim_and_zap_withdraw_returned() {
+        let (mut context, mut contract) = setup_contract();
+        let seed_id = setup_zap(&mut context, &mut contract);
+        let args = zap_args(&seed_id, 4000, 1);
+        claim_and_zap(&mut context, &mut contract, args.clone());
+
+        // the exchange reports the withdraw successful but put the tokens back in the deposits
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(vec![])]);
+        contract.callback_post_zap_withdraw(accounts(3).into(), accounts(5).into(), vec![accounts(2).into()], vec![U128(100)]);
+        zap_promise_result(&mut context, vec![PromiseResult::Failed]);
+        contract.callback_post_zap_confirm(accounts(3).into(), accounts(5).into(), vec![accounts(2).into()], vec![U128(100)]);
+        assert!(zap_stalled(&contract));
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(0).prepaid_gas(300_000_000_000_000).build());
+        contract.resume_zap(accounts(5));
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"{\"charlie\":\"100\"}".to_vec())]);
+        contract.callback_post_zap_confirm(accounts(3).into(), accounts(5).into(), vec![accounts(2).into()], vec![U128(100)]);
+        assert_eq!(contract.get_reward(accounts(3), accounts(2)), U128(0));
+        assert_eq!(contract.get_zap_refund(accounts(3), accounts(5), accounts(2)), U128(100));
+        assert!(contract.data().zapping_exchanges.get(&accounts(5).into()).is_none());
+
+        // the sweep of the next zap leaves the returned tokens to the zap refund
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(360)).attached_deposit(1).prepaid_gas(300_000_000_000_000).build());
+        contract.claim_and_zap(vec![accounts(1).into()], vec![], args);
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"{\"bob\":\"0\",\"charlie\":\"100\"}".to_vec())]);
+        contract.callback_post_zap_sweep(accounts(3).into(), accounts(5).into(), vec![accounts(1).into(), accounts(2).into()]);
+        assert!(contract.data().zapping_exchanges.get(&accounts(5).into()).is_none());
+        assert_eq!(contract.get_reward(accounts(3), accounts(2)), U128(0));
+        assert_eq!(contract.get_zap_refund(accounts(3), accounts(5), accounts(2)), U128(100));
+    }
+
+    #[test]
+    fn test_resume_zap_sweep() {
+        let (mut context, mut contract) = setup_contract();
+        let seed_id = setup_zap(&mut context, &mut contract);
+        let args = zap_args(&seed_id, 4000, 1);
+        claim_and_zap(&mut context, &mut contract, args);
+
+        zap_promise_result(&mut context, vec![PromiseResult::Failed]);
+        contract.callback_post_zap_sweep(accounts(3).into(), accounts(5).into(), vec![accounts(1).into(), accounts(2).into()]);
+        assert!(zap_stalled(&contract));
+
+        // anyone resumes the sweep, for the farmer of the zap
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(0).prepaid_gas(300_000_000_000_000).build());
+        contract.resume_zap(accounts(5));
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"{\"bob\":\"10000\"}".to_vec())]);
+        contract.callback_post_zap_sweep(accounts(3).into(), accounts(5).into(), vec![accounts(1).into(), accounts(2).into()]);
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(vec![])]);
+        contract.callback_post_zap_withdraw(accounts(3).into(), accounts(5).into(), vec![accounts(1).into()], vec![U128(10000)]);
+        zap_promise_result(&mut context, vec![PromiseResult::Successful(b"{}".to_vec())]);
+        contract.callback_post_zap_confirm(accounts(3).into(), accounts(5).into(), vec![accounts(1).into()], vec![U128(10000)]);
+        assert_eq!(contract.get_reward(accounts(3), accounts(1)), U128(10000));
+        assert!(contract.data().zapping_exchanges.get(&accounts(5).into()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "E87: the zap through the exchange is still running")]
+    fn test_resume_zap_running() {
+        let (mut context, mut contract) = setup_contract();
+        let seed_id = setup_zap(&mut context, &mut contract);
+        claim_and_zap(&mut context, &mut contract, zap_args(&seed_id, 4000, 1));
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(0).build());
+        contract.resume_zap(accounts(5));
+    }
+
+    #[test]
+    #[should_panic(expected = "E84: a zap through the exchange is in progress")]
+    fn test_claim_and_zap_in_progress() {
+        let (mut context, mut contract) = setup_contract();
+        let seed_id = setup_zap(&mut context, &mut contract);
+        claim_and_zap(&mut context, &mut contract, zap_args(&seed_id, 4000, 1));
+        deposit_seed(&mut context, &mut contract, accounts(3), 310, 10);
+        claim_and_zap(&mut context, &mut contract, zap_args(&seed_id, 1000, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E83: pool token is not registered for zaps through the exchange")]
+    fn test_claim_and_zap_token_not_registered() {
+        let (mut context, mut contract) = setup_contract();
+        let seed_id = setup_zap(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(0).build());
+        contract.set_zap_tokens(accounts(5), vec![accounts(2)], false);
+        claim_and_zap(&mut context, &mut contract, zap_args(&seed_id, 4000, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E80: swap amount should be positive and less than the zapped reward")]
+    fn test_claim_and_zap_swap_amount() {
+        let (mut context, mut contract) = setup_contract();
+        let seed_id = setup_zap(&mut context, &mut contract);
+        claim_and_zap(&mut context, &mut contract, zap_args(&seed_id, 10000, 1));
+    }
+
+    #[test]
+    fn test_storage_transfer_failed() {
+        let (mut context, mut contract) = setup_contract();
+        register_farmer(&mut context, &mut contract, accounts(3));
+        let registered = contract.storage_balance_of(accounts(3)).unwrap();
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let sb = contract.storage_withdraw(None);
+        assert_eq!(sb.available.0, 0);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.callback_post_storage_withdraw(accounts(3).into(), registered.available);
+        assert_eq!(contract.storage_balance_of(accounts(3)).unwrap().total, registered.total);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        assert!(contract.storage_unregister(None));
+        assert!(contract.storage_balance_of(accounts(3)).is_none());
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.callback_post_storage_unregister(accounts(3).into(), registered.total);
+        assert_eq!(contract.storage_balance_of(accounts(3)).unwrap().total, registered.total);
+        assert_eq!(contract.get_metadata().farmer_count.0, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "E17: still has staked nft when unregister")]
+    fn test_unregister_with_nfts() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(0));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:1");
+        assert!(contract.list_user_seeds(accounts(3)).is_empty());
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.storage_unregister(None);
+    }
+
+    #[test]
+    fn test_force_unregister_keeps_new_nfts() {
+        let (mut context, mut contract) = setup_contract();
+        let mut nft_balance = HashMap::new();
+        nft_balance.insert(String::from("eugene@1"), U128(0));
+        create_nft_farm(&mut context, &mut contract, nft_balance);
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:1");
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        assert!(contract.storage_unregister(Some(true)));
+        assert!(user_nfts(&contract, accounts(3)).is_empty());
+
+        // a nft staked while the transfers are pending keeps the farmer registered
+        deposit_nft(&mut context, &mut contract, accounts(3), "1:2");
+        let transfers = UnregisterTransfers {
+            rewards: vec![],
+            seeds: vec![],
+            nfts: vec![(String::from("eugene@1"), String::from("eugene@1:1"))],
+        };
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.callback_post_force_unregister(accounts(3).into(), transfers);
+        assert!(contract.storage_balance_of(accounts(3)).is_some());
+        assert_eq!(user_nfts(&contract, accounts(3)), vec![String::from("eugene@1:2")]);
+    }
+
+    #[test]
+    fn test_force_unregister() {
+        let (mut context, mut contract) = setup_contract();
+        create_farm(&mut context, &mut contract, accounts(1), accounts(2), 5000, 50);
+        deposit_reward(&mut context, &mut contract, 50000, 100);
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_seed(&mut context, &mut contract, accounts(3), 110, 10);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(160)).attached_deposit(1).build());
+        assert!(contract.storage_unregister(Some(true)));
+        assert!(contract.list_user_seeds(accounts(3)).is_empty());
+        assert!(contract.list_rewards(accounts(3)).is_empty());
+        assert_eq!(contract.get_seed_info(accounts(1).into()).unwrap().amount.0, 0);
+
+        // failed seed transfer is given back and the farmer stays registered
+        let transfers = UnregisterTransfers {
+            rewards: vec![(accounts(2).into(), U128(5000))],
+            seeds: vec![(accounts(1).into(), U128(10))],
+            nfts: vec![],
+        };
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).block_timestamp(to_nano(170)).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![]), PromiseResult::Failed]
+        );
+        contract.callback_post_force_unregister(accounts(3).into(), transfers);
+        assert_eq!(contract.list_user_seeds(accounts(3)).get(&accounts(1).to_string()).unwrap().0, 10);
+        assert!(contract.list_rewards(accounts(3)).is_empty());
+        assert!(contract.storage_balance_of(accounts(3)).is_some());
+
+        // storage deposit is released once everything is sent
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(180)).attached_deposit(1).build());
+        assert!(contract.storage_unregister(Some(true)));
+        let transfers = UnregisterTransfers {
+            rewards: vec![],
+            seeds: vec![(accounts(1).into(), U128(10))],
+            nfts: vec![],
+        };
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).block_timestamp(to_nano(190)).attached_deposit(0).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.callback_post_force_unregister(accounts(3).into(), transfers);
+        assert!(contract.storage_balance_of(accounts(3)).is_none());
+    }
+
+    #[test]
+    fn test_force_unregister_outdated_farm() {
+        let (mut context, mut contract) = setup_contract();
+        create_farm(&mut context, &mut contract, accounts(1), accounts(2), 5000, 50);
+        deposit_reward(&mut context, &mut contract, 50000, 100);
+        register_farmer(&mut context, &mut contract, accounts(3));
+        deposit_seed(&mut context, &mut contract, accounts(3), 110, 10);
+        claim_reward(&mut context, &mut contract, accounts(3), 700);
+        remove_farm(&mut context, &mut contract, 750);
+        assert_ne!(contract.get_user_rps(accounts(3), String::from("bob#0")), String::from("0"));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(760)).attached_deposit(1).build());
+        assert!(contract.storage_unregister(Some(true)));
+        assert_eq!(contract.get_user_rps(accounts(3), String::from("bob#0")), String::from("0"));
+    }
+
+    #[test]
+    fn test_unregister_clears_settings() {
+        let (mut context, mut contract) = setup_contract();
+        create_farm(&mut context, &mut contract, accounts(1), accounts(2), 5000, 50);
+        register_farmer(&mut context, &mut contract, accounts(3));
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.set_staking_manager(accounts(1).into(), Some(accounts(0)));
+        contract.set_auto_compound(accounts(1).into(), true);
+
+        assert!(contract.storage_unregister(None));
+        assert!(contract.get_staking_manager(accounts(3), accounts(1).into()).is_none());
+        assert!(!contract.is_auto_compound(accounts(3), accounts(1).into()));
+        assert_eq!(contract.get_auto_compound_farmer_count(accounts(1).into()), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "E89: still has zap refunds when unregister")]
+    fn test_unregister_with_zap_refunds() {
+        let (mut context, mut contract) = setup_contract();
+        let seed_id = setup_zap(&mut context, &mut contract);
+        claim_and_zap(&mut context, &mut contract, zap_args(&seed_id, 4000, 1));
+        zap_promise_result(&mut context, vec![PromiseResult::Failed]);
+        contract.callback_post_zap_withdraw(accounts(3).into(), accounts(5).into(), vec![accounts(2).into()], vec![U128(3500)]);
+        assert_eq!(contract.get_zap_refund(accounts(3), accounts(5), accounts(2)), U128(3500));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.storage_unregister(Some(true));
+    }
+
+    #[test]
+    fn test_upgrade_from_v3() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let mut data = ContractDataV3 {
+            owner_id: accounts(0).into(),
+            seeds: UnorderedMap::new(StorageKeys::Seed),
+            farmers: LookupMap::new(StorageKeys::Farmer),
+            farms: UnorderedMap::new(StorageKeys::Farm),
+            outdated_farms: UnorderedMap::new(StorageKeys::OutdatedFarm),
+            nft_balance_seeds: LookupMap::new(StorageKeys::NftBalanceSeed),
+            farmer_count: 0,
+            reward_info: UnorderedMap::new(StorageKeys::RewardInfo),
+            nft_trait_rules: LookupMap::new(StorageKeys::NftTraitRules),
+            nft_trait_multipliers: LookupMap::new(StorageKeys::NftTraitMultiplier),
+        };
+        data.nft_trait_multipliers.insert(&(String::from("eugene@1"), String::from("eugene@1:1")), &150);
+        let contract = Contract {
+            data: VersionedContractData::CurrentV3(data),
+            shared_storage_usage: 0,
+        };
+        assert!(contract.need_upgrade());
+
+        let contract = Contract {
+            data: VersionedContractData::CurrentV4(contract.upgrade()),
+            shared_storage_usage: 0,
+        };
+        assert!(!contract.need_upgrade());
+        assert_eq!(
+            contract.data().nft_trait_multipliers.get(&(String::from("eugene@1"), String::from("eugene@1:1"))),
+            Some(150)
+        );
+        assert_eq!(contract.get_metadata().owner_id, accounts(0).to_string());
+    }
 }
@@ -0,0 +1,235 @@
+//! Receipts of staked nfts.
+//! Each nft staked by transfer is a NEP-171 token of this contract with its
+//! `ContractNFTTokenId` as token id, owned by the farmer staking it. Holder of the
+//! receipt withdraws the nft, and transferring the receipt moves the stake.
+use std::collections::HashMap;
+
+use near_contract_standards::non_fungible_token::metadata::{NFTContractMetadata, TokenMetadata, NFT_METADATA_SPEC};
+use near_contract_standards::non_fungible_token::Token;
+use near_sdk::json_types::ValidAccountId;
+use near_sdk::serde_json::json;
+use near_sdk::{PromiseOrValue, PromiseResult};
+
+use crate::errors::*;
+use crate::event::{NearEvent, NftBurnData, NftMintData, NftTransferData};
+use crate::simple_farm::ContractNFTTokenId;
+use crate::utils::{assert_one_yocto, ext_non_fungible_token_receiver, ext_self, parse_contract_nft_token_id, GAS_FOR_RECEIPT_TRANSFER_CALL, GAS_FOR_RESOLVE_TRANSFER};
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    pub fn nft_metadata(&self) -> NFTContractMetadata {
+        NFTContractMetadata {
+            spec: NFT_METADATA_SPEC.to_string(),
+            name: String::from("Paras Farming Staked NFT"),
+            symbol: String::from("STAKED"),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    /// receipt of the staked nft, metadata points to the original token.
+    pub fn nft_token(&self, token_id: ContractNFTTokenId) -> Option<Token> {
+        let (owner_id, seed_id) = self.data().nft_receipt_owners.get(&token_id)?;
+        let (nft_contract_id, nft_token_id) = parse_contract_nft_token_id(&token_id);
+        Some(Token {
+            token_id: token_id.clone(),
+            owner_id,
+            metadata: Some(TokenMetadata {
+                title: Some(format!("Staked {}", token_id)),
+                description: Some(format!("Receipt of {} staked in seed {}", token_id, seed_id)),
+                media: None,
+                media_hash: None,
+                copies: None,
+                issued_at: None,
+                expires_at: None,
+                starts_at: None,
+                updated_at: None,
+                extra: Some(json!({
+                    "seed_id": seed_id,
+                    "nft_contract_id": nft_contract_id,
+                    "token_id": nft_token_id,
+                }).to_string()),
+                reference: None,
+                reference_hash: None,
+            }),
+            approved_account_ids: None,
+        })
+    }
+
+    /// move the staked nft to the receiver, receipts have no approvals.
+    /// an unregistered receiver is registered with storage deposit of the sender.
+    #[payable]
+    pub fn nft_transfer(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: ContractNFTTokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    ) {
+        assert_one_yocto();
+        let _ = approval_id;
+        let sender_id = env::predecessor_account_id();
+        self.internal_nft_receipt_transfer(&sender_id, receiver_id.as_ref(), &token_id, memo);
+    }
+
+    /// move the staked nft and call `nft_on_transfer` of the receiver,
+    /// the stake moves back in `nft_resolve_transfer` if the receiver asks for it.
+    #[payable]
+    pub fn nft_transfer_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: ContractNFTTokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        assert_one_yocto();
+        let _ = approval_id;
+        let sender_id = env::predecessor_account_id();
+        self.internal_nft_receipt_transfer(&sender_id, receiver_id.as_ref(), &token_id, memo);
+        ext_non_fungible_token_receiver::nft_on_transfer(
+            sender_id.clone(),
+            sender_id.clone(),
+            token_id.clone(),
+            msg,
+            receiver_id.as_ref(),
+            0,
+            env::prepaid_gas() - env::used_gas() - GAS_FOR_RECEIPT_TRANSFER_CALL,
+        )
+        .then(ext_self::nft_resolve_transfer(
+            sender_id,
+            receiver_id.into(),
+            token_id,
+            None,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+        .into()
+    }
+
+    /// returns true if the receipt stays with the receiver.
+    #[private]
+    pub fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: ContractNFTTokenId,
+        approved_account_ids: Option<HashMap<AccountId, u64>>,
+    ) -> bool {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            ERR73_NFT_RECEIPT_RESOLVE_TRANSFER_INVALID
+        );
+        let _ = approved_account_ids;
+        let must_revert = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => {
+                near_sdk::serde_json::from_slice::<bool>(&value).unwrap_or(true)
+            }
+            PromiseResult::Failed => true,
+        };
+        if !must_revert {
+            return true;
+        }
+
+        // receiver may have withdrawn or moved the nft already
+        if let Some((owner_id, seed_id)) = self.data().nft_receipt_owners.get(&token_id) {
+            let can_revert = owner_id == receiver_id
+                && self.get_farmer_wrapped(&previous_owner_id).is_some()
+                && self.data().staking_managers.get(&(receiver_id.clone(), seed_id.clone())).is_none()
+                && self.data().staking_managers.get(&(previous_owner_id.clone(), seed_id.clone())).is_none();
+            if can_revert {
+                // the previous owner held the nft before, its storage is not asserted again
+                self.internal_move_seed(&seed_id, &receiver_id, &previous_owner_id, 0, Some(token_id));
+                return false;
+            }
+        }
+        true
+    }
+
+    /// mint missing receipts of nfts the predecessor staked in the seed by transfer before receipts existed.
+    #[payable]
+    pub fn mint_staked_nft_receipts(&mut self, seed_id: SeedId) {
+        assert_one_yocto();
+        let farmer_id = env::predecessor_account_id();
+        let storage_snapshot = self.internal_farmer_storage_snapshot(&farmer_id);
+        let contract_nft_token_ids: Vec<ContractNFTTokenId> = self.get_farmer(&farmer_id).get_ref().nft_seeds
+            .get(&seed_id)
+            .map(|nft_contract_seed| nft_contract_seed.to_vec())
+            .unwrap_or_default();
+        for contract_nft_token_id in contract_nft_token_ids.iter() {
+            if self.data().nft_receipt_owners.get(contract_nft_token_id).is_none()
+                && !self.internal_is_approval_staked(&farmer_id, &seed_id, contract_nft_token_id) {
+                self.internal_mint_nft_receipt(&farmer_id, &seed_id, contract_nft_token_id);
+            }
+        }
+        self.internal_update_farmer_storage(&farmer_id, storage_snapshot);
+        self.assert_storage_usage(&farmer_id);
+    }
+}
+
+impl Contract {
+    fn internal_nft_receipt_transfer(
+        &mut self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        token_id: &ContractNFTTokenId,
+        memo: Option<String>,
+    ) {
+        assert_ne!(receiver_id, &env::current_account_id(), "{}", ERR90_NFT_RECEIPT_TRANSFER_TO_SELF);
+        let (owner_id, seed_id) = self.data().nft_receipt_owners.get(token_id).expect(ERR71_NFT_RECEIPT_NOT_EXIST);
+        assert_eq!(&owner_id, sender_id, "{}", ERR72_NOT_NFT_RECEIPT_OWNER);
+        assert!(
+            !self.internal_is_approval_staked(sender_id, &seed_id, token_id),
+            "{}", ERR65_APPROVAL_STAKED_NFT_TRANSFER
+        );
+        self.internal_transfer_seed(&seed_id, sender_id, receiver_id, 0, Some(token_id.clone()));
+        if let Some(memo) = memo {
+            env::log(format!("Memo: {}", memo).as_bytes());
+        }
+    }
+
+    pub(crate) fn internal_mint_nft_receipt(&mut self, owner_id: &AccountId, seed_id: &SeedId, contract_nft_token_id: &ContractNFTTokenId) {
+        self.data_mut().nft_receipt_owners.insert(contract_nft_token_id, &(owner_id.clone(), seed_id.clone()));
+        NearEvent::log_nft_mint(NftMintData {
+            owner_id: owner_id.clone(),
+            token_ids: vec![contract_nft_token_id.clone()],
+            memo: None,
+        });
+    }
+
+    pub(crate) fn internal_move_nft_receipt(
+        &mut self,
+        old_owner_id: &AccountId,
+        new_owner_id: &AccountId,
+        seed_id: &SeedId,
+        contract_nft_token_id: &ContractNFTTokenId
+    ) {
+        self.data_mut().nft_receipt_owners.insert(contract_nft_token_id, &(new_owner_id.clone(), seed_id.clone()));
+        NearEvent::log_nft_transfer(NftTransferData {
+            authorized_id: None,
+            old_owner_id: old_owner_id.clone(),
+            new_owner_id: new_owner_id.clone(),
+            token_ids: vec![contract_nft_token_id.clone()],
+            memo: None,
+        });
+    }
+
+    /// nfts staked before receipts existed have none to burn.
+    pub(crate) fn internal_burn_nft_receipt(&mut self, contract_nft_token_id: &ContractNFTTokenId) {
+        if let Some((owner_id, _)) = self.data_mut().nft_receipt_owners.remove(contract_nft_token_id) {
+            NearEvent::log_nft_burn(NftBurnData {
+                authorized_id: None,
+                owner_id,
+                token_ids: vec![contract_nft_token_id.clone()],
+                memo: None,
+            });
+        }
+    }
+}
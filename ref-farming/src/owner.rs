@@ -1,6 +1,10 @@
 use crate::*;
+use crate::errors::*;
+use crate::farm_seed::{SeedType, NFTTokenId};
+use crate::utils::{ext_ref_exchange, MAX_FARM_CHECKPOINTS, GAS_FOR_ZAP_REGISTER_TOKENS};
 
 use near_sdk::json_types::U128;
+use near_sdk::{assert_one_yocto, Promise};
 
 #[near_bindgen]
 impl Contract {
@@ -21,6 +25,206 @@ impl Contract {
         farm_seed.get_ref_mut().min_deposit = min_deposit.into();
     }
 
+    /// set trait rules of nft seed, empty rules means weight by nft_balance only.
+    /// only affects nft staked afterward.
+    pub fn set_nft_trait_rules(&mut self, seed_id: SeedId, rules: Vec<NftTraitRule>) {
+        self.assert_owner();
+        let farm_seed = self.get_seed(&seed_id);
+        assert_eq!(farm_seed.get_ref().seed_type, SeedType::NFT, "Seed type is not NFT");
+        for rule in rules.iter() {
+            assert!(rule.multiplier > 0, "{}", ERR52_INVALID_NFT_TRAIT_MULTIPLIER);
+        }
+
+        assert!(
+            rules.is_empty() || !self.data().nft_approval_stake_seeds.contains(&seed_id),
+            "Approval staking seed can not have trait rules"
+        );
+
+        if rules.is_empty() {
+            self.data_mut().nft_trait_rules.remove(&seed_id);
+        } else {
+            self.data_mut().nft_trait_rules.insert(&seed_id, &rules);
+        }
+    }
+
+    /// let nft seed be staked by nft_approve, the nfts stay in owner wallet
+    /// and are re-checked on claim and withdraw.
+    /// nfts staked by approval keep their stake when it is disabled.
+    pub fn set_nft_approval_staking(&mut self, seed_id: SeedId, enabled: bool) {
+        self.assert_owner();
+        let farm_seed = self.get_seed(&seed_id);
+        assert_eq!(farm_seed.get_ref().seed_type, SeedType::NFT, "Seed type is not NFT");
+
+        if enabled {
+            assert!(self.data().nft_trait_rules.get(&seed_id).is_none(), "Approval staking seed can not have trait rules");
+            self.data_mut().nft_approval_stake_seeds.insert(&seed_id);
+        } else {
+            self.data_mut().nft_approval_stake_seeds.remove(&seed_id);
+        }
+    }
+
+    /// let first deposit of unregistered accounts to the seed register them,
+    /// with the registration deposit covered by the storage sponsorship pool.
+    pub fn set_seed_auto_register(&mut self, seed_id: SeedId, enabled: bool) {
+        self.assert_owner();
+        self.get_seed(&seed_id);
+        if enabled {
+            self.data_mut().auto_register_seeds.insert(&seed_id);
+        } else {
+            self.data_mut().auto_register_seeds.remove(&seed_id);
+        }
+    }
+
+    /// expose staked balance of the seed through the given receipt token contract,
+    /// or stop it with None. nft seeds have no receipt token.
+    pub fn set_seed_receipt_token(&mut self, seed_id: SeedId, token_id: Option<ValidAccountId>) {
+        self.assert_owner();
+        self.internal_assert_receipt_token_seed(&seed_id);
+        if let Some(token_id) = token_id {
+            // keep the nonce so the token never accepts an outdated balance
+            let nonce = self.data().receipt_tokens.get(&seed_id).map(|receipt_token| receipt_token.nonce).unwrap_or(0);
+            self.data_mut().receipt_tokens.insert(&seed_id, &ReceiptToken { token_id: token_id.into(), nonce });
+        } else {
+            self.data_mut().receipt_tokens.remove(&seed_id);
+        }
+    }
+
+    /// push staked balances of a page of stakers of the seed to its receipt token,
+    /// used to backfill balances staked before the receipt token was set.
+    pub fn sync_receipt_token(&mut self, seed_id: SeedId, from_index: u64, limit: u64) -> u64 {
+        self.assert_owner();
+        assert!(self.data().receipt_tokens.get(&seed_id).is_some(), "{}", ERR67_RECEIPT_TOKEN_NOT_ENABLED);
+        self.internal_sync_receipt_stakers(&seed_id, from_index, limit)
+    }
+
+    /// allow zaps into pools of the tokens through the exchange, registering the tokens
+    /// in the exchange deposits of this contract. this contract needs storage at the exchange and the tokens.
+    pub fn set_zap_tokens(&mut self, exchange_id: ValidAccountId, token_ids: Vec<ValidAccountId>, enabled: bool) {
+        self.assert_owner();
+        let exchange_id: AccountId = exchange_id.into();
+        let token_ids: Vec<AccountId> = token_ids.into_iter().map(|token_id| token_id.into()).collect();
+        for token_id in token_ids.iter() {
+            if enabled {
+                self.data_mut().zap_tokens.insert(&(exchange_id.clone(), token_id.clone()));
+            } else {
+                self.data_mut().zap_tokens.remove(&(exchange_id.clone(), token_id.clone()));
+            }
+        }
+        if enabled {
+            ext_ref_exchange::register_tokens(token_ids, &exchange_id, 1, GAS_FOR_ZAP_REGISTER_TOKENS);
+        }
+    }
+
+    /// withdraw the tokens left in the exchange by a zap whose callback did not run to the end
+    /// to the farmer of the zap, which lets zaps run through the exchange again.
+    pub fn sweep_zap_exchange(&mut self, exchange_id: ValidAccountId) {
+        self.assert_owner();
+        let exchange_id: AccountId = exchange_id.into();
+        let state = self.data().zapping_exchanges.get(&exchange_id).expect(ERR86_NO_ZAP_IN_PROGRESS);
+        assert!(state.stalled.is_none(), "{}", ERR88_ZAP_STALLED);
+        self.internal_zap_sweep_tokens(&state.sender_id, &exchange_id, state.token_ids);
+    }
+
+    /// check owner of a page of staked nfts of the farmer by nft_token,
+    /// nfts burned or moved out of this contract (or of the farmer wallet for approval stakes)
+    /// are evicted with their unclaimed rewards forfeited.
+    pub fn verify_staked_nfts(&mut self, farmer_id: ValidAccountId, seed_id: SeedId, from_index: u64, limit: u64) {
+        self.assert_owner();
+        self.internal_verify_staked_nfts(farmer_id.into(), seed_id, from_index, limit);
+    }
+
+    /// keep up to capacity checkpoints of rps and total seeds of the farm,
+    /// changing the capacity keeps the latest checkpoints fitting in it, 0 disables them.
+    pub fn set_farm_checkpoints(&mut self, farm_id: FarmId, capacity: u64) {
+        self.assert_owner();
+        assert!(self.data().farms.get(&farm_id).is_some(), "{}", ERR41_FARM_NOT_EXIST);
+        assert!(capacity <= MAX_FARM_CHECKPOINTS, "{}", ERR45_INVALID_FARM_CHECKPOINT_CAPACITY);
+
+        let current_capacity = self.data().farm_checkpoints.get(&farm_id).map(|checkpoints| checkpoints.capacity).unwrap_or(0);
+        if capacity == current_capacity {
+            return;
+        }
+        let mut kept = vec![];
+        if let Some(mut checkpoints) = self.data_mut().farm_checkpoints.remove(&farm_id) {
+            let len = checkpoints.len();
+            kept = (len.saturating_sub(capacity)..len).map(|index| checkpoints.get(index).unwrap()).collect();
+            checkpoints.clear();
+        }
+        if capacity > 0 {
+            let mut checkpoints = FarmCheckpoints::new(&farm_id, capacity);
+            for checkpoint in kept.iter() {
+                checkpoints.push(checkpoint);
+            }
+            self.data_mut().farm_checkpoints.insert(&farm_id, &checkpoints);
+        }
+    }
+
+    /// fund the storage sponsorship pool with the attached deposit.
+    #[payable]
+    pub fn deposit_storage_sponsorship(&mut self) {
+        self.assert_owner();
+        self.data_mut().storage_sponsorship.balance += env::attached_deposit();
+    }
+
+    /// withdraw sponsorship not given out yet to the owner, all of it if amount is not given.
+    #[payable]
+    pub fn withdraw_storage_sponsorship(&mut self, amount: Option<U128>) -> Promise {
+        assert_one_yocto();
+        self.assert_owner();
+        let balance = self.data().storage_sponsorship.balance;
+        let amount = amount.map(|a| a.0).unwrap_or(balance);
+        assert!(amount <= balance, "{}", ERR15_INSUFFICIENT_STORAGE_SPONSORSHIP);
+        self.data_mut().storage_sponsorship.balance -= amount;
+        Promise::new(self.data().owner_id.clone()).transfer(amount)
+    }
+
+    /// max registration deposit sponsored for each new farmer, 0 disables sponsorship.
+    pub fn set_storage_sponsorship_cap(&mut self, cap: U128) {
+        self.assert_owner();
+        self.data_mut().storage_sponsorship.cap = cap.into();
+    }
+
+    /// add or change balance equivalent of nft contracts/series/tokens of a nft seed.
+    /// staked nfts are repriced on the next claim/deposit/withdraw of its farmer.
+    pub fn set_nft_balance(&mut self, seed_id: SeedId, nft_balance: NftBalance) {
+        self.assert_owner();
+        let farm_seed = self.get_seed(&seed_id);
+        assert_eq!(farm_seed.get_ref().seed_type, SeedType::NFT, "Seed type is not NFT");
+
+        let mut current_nft_balance = self.data().nft_balance_seeds.get(&seed_id).unwrap_or_default();
+        current_nft_balance.extend(nft_balance);
+        self.data_mut().nft_balance_seeds.insert(&seed_id, &current_nft_balance);
+    }
+
+    /// remove nft contracts/series/tokens from a nft seed,
+    /// staked nfts of them keep withdrawable but have no weight on the next reprice.
+    pub fn remove_nft_balance(&mut self, seed_id: SeedId, nft_token_ids: Vec<NFTTokenId>) {
+        self.assert_owner();
+        let farm_seed = self.get_seed(&seed_id);
+        assert_eq!(farm_seed.get_ref().seed_type, SeedType::NFT, "Seed type is not NFT");
+
+        let mut current_nft_balance = self.data().nft_balance_seeds.get(&seed_id).unwrap_or_default();
+        for nft_token_id in nft_token_ids.iter() {
+            current_nft_balance.remove(nft_token_id);
+        }
+        self.data_mut().nft_balance_seeds.insert(&seed_id, &current_nft_balance);
+    }
+
+    /// add farmers registered before farmer_ids existed to the index,
+    /// together with their seeds to the staker index of each seed.
+    /// account ids not registered are ignored.
+    pub fn index_farmers(&mut self, farmer_ids: Vec<ValidAccountId>) {
+        self.assert_owner();
+        for farmer_id in farmer_ids {
+            let farmer_id: AccountId = farmer_id.into();
+            if let Some(farmer) = self.get_farmer_wrapped(&farmer_id) {
+                self.data_mut().farmer_ids.insert(&farmer_id);
+                for seed_id in farmer.get_ref().seeds.keys() {
+                    self.internal_update_seed_staker(seed_id, farmer.get_ref());
+                }
+            }
+        }
+    }
 
     /// Migration function between versions.
     /// For next version upgrades, change this function.
@@ -36,7 +240,8 @@ impl Contract {
         return if contract.need_upgrade() {
             let contract_data_upgraded = contract.upgrade();
             let this = Contract {
-                data: VersionedContractData::CurrentV2(contract_data_upgraded)
+                data: VersionedContractData::CurrentV4(contract_data_upgraded),
+                shared_storage_usage: 0,
             };
             this
         } else {
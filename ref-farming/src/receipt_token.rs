@@ -0,0 +1,121 @@
+//! Receipt tokens of staked seed positions.
+//! Each seed with a receipt token has a NEP-141 companion contract (see receipt-token crate)
+//! mirroring staked balances of the seed. Balances are pushed to it by `ft_sync` each time
+//! a staked balance changes, so receipts are minted by seed deposit and burned by withdraw.
+//! Transfers of the companion are forwarded here and move the position with its future rewards.
+use near_sdk::json_types::{ValidAccountId, U128, U64};
+
+use crate::errors::*;
+use crate::farm_seed::SeedType;
+use crate::utils::{ext_receipt_token, GAS_FOR_RECEIPT_SYNC, MAX_RECEIPT_SYNC_BATCH_SIZE};
+use crate::*;
+
+/// companion token of a seed, with the nonce of the last balance pushed to it
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ReceiptToken {
+    pub token_id: AccountId,
+    pub nonce: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// move staked seed for a ft_transfer of the receipt token of the seed,
+    /// an unregistered receiver is registered with storage deposit of the sender. locked seed can not be transferred.
+    pub fn receipt_transfer(
+        &mut self,
+        seed_id: SeedId,
+        sender_id: ValidAccountId,
+        receiver_id: ValidAccountId,
+        amount: U128,
+        memo: Option<String>,
+    ) {
+        self.internal_assert_receipt_token(&seed_id);
+        assert!(amount.0 > 0, "{}", ERR69_RECEIPT_TRANSFER_ZERO_AMOUNT);
+        self.internal_transfer_seed(&seed_id, sender_id.as_ref(), receiver_id.as_ref(), amount.0, None);
+        if let Some(memo) = memo {
+            env::log(format!("Memo: {}", memo).as_bytes());
+        }
+    }
+
+    /// move the unused part of a ft_transfer_call of the receipt token back to the sender
+    /// as far as the receiver still holds it, never panics once called by the receipt token.
+    /// returns the amount used by the receiver.
+    pub fn receipt_refund(
+        &mut self,
+        seed_id: SeedId,
+        sender_id: ValidAccountId,
+        receiver_id: ValidAccountId,
+        amount: U128,
+        unused_amount: U128,
+    ) -> U128 {
+        self.internal_assert_receipt_token(&seed_id);
+        let (sender_id, receiver_id): (AccountId, AccountId) = (sender_id.into(), receiver_id.into());
+        let refund_amount = self.get_farmer_wrapped(&receiver_id)
+            .map(|farmer| farmer.get_ref().get_available_balance(&seed_id))
+            .unwrap_or(0)
+            .min(unused_amount.0)
+            .min(amount.0);
+        let can_refund = sender_id != receiver_id
+            && self.get_farmer_wrapped(&sender_id).is_some()
+            && self.data().staking_managers.get(&(receiver_id.clone(), seed_id.clone())).is_none()
+            && self.data().staking_managers.get(&(sender_id.clone(), seed_id.clone())).is_none();
+        if refund_amount > 0 && can_refund {
+            // the sender held the position before, its storage is not asserted again
+            self.internal_move_seed(&seed_id, &receiver_id, &sender_id, refund_amount, None);
+            U128(amount.0 - refund_amount)
+        } else {
+            amount
+        }
+    }
+
+    pub fn get_seed_receipt_token(&self, seed_id: SeedId) -> Option<AccountId> {
+        self.data().receipt_tokens.get(&seed_id).map(|receipt_token| receipt_token.token_id)
+    }
+}
+
+impl Contract {
+    fn internal_assert_receipt_token(&self, seed_id: &SeedId) {
+        let receipt_token = self.data().receipt_tokens.get(seed_id).expect(ERR67_RECEIPT_TOKEN_NOT_ENABLED);
+        assert_eq!(receipt_token.token_id, env::predecessor_account_id(), "{}", ERR70_NOT_RECEIPT_TOKEN);
+    }
+
+    pub(crate) fn internal_assert_receipt_token_seed(&self, seed_id: &SeedId) {
+        assert_ne!(self.get_seed(seed_id).get_ref().seed_type, SeedType::NFT, "{}", ERR68_NFT_SEED_RECEIPT_TOKEN);
+    }
+
+    /// push staked balance of the farmer to the receipt token of the seed, if any.
+    pub(crate) fn internal_sync_receipt(&mut self, seed_id: &SeedId, farmer_id: &AccountId, balance: Balance) {
+        if let Some(mut receipt_token) = self.data().receipt_tokens.get(seed_id) {
+            receipt_token.nonce += 1;
+            self.data_mut().receipt_tokens.insert(seed_id, &receipt_token);
+            ext_receipt_token::ft_sync(
+                farmer_id.clone(),
+                balance.into(),
+                U64(receipt_token.nonce),
+                &receipt_token.token_id,
+                0,
+                GAS_FOR_RECEIPT_SYNC,
+            );
+        }
+    }
+
+    /// push staked balances of a page of stakers of the seed to its receipt token,
+    /// returns the number of stakers pushed.
+    pub(crate) fn internal_sync_receipt_stakers(&mut self, seed_id: &SeedId, from_index: u64, limit: u64) -> u64 {
+        let staker_ids: Vec<AccountId> = match self.data().seed_stakers.get(seed_id) {
+            Some(stakers) => {
+                let keys = stakers.as_vector();
+                let limit = std::cmp::min(limit, MAX_RECEIPT_SYNC_BATCH_SIZE);
+                (from_index..std::cmp::min(from_index + limit, keys.len()))
+                    .map(|index| keys.get(index).unwrap())
+                    .collect()
+            },
+            None => vec![],
+        };
+        for staker_id in staker_ids.iter() {
+            let balance = self.get_farmer(staker_id).get_ref().get_balance(seed_id);
+            self.internal_sync_receipt(seed_id, staker_id, balance);
+        }
+        staker_ids.len() as u64
+    }
+}
@@ -6,6 +6,7 @@
 //!   But to enable farming, the creator or someone else should deposit reward 
 //! token to the farm, after it was created.
 
+use std::convert::TryInto;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::{U128, ValidAccountId};
 use near_sdk::serde::{Deserialize, Serialize};
@@ -30,6 +31,73 @@ pub type RPS = [u8; 32];
 // this value should be carefully choosen, now is 10**24.
 pub const DENOM: u128 = 1_000_000_000_000_000_000_000_000;
 
+pub const SECONDS_PER_DAY: u128 = 60 * 60 * 24;
+pub const SECONDS_PER_YEAR: u128 = SECONDS_PER_DAY * 365;
+// apr is expressed in basis points
+pub const APR_DENOM: u128 = 10_000;
+
+///   Reward emission of a farm releasing a fixed reward each session,
+/// shared by farm kinds following that schema.
+pub struct FarmEmission {
+    pub per_second: Balance,
+    pub per_day: Balance,
+    pub per_year: Balance,
+    /// reward per staked seed per day, multiplied by DENOM like rps
+    pub per_seed_per_day: U256,
+    /// projected timestamp the undistributed reward runs out, None if no reward yet
+    pub end_at: Option<TimestampSec>,
+}
+
+impl FarmEmission {
+    pub(crate) fn new(
+        reward_per_session: Balance,
+        session_interval: TimestampSec,
+        total_seeds: &Balance,
+        end_at: Option<TimestampSec>,
+    ) -> Self {
+        let per_day = reward_per_session * SECONDS_PER_DAY / session_interval as u128;
+        let per_seed_per_day = if total_seeds == &0 {
+            U256::from(0)
+        } else {
+            U256::from(per_day) * U256::from(DENOM) / U256::from(*total_seeds)
+        };
+        Self {
+            per_second: reward_per_session / session_interval as u128,
+            per_day,
+            per_year: reward_per_session * SECONDS_PER_YEAR / session_interval as u128,
+            per_seed_per_day,
+            end_at,
+        }
+    }
+
+    /// APR in basis points of staking total_seeds, prices are per whole token
+    /// in any common quote unit, return None if nothing is staked or seed has no price.
+    pub(crate) fn apr(
+        &self,
+        total_seeds: &Balance,
+        reward_token_price: Balance,
+        reward_token_decimals: u8,
+        seed_price: Balance,
+        seed_decimals: u8,
+    ) -> Option<Balance> {
+        if total_seeds == &0 || seed_price == 0 {
+            return None;
+        }
+        let yearly_reward_value = U256::from(self.per_year)
+            * U256::from(reward_token_price)
+            * U256::exp10(seed_decimals as usize);
+        let staked_value = U256::from(*total_seeds)
+            * U256::from(seed_price)
+            * U256::exp10(reward_token_decimals as usize);
+        let apr = yearly_reward_value * U256::from(APR_DENOM) / staked_value;
+        if apr > U256::from(u128::MAX) {
+            Some(u128::MAX)
+        } else {
+            Some(apr.as_u128())
+        }
+    }
+}
+
 ///   The terms defines how the farm works.
 ///   In this version, we distribute reward token with a start height, a reward 
 /// session interval, and reward amount per session.  
@@ -98,6 +166,16 @@ pub struct SimpleFarmRewardDistribution {
     pub rr: u32,
 }
 
+/// Reward accounting of a farm after a distribution moved it to a new round
+#[derive(BorshSerialize, BorshDeserialize, Clone)]
+pub struct FarmCheckpoint {
+    pub round: u32,
+    /// block timestamp the distribution happened at
+    pub timestamp: TimestampSec,
+    pub rps: RPS,
+    pub total_seeds: Balance,
+}
+
 ///   Implementation of simple farm, Similar to the design of "berry farm".
 ///   Farmer stake their seed to farming on multiple farm accept that seed.
 #[derive(BorshSerialize, BorshDeserialize)]
@@ -321,6 +399,25 @@ impl SimpleFarm {
         (self.last_distribution.rps, claimed)
     }
 
+    /// Forfeit user's unclaimed reward of given seeds to beneficiary,
+    /// user RPS is left untouched so the rest of user seeds still can be claimed,
+    /// return amount of reward forfeited.
+    pub(crate) fn forfeit_user_reward(
+        &mut self, 
+        user_rps: &RPS,
+        user_seeds: &Balance, 
+        total_seeds: &Balance, 
+    ) -> Balance {
+        let (_, forfeited) = self.claim_user_reward(user_rps, user_seeds, total_seeds, true);
+        self.amount_of_beneficiary += forfeited;
+        forfeited
+    }
+
+    /// Forfeit reward claimed from the farm and held for the user to beneficiary.
+    pub(crate) fn forfeit_held_reward(&mut self, amount: &Balance) {
+        self.amount_of_beneficiary += amount;
+    }
+
     /// Move an Ended farm to Cleared, if any unclaimed reward exists, go to beneficiary
     pub(crate) fn move_to_clear(&mut self, total_seeds: &Balance) -> bool {
         if let SimpleFarmStatus::Running = self.status {
@@ -339,6 +436,64 @@ impl SimpleFarm {
         }
     }
 
+    /// checkpoint of the last distribution
+    pub(crate) fn checkpoint(&self, total_seeds: &Balance) -> FarmCheckpoint {
+        FarmCheckpoint {
+            round: self.last_distribution.rr,
+            timestamp: to_sec(env::block_timestamp()),
+            rps: self.last_distribution.rps,
+            total_seeds: *total_seeds,
+        }
+    }
+
+    /// rounds needed to distribute all undistributed reward of the distribution,
+    /// the tail round releasing less than reward_per_session is counted.
+    pub(crate) fn remaining_rounds(&self, dis: &SimpleFarmRewardDistribution) -> u32 {
+        dis.undistributed.div_ceil(self.terms.reward_per_session).min(u32::MAX as u128) as u32
+    }
+
+    /// projected timestamp the farm runs out of reward,
+    /// return None if no reward deposited in yet or it is beyond u32 seconds.
+    pub(crate) fn estimated_end_at(&self, total_seeds: &Balance) -> Option<TimestampSec> {
+        if let SimpleFarmStatus::Created = self.status {
+            return None;
+        }
+        let dis = self.try_distribute(total_seeds).unwrap_or_else(|| self.last_distribution.clone());
+        let remaining_rounds: u32 = dis.undistributed.div_ceil(self.terms.reward_per_session).try_into().ok()?;
+        dis.rr.checked_add(remaining_rounds)?
+            .checked_mul(self.terms.session_interval)?
+            .checked_add(self.terms.start_at)
+    }
+
+    /// seconds until the next round releases reward,
+    /// return None if the farm is not running or has no reward left.
+    pub(crate) fn seconds_to_next_round(&self, total_seeds: &Balance) -> Option<TimestampSec> {
+        if let SimpleFarmStatus::Running = self.status {
+            let now = to_sec(env::block_timestamp());
+            if now < self.terms.start_at {
+                // first round is released at start_at + session_interval
+                return Some(self.terms.start_at - now + self.terms.session_interval);
+            }
+            match self.try_distribute(total_seeds) {
+                Some(dis) if dis.undistributed > 0 => Some(
+                    self.terms.session_interval - (now - self.terms.start_at) % self.terms.session_interval
+                ),
+                _ => None,
+            }
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn emission(&self, total_seeds: &Balance) -> FarmEmission {
+        FarmEmission::new(
+            self.terms.reward_per_session,
+            self.terms.session_interval,
+            total_seeds,
+            self.estimated_end_at(total_seeds),
+        )
+    }
+
     pub fn can_be_removed(&self, total_seeds: &Balance) -> bool {
         match self.status {
             SimpleFarmStatus::Ended => true,
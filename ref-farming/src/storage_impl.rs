@@ -4,15 +4,45 @@ use near_contract_standards::storage_management::{
 
 use std::convert::TryInto;
 
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::{ValidAccountId, U128};
-use near_sdk::{assert_one_yocto, env, near_bindgen, Promise, Balance};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{assert_one_yocto, env, near_bindgen, Promise, PromiseResult, Balance, StorageUsage};
 
 use crate::errors::*;
 use crate::*;
+use crate::event::{NearEvent, SeedData, WithdrawRewardData};
+use crate::farm_seed::SeedType;
 use crate::farmer::MIN_FARMER_LENGTH;
-use crate::utils::MAX_ACCOUNT_LENGTH;
+use crate::simple_farm::ContractNFTTokenId;
+use crate::utils::{
+    ext_self, ext_fungible_token, ext_multi_fungible_token, ext_non_fungible_token,
+    parse_seed_id, parse_contract_nft_token_id, wrap_mft_token_id, to_sec,
+    MAX_ACCOUNT_LENGTH, MAX_NFT_BATCH_SIZE, FT_INDEX_TAG,
+    GAS_FOR_FT_TRANSFER, GAS_FOR_NFT_TRANSFER, GAS_FOR_RESOLVE_TRANSFER, GAS_FOR_RESOLVE_FORCE_UNREGISTER,
+};
+
 
+/// Pool funded by the owner to cover registration deposit of new farmers.
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+pub struct StorageSponsorship {
+    /// sponsorship not given out yet
+    pub balance: Balance,
+    /// max registration deposit sponsored for each farmer, 0 disables sponsorship
+    pub cap: Balance,
+    /// registration deposit currently sponsored to farmers
+    pub sponsored: Balance,
+}
 
+/// Rewards, ft seeds and nfts sent back by a forced `storage_unregister`,
+/// each list in the order of its transfers.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UnregisterTransfers {
+    pub rewards: Vec<(AccountId, U128)>,
+    pub seeds: Vec<(SeedId, U128)>,
+    pub nfts: Vec<(SeedId, ContractNFTTokenId)>,
+}
 
 /// Implements users storage management for the pool.
 #[near_bindgen]
@@ -32,18 +62,24 @@ impl StorageManagement for Contract {
 
         let (locked, deposited) = self.internal_farmer_storage(&account_id);
         if deposited == 0 {  // new account register
-            if amount < Contract::suggested_min_storage_usage() {
+            // shortfall of the registration deposit is covered by the sponsorship pool,
+            // only for new farmers registering themselves
+            let sponsored = Contract::suggested_min_storage_usage().saturating_sub(amount);
+            if sponsored > 0 && account_id != env::predecessor_account_id() {
                 env::panic(format!("{}", ERR11_INSUFFICIENT_STORAGE).as_bytes());
             }
-            if registration_only {
+            if registration_only || sponsored > 0 {
                 self.internal_register_account(&account_id, Contract::suggested_min_storage_usage());
-                let refund = amount - Contract::suggested_min_storage_usage();
+                let refund = amount + sponsored - Contract::suggested_min_storage_usage();
                 if refund > 0 {
                     Promise::new(env::predecessor_account_id()).transfer(refund);
                 }
             } else {
                 self.internal_register_account(&account_id, amount);
             }
+            if sponsored > 0 {
+                self.internal_sponsor_storage(&account_id, sponsored);
+            }
         } else {  // old account, only can complement storage fee
             if registration_only {
                 env::panic(format!("{}", ERR14_ACC_ALREADY_REGISTERED).as_bytes());
@@ -63,34 +99,50 @@ impl StorageManagement for Contract {
 
         let account_id = env::predecessor_account_id();        
         let (locked, deposited) = self.internal_farmer_storage(&account_id);
+        // sponsored deposit is kept until unregister
+        let locked = locked.max(self.internal_sponsored_storage(&account_id));
         if deposited > 0 {
             if deposited < locked {
                 env::panic(format!("{}", ERR11_INSUFFICIENT_STORAGE).as_bytes());
             }
             let amount = amount.map(|a| a.0).unwrap_or(deposited - locked);
             assert!(deposited >= locked + amount, "{}", ERR11_INSUFFICIENT_STORAGE);
-            // TODO: should make sure tranfer is OK with a callback
+            // Note: subtraction, will be reverted if the transfer fails.
             let mut farmer = self.get_farmer(&account_id);
             farmer.get_ref_mut().amount -= amount;
             self.data_mut().farmers.insert(&account_id, &farmer);
-            Promise::new(account_id.clone()).transfer(amount);
+            Promise::new(account_id.clone()).transfer(amount)
+                .then(ext_self::callback_post_storage_withdraw(
+                    account_id.clone(),
+                    amount.into(),
+                    &env::current_account_id(),
+                    0,
+                    GAS_FOR_RESOLVE_TRANSFER,
+                ));
             self.storage_balance_of(account_id.try_into().unwrap()).unwrap()
         } else {
             env::panic(format!("{}", ERR10_ACC_NOT_REGISTERED).as_bytes());
         }
     }
 
-    #[allow(unused_variables)]
+    /// with force, all rewards, seeds and nfts are sent back first,
+    /// and the storage deposit is released once every transfer is done.
     #[payable]
     fn storage_unregister(&mut self, force: Option<bool>) -> bool {
         assert_one_yocto();
 
-        // force option is useless, leave it for compatible consideration.
-        // User should withdraw all his rewards and seeds token before unregister!
-
         let account_id = env::predecessor_account_id();
         if let Some(farmer) = self.get_farmer_wrapped(&account_id) {
-            
+            assert!(
+                self.data().zap_refund_ids.get(&account_id).is_none(),
+                "{}", ERR89_STORAGE_UNREGISTER_ZAP_REFUNDS_NOT_EMPTY
+            );
+            let has_nfts = farmer.get_ref().has_nfts();
+            if force.unwrap_or(false) && (!farmer.get_ref().rewards.is_empty() || !farmer.get_ref().seeds.is_empty() || has_nfts) {
+                self.internal_force_unregister(&account_id);
+                return true;
+            }
+
             assert!(
                 farmer.get_ref().rewards.is_empty(),
                 "{}", ERR12_STORAGE_UNREGISTER_REWARDS_NOT_EMPTY
@@ -99,10 +151,8 @@ impl StorageManagement for Contract {
                 farmer.get_ref().seeds.is_empty(),
                 "{}", ERR13_STORAGE_UNREGISTER_SEED_NOT_EMPTY
             );
-            self.data_mut().farmers.remove(&account_id);
-            self.data_mut().farmer_count -= 1;
-            // TODO: should make sure tranfer is OK with a callback
-            Promise::new(account_id.clone()).transfer(farmer.get_ref().amount);
+            assert!(!has_nfts, "{}", ERR17_STORAGE_UNREGISTER_NFTS_NOT_EMPTY);
+            self.internal_unregister_account(&account_id);
             true
         } else {
             false
@@ -118,6 +168,7 @@ impl StorageManagement for Contract {
 
     fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance> {
         let (locked, deposited) = self.internal_farmer_storage(account_id.as_ref()); 
+        let locked = locked.max(self.internal_sponsored_storage(account_id.as_ref()));
         if locked > 0 {
             Some(StorageBalance {
                 total: U128(deposited),
@@ -129,6 +180,135 @@ impl StorageManagement for Contract {
     }
 }
 
+#[near_bindgen]
+impl Contract {
+    #[private]
+    pub fn callback_post_storage_withdraw(
+        &mut self,
+        account_id: AccountId,
+        amount: U128,
+    ) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            ERR28_CALLBACK_POST_STORAGE_TRANSFER_INVALID
+        );
+        if let PromiseResult::Failed = env::promise_result(0) {
+            env::log(
+                format!(
+                    "{} withdraw storage deposit {}, Callback Failed.",
+                    account_id, amount.0,
+                )
+                .as_bytes(),
+            );
+            // This reverts the changes from storage_withdraw, registers the account again if it is gone.
+            self.internal_register_account(&account_id, amount.0);
+        }
+    }
+
+    #[private]
+    pub fn callback_post_storage_unregister(
+        &mut self,
+        account_id: AccountId,
+        amount: U128,
+    ) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            ERR28_CALLBACK_POST_STORAGE_TRANSFER_INVALID
+        );
+        if let PromiseResult::Failed = env::promise_result(0) {
+            env::log(
+                format!(
+                    "{} unregister with storage deposit {}, Callback Failed.",
+                    account_id, amount.0,
+                )
+                .as_bytes(),
+            );
+            // This reverts the changes from storage_unregister, without the sponsored deposit.
+            self.internal_register_account(&account_id, amount.0);
+        }
+    }
+
+    /// Give back to the farmer what failed to be sent by a forced `storage_unregister`,
+    /// or release the storage deposit if everything was sent.
+    #[private]
+    pub fn callback_post_force_unregister(
+        &mut self,
+        account_id: AccountId,
+        transfers: UnregisterTransfers,
+    ) {
+        assert_eq!(
+            env::promise_results_count(),
+            (transfers.rewards.len() + transfers.seeds.len() + transfers.nfts.len()) as u64,
+            "{}",
+            ERR28_CALLBACK_POST_STORAGE_TRANSFER_INVALID
+        );
+
+        let mut results = (0..env::promise_results_count())
+            .map(|index| !matches!(env::promise_result(index), PromiseResult::Failed));
+        let mut is_reverted = false;
+        for (token_id, amount) in transfers.rewards.into_iter() {
+            if results.next().unwrap() {
+                NearEvent::log_withdraw_reward(WithdrawRewardData {
+                    account_id: account_id.clone(),
+                    reward_token: token_id,
+                    amount: amount.0.to_string(),
+                });
+            } else {
+                is_reverted = true;
+                let mut farmer = self.get_farmer(&account_id);
+                farmer.get_ref_mut().add_reward(&token_id, amount.0);
+                self.data_mut().farmers.insert(&account_id, &farmer);
+            }
+        }
+        let mut withdraw_seed_data: Vec<SeedData> = vec![];
+        for (seed_id, amount) in transfers.seeds.into_iter() {
+            if results.next().unwrap() {
+                withdraw_seed_data.push(SeedData {
+                    account_id: account_id.clone(),
+                    seed_id,
+                    amount: amount.0.to_string(),
+                    contract_nft_token_id: None,
+                });
+            } else {
+                is_reverted = true;
+                self.internal_seed_credit(&seed_id, &account_id, amount.0);
+            }
+        }
+        for (seed_id, contract_nft_token_id) in transfers.nfts.into_iter() {
+            if results.next().unwrap() {
+                withdraw_seed_data.push(SeedData {
+                    account_id: account_id.clone(),
+                    seed_id: seed_id.clone(),
+                    amount: "0".to_string(),
+                    contract_nft_token_id: Some(contract_nft_token_id.clone()),
+                });
+                self.data_mut().nft_trait_multipliers.remove(&(seed_id, contract_nft_token_id));
+            } else {
+                is_reverted = true;
+                self.internal_nfts_credit(&seed_id, &account_id, &[contract_nft_token_id]);
+            }
+        }
+        if !withdraw_seed_data.is_empty() {
+            NearEvent::log_withdraw_seed(withdraw_seed_data);
+        }
+
+        if is_reverted {
+            env::log(format!("{} force unregister, Callback Failed.", account_id).as_bytes());
+            return;
+        }
+        // the farmer could have deposited again while the transfers were pending
+        let farmer = self.get_farmer(&account_id);
+        if farmer.get_ref().rewards.is_empty() && farmer.get_ref().seeds.is_empty() && !farmer.get_ref().has_nfts()
+            && self.data().zap_refund_ids.get(&account_id).is_none() {
+            self.internal_unregister_account(&account_id);
+        }
+    }
+}
+
 impl Contract {
 
     /// return storage used by given account, and his deposited storage fee 
@@ -177,7 +357,271 @@ impl Contract {
             self.data_mut().farmers.insert(&account_id, &farmer);
         } else {
             self.data_mut().farmers.insert(&account_id, &VersionedFarmer::new(account_id.clone(), amount));
+            self.data_mut().farmer_ids.insert(account_id);
             self.data_mut().farmer_count += 1;
+            // the empty farmer is charged MIN_FARMER_LENGTH, what is added to it is measured
+            let storage_snapshot = (self.internal_unshared_storage_usage(), MIN_FARMER_LENGTH as StorageUsage);
+            self.internal_update_farmer_storage(account_id, storage_snapshot);
+        }
+    }
+
+    /// register the account with the minimal storage deposit taken from storage deposit of the payer,
+    /// the sponsored part of the payer deposit can not be given away.
+    pub(crate) fn internal_register_account_paid_by(&mut self, payer_id: &AccountId, account_id: &AccountId) {
+        let amount = Contract::suggested_min_storage_usage();
+        let sponsored = self.internal_sponsored_storage(payer_id);
+        let mut payer = self.get_farmer(payer_id);
+        assert!(payer.get_ref().amount > sponsored + amount, "{}", ERR11_INSUFFICIENT_STORAGE);
+        payer.get_ref_mut().amount -= amount;
+        self.data_mut().farmers.insert(payer_id, &payer);
+        self.internal_register_account(account_id, amount);
+    }
+
+    /// remove the farmer and send back his storage deposit,
+    /// the sponsored part of it goes back to the sponsorship pool.
+    fn internal_unregister_account(&mut self, account_id: &AccountId) {
+        let farmer = self.data_mut().farmers.remove(account_id).expect(ERR10_ACC_NOT_REGISTERED);
+        self.data_mut().farmer_ids.remove(account_id);
+        self.data_mut().farmer_count -= 1;
+        // staking managers, auto compound and parked approvals can be set without a position
+        let seed_ids = self.data().seeds.keys_as_vector().to_vec();
+        for seed_id in seed_ids.iter() {
+            self.data_mut().staking_managers.remove(&(account_id.clone(), seed_id.clone()));
+            self.internal_remove_auto_compound(account_id, seed_id);
+        }
+        self.data_mut().zap_refund_ids.remove(account_id);
+        for contract_nft_token_id in self.data_mut().nft_approval_ids.remove(account_id).unwrap_or_default() {
+            self.data_mut().nft_approvals.remove(&contract_nft_token_id);
+        }
+        let sponsored = self.data_mut().sponsored_storage.remove(account_id).unwrap_or(0);
+        if sponsored > 0 {
+            let storage_sponsorship = &mut self.data_mut().storage_sponsorship;
+            storage_sponsorship.balance += sponsored;
+            storage_sponsorship.sponsored -= sponsored;
+        }
+        // Note: farmer is registered again if the transfer fails.
+        let amount = farmer.get_ref().amount - sponsored;
+        Promise::new(account_id.clone()).transfer(amount)
+            .then(ext_self::callback_post_storage_unregister(
+                account_id.clone(),
+                amount.into(),
+                &env::current_account_id(),
+                0,
+                GAS_FOR_RESOLVE_TRANSFER,
+            ));
+    }
+
+    /// Take out all rewards, seeds and nfts of the farmer and send them back in one batch,
+    /// result goes to `callback_post_force_unregister`.
+    /// nfts staked by approval stay in the farmer wallet and are unstaked with their rewards forfeited,
+    /// locked seeds must have ended.
+    fn internal_force_unregister(&mut self, account_id: &AccountId) {
+        let farmer = self.get_farmer(account_id);
+        let mut seed_ids: Vec<SeedId> = farmer.get_ref().seeds.keys().cloned().collect();
+        for seed_id in farmer.get_ref().nft_seeds.keys() {
+            if !seed_ids.contains(seed_id) {
+                seed_ids.push(seed_id.clone());
+            }
+        }
+
+        for seed_id in seed_ids.iter() {
+            self.internal_assert_not_managed(account_id, seed_id);
+            self.internal_remove_auto_compound(account_id, seed_id);
+        }
+        for seed_id in seed_ids.iter() {
+            let approval_staked_nfts = self.internal_get_approval_staked_nfts(account_id, seed_id);
+            if !approval_staked_nfts.is_empty() {
+                self.internal_evict_nfts(seed_id, account_id, &approval_staked_nfts);
+            }
+            self.internal_claim_user_reward_by_seed_id(account_id, seed_id);
+        }
+
+        let storage_snapshot = self.internal_farmer_storage_snapshot(account_id);
+        let mut farmer = self.get_farmer(account_id);
+        let current_block_time = to_sec(env::block_timestamp());
+        for locked_seed in farmer.get_ref().locked_seeds.values() {
+            assert!(locked_seed.ended_at <= current_block_time, "{}", ERR39_USER_CANNOT_UNLOCK_SEED);
+        }
+        farmer.get_ref_mut().locked_seeds.clear();
+
+        let mut transfers = UnregisterTransfers::default();
+        for seed_id in seed_ids.iter() {
+            let mut farm_seed = self.get_seed(seed_id);
+            let amount = farmer.get_ref().get_balance(seed_id);
+            if let Some(mut nft_contract_seed) = farmer.get_ref_mut().nft_seeds.remove(seed_id) {
+                for contract_nft_token_id in nft_contract_seed.iter() {
+                    self.internal_burn_nft_receipt(&contract_nft_token_id);
+                    transfers.nfts.push((seed_id.clone(), contract_nft_token_id));
+                }
+                nft_contract_seed.clear();
+            } else if amount > 0 {
+                transfers.seeds.push((seed_id.clone(), amount.into()));
+            }
+            farmer.get_ref_mut().seeds.remove(seed_id);
+            farm_seed.get_ref_mut().sub_amount(amount);
+            for farm_id in farm_seed.get_ref().farms.iter() {
+                farmer.get_ref_mut().remove_rps(farm_id);
+            }
+            self.data_mut().seeds.insert(seed_id, &farm_seed);
+        }
+        for farm_id in self.data().outdated_farms.keys() {
+            farmer.get_ref_mut().remove_rps(&farm_id);
+        }
+        for (token_id, amount) in farmer.get_ref_mut().rewards.drain() {
+            if amount > 0 {
+                transfers.rewards.push((token_id, amount.into()));
+            }
+        }
+        assert!(
+            transfers.rewards.len() + transfers.seeds.len() + transfers.nfts.len() <= MAX_NFT_BATCH_SIZE,
+            "{}", ERR16_TOO_MANY_POSITIONS_TO_UNREGISTER
+        );
+        self.data_mut().farmers.insert(account_id, &farmer);
+        for seed_id in seed_ids.iter() {
+            self.internal_update_seed_staker(seed_id, farmer.get_ref());
+        }
+        self.internal_update_farmer_storage(account_id, storage_snapshot);
+
+        if transfers.rewards.is_empty() && transfers.seeds.is_empty() && transfers.nfts.is_empty() {
+            // only dust without weight was left
+            self.internal_unregister_account(account_id);
+            return;
+        }
+
+        let mut batch: Option<Promise> = None;
+        for (token_id, amount) in transfers.rewards.iter() {
+            let transfer = ext_fungible_token::ft_transfer(
+                account_id.clone(),
+                *amount,
+                None,
+                token_id,
+                1,
+                GAS_FOR_FT_TRANSFER,
+            );
+            batch = Some(match batch {
+                Some(promise) => promise.and(transfer),
+                None => transfer,
+            });
+        }
+        for (seed_id, amount) in transfers.seeds.iter() {
+            let transfer = if self.is_seed_type(seed_id, SeedType::MFT) {
+                let (seed_contract_id, token_id) = parse_seed_id(seed_id);
+                ext_multi_fungible_token::mft_transfer(
+                    wrap_mft_token_id(&token_id),
+                    account_id.clone(),
+                    *amount,
+                    None,
+                    &seed_contract_id,
+                    1,
+                    GAS_FOR_FT_TRANSFER,
+                )
+            } else {
+                let seed_contract_id: AccountId = seed_id.split(FT_INDEX_TAG).next().unwrap().to_string();
+                ext_fungible_token::ft_transfer(
+                    account_id.clone(),
+                    *amount,
+                    None,
+                    &seed_contract_id,
+                    1,
+                    GAS_FOR_FT_TRANSFER,
+                )
+            };
+            batch = Some(match batch {
+                Some(promise) => promise.and(transfer),
+                None => transfer,
+            });
+        }
+        for (_, contract_nft_token_id) in transfers.nfts.iter() {
+            let (nft_contract_id, nft_token_id) = parse_contract_nft_token_id(contract_nft_token_id);
+            let transfer = ext_non_fungible_token::nft_transfer(
+                account_id.clone(),
+                nft_token_id,
+                None,
+                None,
+                &nft_contract_id,
+                1,
+                GAS_FOR_NFT_TRANSFER,
+            );
+            batch = Some(match batch {
+                Some(promise) => promise.and(transfer),
+                None => transfer,
+            });
+        }
+        batch.unwrap().then(ext_self::callback_post_force_unregister(
+            account_id.clone(),
+            transfers,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_FORCE_UNREGISTER,
+        ));
+    }
+
+    /// storage deposit of the farmer sponsored by the pool
+    pub(crate) fn internal_sponsored_storage(&self, account_id: &AccountId) -> Balance {
+        self.data().sponsored_storage.get(account_id).unwrap_or(0)
+    }
+
+    /// register first time depositor of an auto registration seed with its deposit covered by the sponsorship pool,
+    /// returns false if the account is not registered and can not be.
+    pub(crate) fn internal_auto_register_account(&mut self, account_id: &AccountId, seed_id: &SeedId) -> bool {
+        if self.data().farmers.contains_key(account_id) {
+            return true;
+        }
+        let min_storage = Contract::suggested_min_storage_usage();
+        let storage_sponsorship = &self.data().storage_sponsorship;
+        if !self.data().auto_register_seeds.contains(seed_id)
+            || min_storage > storage_sponsorship.cap
+            || min_storage > storage_sponsorship.balance {
+            return false;
+        }
+        self.internal_register_account(account_id, min_storage);
+        self.internal_sponsor_storage(account_id, min_storage);
+        true
+    }
+
+    /// cover the registration deposit shortfall of a new farmer from the sponsorship pool.
+    fn internal_sponsor_storage(&mut self, account_id: &AccountId, amount: Balance) {
+        let storage_sponsorship = &self.data().storage_sponsorship;
+        assert!(amount <= storage_sponsorship.cap, "{}", ERR11_INSUFFICIENT_STORAGE);
+        assert!(amount <= storage_sponsorship.balance, "{}", ERR15_INSUFFICIENT_STORAGE_SPONSORSHIP);
+
+        let storage_snapshot = self.internal_farmer_storage_snapshot(account_id);
+        let storage_sponsorship = &mut self.data_mut().storage_sponsorship;
+        storage_sponsorship.balance -= amount;
+        storage_sponsorship.sponsored += amount;
+        self.data_mut().sponsored_storage.insert(account_id, &amount);
+        self.internal_update_farmer_storage(account_id, storage_snapshot);
+    }
+
+    /// contract storage usage less the storage grown or released by shared records within the current call.
+    fn internal_unshared_storage_usage(&self) -> StorageUsage {
+        (env::storage_usage() as i64 - self.shared_storage_usage) as StorageUsage
+    }
+
+    /// keep storage of shared records changed since prev_storage out of the farmer storage.
+    pub(crate) fn internal_track_shared_storage(&mut self, prev_storage: StorageUsage) {
+        self.shared_storage_usage += env::storage_usage() as i64 - prev_storage as i64;
+    }
+
+    /// contract storage usage, less the shared storage changed so far, and storage used by the farmer,
+    /// taken before the farmer is mutated.
+    pub(crate) fn internal_farmer_storage_snapshot(&self, account_id: &AccountId) -> (StorageUsage, StorageUsage) {
+        let storage_used = self.get_farmer_wrapped(account_id)
+            .map(|farmer| farmer.get_ref().storage_used)
+            .unwrap_or(0);
+        (self.internal_unshared_storage_usage(), storage_used)
+    }
+
+    /// charge the farmer for storage grown since the snapshot, or release the storage freed.
+    /// the farmer storage is set from its snapshot value, so nested measurements are not counted twice.
+    pub(crate) fn internal_update_farmer_storage(&mut self, account_id: &AccountId, snapshot: (StorageUsage, StorageUsage)) {
+        if let Some(mut farmer) = self.get_farmer_wrapped(account_id) {
+            let (prev_storage, prev_storage_used) = snapshot;
+            // save first so an upgraded farmer record is measured at its final size
+            self.data_mut().farmers.insert(account_id, &farmer);
+            let storage = self.internal_unshared_storage_usage();
+            farmer.get_ref_mut().storage_used = (prev_storage_used + storage).saturating_sub(prev_storage);
+            self.data_mut().farmers.insert(account_id, &farmer);
         }
     }
 
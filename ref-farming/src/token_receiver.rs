@@ -1,12 +1,14 @@
 use crate::errors::*;
-use crate::farm_seed::SeedType;
-use crate::utils::MFT_TAG;
+use crate::event::{NearEvent, AddRewardData, RefundDepositData};
+use crate::farm_seed::{SeedType, NftApproval};
+use crate::utils::{ext_non_fungible_token, ext_self, MFT_TAG, NFT_DELIMETER, GAS_FOR_NFT_TOKEN, GAS_FOR_RESOLVE_NFT_DEPOSIT, MAX_NFT_BATCH_SIZE, get_nft_balance_equivalent};
 use crate::*;
 use near_sdk::json_types::U128;
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::PromiseOrValue;
 
 use near_contract_standards::non_fungible_token::core::NonFungibleTokenReceiver;
+use near_contract_standards::non_fungible_token::approval::NonFungibleTokenApprovalReceiver;
 use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
 
 pub type TokenId = String;
@@ -18,11 +20,33 @@ pub struct FarmArgs {
     pub seed_id: String,
 }
 
+/// seed deposit msg, to stake on behalf of another account
+#[derive(Serialize, Deserialize, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeedDepositArgs {
+    /// seed of nft deposits, not used by ft and mft deposits
+    pub seed_id: Option<SeedId>,
+    /// registered account credited with the seed, the sender by default
+    pub beneficiary_id: Option<ValidAccountId>,
+    /// account controlling withdrawals of the position instead of the beneficiary
+    pub staking_manager_id: Option<ValidAccountId>,
+}
+
+/// empty msg means the sender stakes for himself
+fn parse_seed_deposit_args(msg: &str) -> SeedDepositArgs {
+    if msg.is_empty() {
+        SeedDepositArgs::default()
+    } else {
+        near_sdk::serde_json::from_str(msg).expect(ERR60_INVALID_SEED_DEPOSIT_MSG)
+    }
+}
+
 #[near_bindgen]
 impl FungibleTokenReceiver for Contract {
     /// Callback on receiving tokens by this contract.
     /// transfer reward token with specific msg indicate
     /// which farm to be deposited to.
+    /// seed token is deposited with empty msg, or `SeedDepositArgs` as json.
     fn ft_on_transfer(
         &mut self,
         sender_id: ValidAccountId,
@@ -33,8 +57,10 @@ impl FungibleTokenReceiver for Contract {
         let amount: u128 = amount.into();
         let ft_contract_id = env::predecessor_account_id();
 
-        if msg.is_empty() {
+        if msg.is_empty() || msg.starts_with('{') {
             // ****** seed Token deposit in ********
+            let args = parse_seed_deposit_args(&msg);
+            let farmer_id: AccountId = args.beneficiary_id.map(|a| a.into()).unwrap_or_else(|| sender.clone());
 
             // if seed not exist, it will panic
             let seed_farm = self.get_seed(&ft_contract_id);
@@ -52,24 +78,27 @@ impl FungibleTokenReceiver for Contract {
                 )
             }
 
+            let staking_manager_id = args.staking_manager_id.map(|a| a.into());
+            if let Err(reason) = self.internal_prepare_seed_deposit(&farmer_id, &ft_contract_id, &sender, staking_manager_id) {
+                NearEvent::log_refund_deposit(RefundDepositData {
+                    account_id: sender,
+                    seed_id: ft_contract_id,
+                    amount: amount.to_string(),
+                    contract_nft_token_id: None,
+                    reason: reason.to_string(),
+                });
+                return PromiseOrValue::Value(U128(amount));
+            }
+
             self.internal_seed_deposit(
                 &ft_contract_id,
-                &sender,
+                &farmer_id,
                 amount.into(),
                 SeedType::FT,
             );
 
-            self.assert_storage_usage(&sender);
+            self.assert_storage_usage(&farmer_id);
 
-            env::log(
-                format!(
-                    "{} deposit FT seed {} with amount {}.",
-                    sender,
-                    ft_contract_id,
-                    amount,
-                )
-                .as_bytes(),
-            );
             PromiseOrValue::Value(U128(0))
         } else {
             // ****** reward Token deposit in ********
@@ -96,13 +125,13 @@ impl FungibleTokenReceiver for Contract {
                     .reward_info
                     .insert(&env::predecessor_account_id(), &(old_balance + amount));
 
-                env::log(
-                    format!(
-                        "{} added {} Reward Token, Now has {} left",
-                        sender, amount, cur_remain
-                    )
-                    .as_bytes(),
-                );
+                NearEvent::log_add_reward(AddRewardData {
+                    account_id: sender,
+                    farm_id,
+                    reward_token: env::predecessor_account_id(),
+                    amount: amount.to_string(),
+                    undistributed: cur_remain.to_string(),
+                });
                 PromiseOrValue::Value(U128(0))
             } else {
                 env::panic(format!("{}", ERR43_INVALID_FARM_STATUS).as_bytes())
@@ -169,7 +198,9 @@ impl MFTTokenReceiver for Contract {
             }
         }
 
-        assert!(msg.is_empty(), "ERR_MSG_INCORRECT");
+        assert!(msg.is_empty() || msg.starts_with('{'), "ERR_MSG_INCORRECT");
+        let args = parse_seed_deposit_args(&msg);
+        let farmer_id: AccountId = args.beneficiary_id.map(|a| a.into()).unwrap_or_else(|| sender_id.clone());
 
         // if seed not exist, it will panic
         let amount: u128 = amount.into();
@@ -188,17 +219,21 @@ impl MFTTokenReceiver for Contract {
             )
         }
 
-        self.internal_seed_deposit(&seed_id, &sender_id, amount, SeedType::MFT);
+        let staking_manager_id = args.staking_manager_id.map(|a| a.into());
+        if let Err(reason) = self.internal_prepare_seed_deposit(&farmer_id, &seed_id, &sender_id, staking_manager_id) {
+            NearEvent::log_refund_deposit(RefundDepositData {
+                account_id: sender_id,
+                seed_id,
+                amount: amount.to_string(),
+                contract_nft_token_id: None,
+                reason: reason.to_string(),
+            });
+            return PromiseOrValue::Value(U128(amount));
+        }
 
-        self.assert_storage_usage(&sender_id);
+        self.internal_seed_deposit(&seed_id, &farmer_id, amount, SeedType::MFT);
 
-        env::log(
-            format!(
-                "{} deposit MFT seed {} with amount {}.",
-                sender_id, seed_id, amount,
-            )
-            .as_bytes(),
-        );
+        self.assert_storage_usage(&farmer_id);
 
         PromiseOrValue::Value(U128(0))
     }
@@ -207,6 +242,7 @@ impl MFTTokenReceiver for Contract {
 // Receiving NFTs
 #[near_bindgen]
 impl NonFungibleTokenReceiver for Contract {
+    /// msg is the seed id, or `SeedDepositArgs` as json.
     fn nft_on_transfer(
         &mut self,
         sender_id: AccountId,
@@ -230,10 +266,141 @@ impl NonFungibleTokenReceiver for Contract {
             "Paras(farming): owner_id should be signer_id"
         );
 
-        let deposit_res = self.internal_nft_deposit(&msg, &previous_owner_id.to_string(), &nft_contract_id, &token_id);
+        let (seed_id, args) = if msg.starts_with('{') {
+            let args = parse_seed_deposit_args(&msg);
+            (args.seed_id.clone().expect(ERR60_INVALID_SEED_DEPOSIT_MSG), args)
+        } else {
+            (msg, SeedDepositArgs::default())
+        };
+        let farmer_id: AccountId = args.beneficiary_id.map(|a| a.into()).unwrap_or_else(|| previous_owner_id.clone());
+
+        // a nft staked by approval of its previous owner has been moved here
+        let contract_nft_token_id = format!("{}{}{}", nft_contract_id, NFT_DELIMETER, token_id);
+        assert!(self.internal_check_approval_stake(&contract_nft_token_id, &previous_owner_id).is_none(), "{}", ERR59_NFT_ALREADY_STAKED);
+        assert!(
+            !self.data().nft_approval_stake_seeds.contains(&seed_id),
+            "Paras(farming): seed only accepts nft staked by nft_approve"
+        );
+
+        // returning true sends the nft back to its previous owner
+        let staking_manager_id = args.staking_manager_id.map(|a| a.into());
+        if let Err(reason) = self.internal_prepare_seed_deposit(&farmer_id, &seed_id, &previous_owner_id, staking_manager_id) {
+            let nft_balance_equivalent = self.data().nft_balance_seeds.get(&seed_id)
+                .and_then(|nft_balance| get_nft_balance_equivalent(nft_balance, contract_nft_token_id.clone()))
+                .unwrap_or(0);
+            NearEvent::log_refund_deposit(RefundDepositData {
+                account_id: previous_owner_id,
+                seed_id,
+                amount: nft_balance_equivalent.to_string(),
+                contract_nft_token_id: Some(contract_nft_token_id),
+                reason: reason.to_string(),
+            });
+            return PromiseOrValue::Value(true);
+        }
+
+        if self.data().nft_trait_rules.get(&seed_id).is_some() {
+            // weight depends on token traits, read metadata before crediting the seed
+            let farm_seed = self.get_seed(&seed_id);
+            assert_eq!(farm_seed.get_ref().seed_type, SeedType::NFT, "Cannot deposit NFT to this farm");
+            let nft_balance = self.data().nft_balance_seeds.get(&seed_id).unwrap();
+            if get_nft_balance_equivalent(nft_balance, contract_nft_token_id).is_none() {
+                panic!("Paras(farming): nft token does not exist on seed");
+            }
+
+            return PromiseOrValue::Promise(
+                ext_non_fungible_token::nft_token(
+                    token_id.clone(),
+                    &nft_contract_id,
+                    0,
+                    GAS_FOR_NFT_TOKEN
+                )
+                .then(ext_self::callback_post_nft_token_traits(
+                    seed_id,
+                    farmer_id,
+                    nft_contract_id,
+                    token_id,
+                    &env::current_account_id(),
+                    0,
+                    GAS_FOR_RESOLVE_NFT_DEPOSIT
+                ))
+            );
+        }
+
+        let deposit_res = self.internal_nft_deposit(&seed_id, &farmer_id, &nft_contract_id, &token_id);
         if !deposit_res {
             panic!("Paras(farming): nft token does not exist on seed");
         }
         PromiseOrValue::Value(false)
     }
 }
+
+// Receiving NFT approvals, staked in place for approval staking seeds,
+// else staked in batch by `stake_approved_nfts`
+#[near_bindgen]
+impl NonFungibleTokenApprovalReceiver for Contract {
+    fn nft_on_approve(
+        &mut self,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+        msg: String,
+    ) -> PromiseOrValue<String> {
+        let nft_contract_id = env::predecessor_account_id();
+        let signer_id = env::signer_account_id();
+
+        assert_ne!(
+            nft_contract_id, signer_id,
+            "Paras(farming): nft_on_approve should only be called via cross-contract call"
+        );
+
+        assert_eq!(
+            owner_id,
+            signer_id,
+            "Paras(farming): owner_id should be signer_id"
+        );
+
+        // only registered farmers can park approvals in this contract
+        self.get_farmer(&owner_id);
+
+        let contract_nft_token_id = format!("{}{}{}", nft_contract_id, NFT_DELIMETER, token_id);
+        // approval stake of the previous owner is not valid anymore
+        if let Some(mut stake) = self.internal_check_approval_stake(&contract_nft_token_id, &owner_id) {
+            assert_eq!(stake.seed_id, msg, "{}", ERR59_NFT_ALREADY_STAKED);
+            stake.approval_id = approval_id;
+            self.data_mut().nft_approval_stakes.insert(&contract_nft_token_id, &stake);
+            return PromiseOrValue::Value(String::from("true"));
+        }
+
+        if self.data().nft_approval_stake_seeds.contains(&msg) {
+            // count the nft as staked while it stays in owner wallet
+            assert!(
+                self.internal_get_approval_staked_nfts(&owner_id, &msg).len() < MAX_NFT_BATCH_SIZE,
+                "{}", ERR58_TOO_MANY_APPROVAL_STAKED_NFTS
+            );
+            let deposit_res = self.internal_nft_deposit(&msg, &owner_id, &nft_contract_id, &token_id);
+            if !deposit_res {
+                panic!("Paras(farming): nft token does not exist on seed");
+            }
+            self.data_mut().nft_approval_stakes.insert(&contract_nft_token_id, &NftApproval {
+                owner_id,
+                approval_id,
+                seed_id: msg,
+            });
+            return PromiseOrValue::Value(String::from("true"));
+        }
+
+        let farm_seed = self.get_seed(&msg);
+        assert_eq!(farm_seed.get_ref().seed_type, SeedType::NFT, "Cannot deposit NFT to this farm");
+        let nft_balance = self.data().nft_balance_seeds.get(&msg).unwrap();
+        if get_nft_balance_equivalent(nft_balance, contract_nft_token_id.clone()).is_none() {
+            panic!("Paras(farming): nft token does not exist on seed");
+        }
+
+        self.internal_park_nft_approval(&contract_nft_token_id, NftApproval {
+            owner_id,
+            approval_id,
+            seed_id: msg,
+        });
+        PromiseOrValue::Value(String::from("true"))
+    }
+}
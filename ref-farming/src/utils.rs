@@ -1,10 +1,16 @@
+use std::collections::HashMap;
 
-use near_sdk::json_types::U128;
-use near_sdk::{Balance, env, ext_contract, Gas, Timestamp};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::Value;
+use near_sdk::{AccountId, Balance, env, ext_contract, Gas, Timestamp};
+use near_contract_standards::non_fungible_token::metadata::TokenMetadata;
 use uint::construct_uint;
-use crate::{SeedId, FarmId, NftBalance};
+use crate::{SeedId, FarmId, NftBalance, NftTraitRule};
 use crate::errors::*;
 use crate::simple_farm::ContractNFTTokenId;
+use crate::storage_impl::UnregisterTransfers;
+use crate::zap::ZapArgs;
 
 pub type TimestampSec = u32;
 
@@ -13,12 +19,59 @@ pub const MAX_ACCOUNT_LENGTH: u128 = 64;
 /// Amount of gas for fungible token transfers.
 pub const GAS_FOR_FT_TRANSFER: Gas = 10_000_000_000_000;
 pub const GAS_FOR_NFT_TRANSFER: Gas = 20_000_000_000_000;
+pub const GAS_FOR_NFT_TOKEN: Gas = 10_000_000_000_000;
+/// crediting a nft claims rewards of every farm in the seed
+pub const GAS_FOR_RESOLVE_NFT_DEPOSIT: Gas = 50_000_000_000_000;
 /// hotfix_insuffient_gas_for_mft_resolve_transfer, increase from 5T to 20T
 pub const GAS_FOR_RESOLVE_TRANSFER: Gas = 20_000_000_000_000;
 pub const MFT_TAG: &str = "@";
 pub const FT_INDEX_TAG: &str = "$";
 pub const NFT_DELIMETER: &str = "@";
 pub const PARAS_SERIES_DELIMETER: &str = ":";
+/// max nfts in one withdraw_nfts/stake_approved_nfts and approval staked nfts per seed of a farmer,
+/// bounded by gas of the joint transfers and ownership checks
+pub const MAX_NFT_BATCH_SIZE: usize = 8;
+/// crediting or reverting a nft batch claims rewards of every farm in the seed once
+pub const GAS_FOR_RESOLVE_NFT_BATCH: Gas = 50_000_000_000_000;
+/// releasing the storage deposit after a forced unregister, or crediting back what failed to be sent
+pub const GAS_FOR_RESOLVE_FORCE_UNREGISTER: Gas = 60_000_000_000_000;
+/// kept out of the receiver call of nft_transfer_call, for the resolve and the rest of the call
+pub const GAS_FOR_RECEIPT_TRANSFER_CALL: Gas = 25_000_000_000_000 + GAS_FOR_RESOLVE_TRANSFER;
+/// pushing a staked balance to the receipt token of the seed
+pub const GAS_FOR_RECEIPT_SYNC: Gas = 5_000_000_000_000;
+/// max stakers pushed to a receipt token in one sync_receipt_token
+pub const MAX_RECEIPT_SYNC_BATCH_SIZE: u64 = 20;
+/// max farmers settled in one auto_compound, each claims rewards of every farm in the seed
+pub const MAX_AUTO_COMPOUND_BATCH_SIZE: u64 = 20;
+/// multiplier of a nft that matches no trait rule, means 1x
+pub const NFT_TRAIT_MULTIPLIER_DENOM: u32 = 100;
+/// ft_transfer_call of a reward token into the ref exchange, with its ft_on_transfer and resolve
+pub const GAS_FOR_ZAP_DEPOSIT: Gas = 50_000_000_000_000;
+pub const GAS_FOR_ZAP_SWAP: Gas = 15_000_000_000_000;
+pub const GAS_FOR_ZAP_ADD_LIQUIDITY: Gas = 15_000_000_000_000;
+pub const GAS_FOR_ZAP_REMOVE_LIQUIDITY: Gas = 15_000_000_000_000;
+pub const GAS_FOR_ZAP_GET_DEPOSITS: Gas = 5_000_000_000_000;
+pub const GAS_FOR_ZAP_SHARE_BALANCE: Gas = 5_000_000_000_000;
+pub const GAS_FOR_ZAP_REGISTER_TOKENS: Gas = 10_000_000_000_000;
+/// withdraw from the ref exchange sends the token back with a callback of its own
+pub const GAS_FOR_ZAP_WITHDRAW: Gas = 40_000_000_000_000;
+/// crediting both withdrawn pool tokens confirmed by the exchange deposits
+pub const GAS_FOR_RESOLVE_ZAP_CONFIRM: Gas = 15_000_000_000_000;
+pub const GAS_FOR_RESOLVE_ZAP_WITHDRAW: Gas = GAS_FOR_ZAP_GET_DEPOSITS + GAS_FOR_RESOLVE_ZAP_CONFIRM + 10_000_000_000_000;
+/// both pool tokens left in the exchange are withdrawn back
+pub const GAS_FOR_RESOLVE_ZAP_SWEEP: Gas = 2 * GAS_FOR_ZAP_WITHDRAW + GAS_FOR_RESOLVE_ZAP_WITHDRAW + 5_000_000_000_000;
+pub const GAS_FOR_ZAP_SWEEP: Gas = GAS_FOR_ZAP_GET_DEPOSITS + GAS_FOR_RESOLVE_ZAP_SWEEP;
+/// shares that can not be removed are staked, claiming rewards of every farm in the seed
+pub const GAS_FOR_RESOLVE_ZAP_REMOVE_LIQUIDITY: Gas = GAS_FOR_ZAP_SWEEP + 15_000_000_000_000;
+/// staking the shares or removing them again, then sweeping what is left in the exchange
+pub const GAS_FOR_RESOLVE_ZAP_SHARES: Gas = GAS_FOR_ZAP_REMOVE_LIQUIDITY + GAS_FOR_RESOLVE_ZAP_REMOVE_LIQUIDITY + 5_000_000_000_000;
+/// the minted shares may be read from the share balance first
+pub const GAS_FOR_RESOLVE_ZAP_ADD_LIQUIDITY: Gas = GAS_FOR_ZAP_SHARE_BALANCE + GAS_FOR_RESOLVE_ZAP_SHARES + 5_000_000_000_000;
+pub const GAS_FOR_RESOLVE_ZAP_SHARES_BEFORE: Gas = GAS_FOR_ZAP_ADD_LIQUIDITY + GAS_FOR_RESOLVE_ZAP_ADD_LIQUIDITY + 5_000_000_000_000;
+pub const GAS_FOR_RESOLVE_ZAP_SWAP: Gas = GAS_FOR_ZAP_SHARE_BALANCE + GAS_FOR_RESOLVE_ZAP_SHARES_BEFORE + 5_000_000_000_000;
+pub const GAS_FOR_RESOLVE_ZAP_DEPOSIT: Gas = GAS_FOR_ZAP_SWAP + GAS_FOR_RESOLVE_ZAP_SWAP + 5_000_000_000_000;
+// bound of checkpoints kept for each farm
+pub const MAX_FARM_CHECKPOINTS: u64 = 720;
 
 
 construct_uint! {
@@ -30,6 +83,8 @@ construct_uint! {
 #[ext_contract(ext_fungible_token)]
 pub trait FungibleToken {
     fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+
+    fn ft_transfer_call(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>, msg: String) -> U128;
 }
 
 /// TODO: this should be in the near_standard_contracts
@@ -38,6 +93,17 @@ pub trait MultiFungibleToken {
     fn mft_transfer(&mut self, token_id: String, receiver_id: AccountId, amount: U128, memo: Option<String>);
 }
 
+/// companion NEP-141 contract of a seed with a receipt token
+#[ext_contract(ext_receipt_token)]
+pub trait ReceiptToken {
+    fn ft_sync(&mut self, account_id: AccountId, balance: U128, nonce: U64);
+}
+
+#[ext_contract(ext_non_fungible_token_receiver)]
+pub trait NonFungibleTokenReceiver {
+    fn nft_on_transfer(&mut self, sender_id: AccountId, previous_owner_id: AccountId, token_id: String, msg: String) -> PromiseOrValue<bool>;
+}
+
 #[ext_contract(ext_non_fungible_token)]
 pub trait NonFungibleToken {
     fn nft_transfer(
@@ -47,6 +113,37 @@ pub trait NonFungibleToken {
         approval_id: Option<u64>,
         memo: Option<String>,
     );
+
+    fn nft_token(&self, token_id: String);
+}
+
+/// single hop swap of the ref exchange
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapAction {
+    pub pool_id: u64,
+    pub token_in: AccountId,
+    pub amount_in: Option<U128>,
+    pub token_out: AccountId,
+    pub min_amount_out: U128,
+}
+
+/// deposits of this contract in the ref exchange, used by claim_and_zap
+#[ext_contract(ext_ref_exchange)]
+pub trait RefExchange {
+    fn swap(&mut self, actions: Vec<SwapAction>, referral_id: Option<AccountId>) -> U128;
+
+    fn add_liquidity(&mut self, pool_id: u64, amounts: Vec<U128>) -> U128;
+
+    fn remove_liquidity(&mut self, pool_id: u64, shares: U128, min_amounts: Vec<U128>) -> Vec<U128>;
+
+    fn withdraw(&mut self, token_id: AccountId, amount: U128, unregister: Option<bool>);
+
+    fn get_deposits(&self, account_id: AccountId) -> HashMap<AccountId, U128>;
+
+    fn mft_balance_of(&self, token_id: String, account_id: AccountId) -> U128;
+
+    fn register_tokens(&mut self, token_ids: Vec<AccountId>);
 }
 
 #[ext_contract(ext_self)]
@@ -79,6 +176,99 @@ pub trait TokenPostActions {
         nft_contract_id: String,
         nft_token_id: String
     );
+
+    fn callback_post_withdraw_nfts(
+        &mut self,
+        seed_id: SeedId,
+        sender_id: AccountId,
+        contract_nft_token_ids: Vec<ContractNFTTokenId>
+    );
+
+    fn callback_post_stake_approved_nfts(
+        &mut self,
+        seed_id: SeedId,
+        sender_id: AccountId,
+        contract_nft_token_ids: Vec<ContractNFTTokenId>
+    );
+
+    fn callback_post_verify_approval_stakes(
+        &mut self,
+        seed_id: SeedId,
+        sender_id: AccountId,
+        contract_nft_token_ids: Vec<ContractNFTTokenId>,
+        is_withdraw: bool
+    );
+
+    fn callback_post_verify_staked_nfts(
+        &mut self,
+        seed_id: SeedId,
+        farmer_id: AccountId,
+        contract_nft_token_ids: Vec<ContractNFTTokenId>
+    );
+
+    fn callback_post_nft_token_traits(
+        &mut self,
+        seed_id: SeedId,
+        sender_id: AccountId,
+        nft_contract_id: String,
+        nft_token_id: String
+    ) -> bool;
+
+    fn callback_post_storage_withdraw(
+        &mut self,
+        account_id: AccountId,
+        amount: U128,
+    );
+
+    fn callback_post_storage_unregister(
+        &mut self,
+        account_id: AccountId,
+        amount: U128,
+    );
+
+    fn callback_post_force_unregister(
+        &mut self,
+        account_id: AccountId,
+        transfers: UnregisterTransfers,
+    );
+
+    fn callback_post_zap_deposit(&mut self, sender_id: AccountId, args: ZapArgs, amount: U128);
+
+    fn callback_post_zap_swap(&mut self, sender_id: AccountId, args: ZapArgs, amount: U128);
+
+    fn callback_post_zap_shares_before(&mut self, sender_id: AccountId, args: ZapArgs, amount: U128, amount_out: U128);
+
+    fn callback_post_zap_add_liquidity(&mut self, sender_id: AccountId, args: ZapArgs, amount: U128, shares_before: U128);
+
+    fn callback_post_zap_shares_after(&mut self, sender_id: AccountId, args: ZapArgs, amount: U128, shares_before: U128);
+
+    fn callback_post_zap_remove_liquidity(&mut self, sender_id: AccountId, args: ZapArgs, shares: U128);
+
+    fn callback_post_zap_sweep(&mut self, sender_id: AccountId, exchange_id: AccountId, token_ids: Vec<AccountId>);
+
+    fn callback_post_zap_withdraw(
+        &mut self,
+        sender_id: AccountId,
+        exchange_id: AccountId,
+        token_ids: Vec<AccountId>,
+        amounts: Vec<U128>,
+    );
+
+    fn callback_post_zap_confirm(
+        &mut self,
+        sender_id: AccountId,
+        exchange_id: AccountId,
+        token_ids: Vec<AccountId>,
+        amounts: Vec<U128>,
+    );
+
+    fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: ContractNFTTokenId,
+        approved_account_ids: Option<HashMap<AccountId, u64>>,
+    ) -> bool;
 }
 
 /// Assert that 1 yoctoNEAR was attached.
@@ -86,6 +276,16 @@ pub fn assert_one_yocto() {
     assert_eq!(env::attached_deposit(), 1, "Requires attached deposit of exactly 1 yoctoNEAR")
 }
 
+// return nft_contract_id, token_id
+pub fn parse_contract_nft_token_id(contract_nft_token_id: &str) -> (String, String) {
+    let v: Vec<&str> = contract_nft_token_id.splitn(2, NFT_DELIMETER).collect();
+    if v.len() == 2 { // nft_contract_id@token_id
+        (v[0].to_string(), v[1].to_string())
+    } else {
+        env::panic(ERR55_INVALID_CONTRACT_NFT_TOKEN_ID.as_bytes())
+    }
+}
+
 /// wrap token_id into correct format in MFT standard
 pub fn wrap_mft_token_id(token_id: &str) -> String {
     format!(":{}", token_id)
@@ -158,6 +358,55 @@ pub fn get_nft_balance_equivalent(
     return result;
 }
 
+/// collect (trait_type, value) pairs from the `extra` field of token metadata,
+/// which is either a json object of traits `{"rarity": "legendary"}`,
+/// or a list of attributes `[{"trait_type": "rarity", "value": "legendary"}]`,
+/// optionally wrapped in `{"attributes": [...]}`.
+pub fn parse_nft_traits(metadata: &Option<TokenMetadata>) -> Vec<(String, String)> {
+    let extra = match metadata.as_ref().and_then(|m| m.extra.as_ref()) {
+        Some(extra) => extra,
+        None => return vec![],
+    };
+    let value_to_string = |value: &Value| match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    };
+    let attributes = |attributes: &Vec<Value>| -> Vec<(String, String)> {
+        attributes.iter().filter_map(|attribute| {
+            let trait_type = attribute.get("trait_type").and_then(value_to_string)?;
+            let value = attribute.get("value").and_then(value_to_string)?;
+            Some((trait_type, value))
+        }).collect()
+    };
+
+    match near_sdk::serde_json::from_str::<Value>(extra) {
+        Ok(Value::Array(list)) => attributes(&list),
+        Ok(Value::Object(map)) => {
+            if let Some(Value::Array(list)) = map.get("attributes") {
+                attributes(list)
+            } else {
+                map.iter()
+                    .filter_map(|(k, v)| value_to_string(v).map(|v| (k.clone(), v)))
+                    .collect()
+            }
+        },
+        _ => vec![],
+    }
+}
+
+/// return the highest multiplier among the rules matched by nft traits,
+/// NFT_TRAIT_MULTIPLIER_DENOM if nothing matched.
+pub fn get_nft_trait_multiplier(rules: &[NftTraitRule], metadata: &Option<TokenMetadata>) -> u32 {
+    let traits = parse_nft_traits(metadata);
+    rules.iter()
+        .filter(|rule| traits.iter().any(|(t, v)| t == &rule.trait_type && v == &rule.value))
+        .map(|rule| rule.multiplier)
+        .max()
+        .unwrap_or(NFT_TRAIT_MULTIPLIER_DENOM)
+}
+
 pub fn is_paras_farming_mainnet() -> bool{
     env::current_account_id() == "staking.paras.near"
 }
@@ -7,7 +7,7 @@ use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{near_bindgen, AccountId};
 
 use crate::farm_seed::SeedInfo;
-use crate::utils::{parse_farm_id, PARAS_SERIES_DELIMETER, NFT_DELIMETER};
+use crate::utils::{parse_farm_id, to_sec, PARAS_SERIES_DELIMETER, NFT_DELIMETER};
 use crate::simple_farm::DENOM;
 use crate::*;
 
@@ -47,6 +47,11 @@ pub struct FarmInfo {
     pub claimed_reward: U128,
     pub unclaimed_reward: U128,
     pub beneficiary_reward: U128,
+
+    pub undistributed_reward: U128,
+    pub remaining_rounds: u32,
+    pub estimated_end_at: Option<u32>,
+    pub seconds_to_next_round: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
@@ -57,6 +62,111 @@ pub struct LockedSeed {
     pub ended_at: u32 
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageSponsorshipInfo {
+    pub balance: U128,
+    pub cap: U128,
+    pub sponsored: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FarmCheckpointInfo {
+    pub round: u32,
+    pub timestamp: u32,
+    pub rps: String,
+    pub total_seeds: U128,
+}
+
+/// prices of a whole token in any common quote unit, used to estimate apr
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AprPrices {
+    pub reward_token_price: U128,
+    pub reward_token_decimals: u8,
+    pub seed_price: U128,
+    pub seed_decimals: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FarmEmissionInfo {
+    pub farm_id: FarmId,
+    pub total_seeds: U128,
+    pub emission_per_second: U128,
+    pub emission_per_day: U128,
+    pub emission_per_year: U128,
+    /// reward per staked seed per day, multiplied by 10**24
+    pub reward_per_seed_per_day: String,
+    pub estimated_end_at: Option<u32>,
+    /// in basis points, only when prices are given
+    pub apr: Option<U128>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FarmRewardOverview {
+    pub farm_id: FarmId,
+    pub reward_token: AccountId,
+    pub unclaimed_reward: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LockedSeedOverview {
+    pub balance: U128,
+    pub started_at: u32,
+    pub ended_at: u32,
+    /// seconds left before the seed can be unlocked, 0 if ended
+    pub remaining_sec: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakedNftOverview {
+    pub contract_nft_token_id: String,
+    pub balance_equivalent: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeedOverview {
+    pub seed_id: SeedId,
+    pub amount: U128,
+    pub farms: Vec<FarmRewardOverview>,
+    pub locked_seed: Option<LockedSeedOverview>,
+    pub nfts: Vec<StakedNftOverview>,
+}
+
+/// everything a farmer has in this contract, in one view
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FarmerOverview {
+    pub farmer_id: AccountId,
+    pub seeds: Vec<SeedOverview>,
+    /// rewards claimed and ready to withdraw
+    pub rewards: HashMap<AccountId, U128>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeedStaker {
+    pub account_id: AccountId,
+    pub amount: U128,
+}
+
+/// staked position of a farmer, unclaimed rewards are keyed by farm
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FarmerInfo {
+    pub farmer_id: AccountId,
+    pub seeds: HashMap<SeedId, U128>,
+    pub locked_seeds: HashMap<SeedId, LockedSeed>,
+    pub rewards: HashMap<AccountId, U128>,
+    pub unclaimed_rewards: HashMap<FarmId, U128>,
+}
+
 impl From<&Farm> for FarmInfo {
     fn from(farm: &Farm) -> Self {
         let farm_kind = farm.kind();
@@ -85,6 +195,11 @@ impl From<&Farm> for FarmInfo {
                         claimed_reward: farm.amount_of_claimed.into(),
                         unclaimed_reward: dis.unclaimed.into(),
                         beneficiary_reward: farm.amount_of_beneficiary.into(),
+
+                        undistributed_reward: dis.undistributed.into(),
+                        remaining_rounds: farm.remaining_rounds(&dis),
+                        estimated_end_at: farm.estimated_end_at(&DENOM),
+                        seconds_to_next_round: farm.seconds_to_next_round(&DENOM),
                     }
                 } else {
                     Self {
@@ -104,6 +219,11 @@ impl From<&Farm> for FarmInfo {
                         // unclaimed_reward: (farm.amount_of_reward - farm.amount_of_claimed).into(),
                         unclaimed_reward: farm.last_distribution.unclaimed.into(),
                         beneficiary_reward: farm.amount_of_beneficiary.into(),
+
+                        undistributed_reward: farm.last_distribution.undistributed.into(),
+                        remaining_rounds: farm.remaining_rounds(&farm.last_distribution),
+                        estimated_end_at: farm.estimated_end_at(&DENOM),
+                        seconds_to_next_round: farm.seconds_to_next_round(&DENOM),
                     }
                 }                
             }
@@ -182,6 +302,53 @@ impl Contract {
         }
     }
 
+    /// Returns reward emission of the farm at current total seeds staked,
+    /// with apr estimated from given prices.
+    pub fn get_farm_emission(&self, farm_id: FarmId, prices: Option<AprPrices>) -> Option<FarmEmissionInfo> {
+        let farm = self.data().farms.get(&farm_id)?;
+        let total_seeds = self.get_seed_wrapped(&farm.get_seed_id())
+            .map(|farm_seed| farm_seed.get_ref().amount)
+            .unwrap_or(0);
+        let emission = farm.emission(&total_seeds);
+        let apr = prices.and_then(|prices| emission.apr(
+            &total_seeds,
+            prices.reward_token_price.into(),
+            prices.reward_token_decimals,
+            prices.seed_price.into(),
+            prices.seed_decimals,
+        ));
+        Some(FarmEmissionInfo {
+            farm_id,
+            total_seeds: total_seeds.into(),
+            emission_per_second: emission.per_second.into(),
+            emission_per_day: emission.per_day.into(),
+            emission_per_year: emission.per_year.into(),
+            reward_per_seed_per_day: format!("{}", emission.per_seed_per_day),
+            estimated_end_at: emission.end_at,
+            apr: apr.map(|apr| apr.into()),
+        })
+    }
+
+    /// Returns checkpoints of the farm of given length from given start index,
+    /// oldest first.
+    pub fn list_farm_checkpoints(&self, farm_id: FarmId, from_index: u64, limit: u64) -> Vec<FarmCheckpointInfo> {
+        if let Some(checkpoints) = self.data().farm_checkpoints.get(&farm_id) {
+            (from_index..std::cmp::min(from_index + limit, checkpoints.len()))
+                .map(|index| {
+                    let checkpoint = checkpoints.get(index).unwrap();
+                    FarmCheckpointInfo {
+                        round: checkpoint.round,
+                        timestamp: checkpoint.timestamp,
+                        rps: format!("{}", U256::from_little_endian(&checkpoint.rps)),
+                        total_seeds: checkpoint.total_seeds.into(),
+                    }
+                })
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
     pub fn list_rewards_info(&self, from_index: u64, limit: u64) -> HashMap<AccountId, U128> {
         let keys = self.data().reward_info.keys_as_vector();
         (from_index..std::cmp::min(from_index + limit, keys.len()))
@@ -209,6 +376,26 @@ impl Contract {
             .collect()
     }
 
+    /// Returns lifetime amount of each reward token claimed by given user.
+    pub fn list_user_claimed_rewards(&self, account_id: ValidAccountId) -> HashMap<AccountId, U128> {
+        self.get_farmer_default(account_id.as_ref())
+            .get()
+            .claimed_rewards
+            .into_iter()
+            .map(|(acc, bal)| (acc, U128(bal)))
+            .collect()
+    }
+
+    /// Returns lifetime amount of reward claimed by given user from each farm he still has rps in.
+    pub fn list_user_farm_claimed_rewards(&self, account_id: ValidAccountId) -> HashMap<FarmId, U128> {
+        self.get_farmer_default(account_id.as_ref())
+            .get()
+            .farm_claimed_rewards
+            .into_iter()
+            .map(|(farm_id, bal)| (farm_id, U128(bal)))
+            .collect()
+    }
+
     /// Returns balance of amount of given reward token that ready to withdraw.
     pub fn get_reward(&self, account_id: ValidAccountId, token_id: ValidAccountId) -> U128 {
         self.internal_get_reward(account_id.as_ref(), token_id.as_ref())
@@ -216,22 +403,8 @@ impl Contract {
     }
 
     pub fn get_unclaimed_reward(&self, account_id: ValidAccountId, farm_id: FarmId) -> U128 {
-        let (seed_id, _) = parse_farm_id(&farm_id);
-
-        if let (Some(farmer), Some(farm_seed)) = (
-            self.get_farmer_wrapped(account_id.as_ref()),
-            self.get_seed_wrapped(&seed_id),
-        ) {
-            if let Some(farm) = self.data().farms.get(&farm_id) {
-                let reward_amount = farm.view_farmer_unclaimed_reward(
-                    &farmer.get_ref().get_rps(&farm.get_farm_id()),
-                    farmer.get_ref().seeds.get(&seed_id).unwrap_or(&0_u128),
-                    &farm_seed.get_ref().amount,
-                );
-                reward_amount.into()
-            } else {
-                0.into()
-            }
+        if let Some(farmer) = self.get_farmer_wrapped(account_id.as_ref()) {
+            self.internal_get_unclaimed_reward(farmer.get_ref(), &farm_id).into()
         } else {
             0.into()
         }
@@ -269,24 +442,7 @@ impl Contract {
 
     pub fn list_user_locked_seeds(&self, account_id: ValidAccountId) -> HashMap<SeedId, LockedSeed> {
         if let Some(farmer) = self.get_farmer_wrapped(account_id.as_ref()) {
-            farmer
-                .get()
-                .locked_seeds
-                .into_iter()
-                .filter(|(seed, _)| {
-                    let farmer = self.get_farmer(&account_id.as_ref());
-                    farmer.get_ref().get_locked_seed_with_retention_wrapped(&seed).is_some()
-                })
-                .map(|(seed, _)| {
-                    let farmer = self.get_farmer(&account_id.as_ref());
-                    let locked_seed = farmer.get_ref().get_locked_seed_with_retention_wrapped(&seed).unwrap();
-                    (seed, LockedSeed{
-                        balance: locked_seed.balance.into(),
-                        started_at: locked_seed.started_at,
-                        ended_at: locked_seed.ended_at
-                    })
-                })
-                .collect()
+            Self::internal_get_locked_seeds(farmer.get_ref())
         } else {
             HashMap::new()
         }
@@ -310,6 +466,7 @@ impl Contract {
             let mut seed_info: SeedInfo = farm_seed.get_ref().into();
             let nft_balance_seed = self.data().nft_balance_seeds.get(&seed_id);
             seed_info.nft_balance = nft_balance_seed;
+            seed_info.staker_count = self.get_seed_staker_count(seed_id).into();
             Some(seed_info)
         } else {
             None
@@ -324,6 +481,7 @@ impl Contract {
                 let nft_balance_seed = self.data().nft_balance_seeds.get(&seed_id);
                 let mut seed: SeedInfo = self.get_seed(&seed_id).get_ref().into();
                 seed.nft_balance = nft_balance_seed;
+                seed.staker_count = self.get_seed_staker_count(seed_id.clone()).into();
                 (
                     seed_id,
                     seed,
@@ -341,6 +499,180 @@ impl Contract {
         }
     }
 
+    pub fn is_nft_approval_staking(&self, seed_id: SeedId) -> bool {
+        self.data().nft_approval_stake_seeds.contains(&seed_id)
+    }
+
+    /// nfts of the user staked by approval in the seed, they stay in user wallet
+    pub fn list_user_approval_staked_nfts(&self, account_id: ValidAccountId, seed_id: SeedId) -> Vec<String> {
+        self.internal_get_approval_staked_nfts(account_id.as_ref(), &seed_id)
+    }
+
+    /// reward of each farm held for the approval staked nft until its ownership is verified
+    pub fn get_approval_stake_held_rewards(&self, contract_nft_token_id: String) -> HashMap<FarmId, U128> {
+        self.data().nft_approval_held_rewards.get(&contract_nft_token_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(farm_id, amount)| (farm_id, U128(amount)))
+            .collect()
+    }
+
+    /// Returns list of registered farmers of given length from given start index,
+    /// with their staked seeds, locked seeds and pending rewards.
+    pub fn list_farmers(&self, from_index: u64, limit: u64) -> Vec<FarmerInfo> {
+        let keys = self.data().farmer_ids.as_vector();
+
+        (from_index..std::cmp::min(from_index + limit, keys.len()))
+            .map(|index| {
+                let farmer = self.get_farmer(&keys.get(index).unwrap());
+                let farmer = farmer.get_ref();
+                let mut unclaimed_rewards: HashMap<FarmId, U128> = HashMap::new();
+                for seed_id in farmer.seeds.keys() {
+                    if let Some(farm_seed) = self.get_seed_wrapped(seed_id) {
+                        for farm_id in farm_seed.get_ref().farms.iter() {
+                            unclaimed_rewards.insert(
+                                farm_id.clone(),
+                                self.internal_get_unclaimed_reward(farmer, farm_id).into()
+                            );
+                        }
+                    }
+                }
+                FarmerInfo {
+                    farmer_id: farmer.farmer_id.clone(),
+                    seeds: farmer.seeds.iter().map(|(seed_id, bal)| (seed_id.clone(), U128(*bal))).collect(),
+                    locked_seeds: Self::internal_get_locked_seeds(farmer),
+                    rewards: farmer.rewards.iter().map(|(token_id, bal)| (token_id.clone(), U128(*bal))).collect(),
+                    unclaimed_rewards,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns staked seeds of the farmer with their farms and unclaimed rewards,
+    /// locked seeds and staked nfts, together with rewards ready to withdraw.
+    pub fn get_farmer_overview(&self, account_id: ValidAccountId) -> Option<FarmerOverview> {
+        let farmer = self.get_farmer_wrapped(account_id.as_ref())?;
+        let farmer = farmer.get_ref();
+        let current_block_time = to_sec(env::block_timestamp());
+
+        let mut seed_ids: Vec<SeedId> = farmer.seeds.keys().cloned().collect();
+        for seed_id in farmer.nft_seeds.keys() {
+            if !farmer.seeds.contains_key(seed_id) {
+                seed_ids.push(seed_id.clone());
+            }
+        }
+
+        let seeds = seed_ids
+            .into_iter()
+            .map(|seed_id| {
+                let farms = self.get_seed_wrapped(&seed_id)
+                    .map(|farm_seed| farm_seed.get_ref().farms.iter()
+                        .filter_map(|farm_id| self.data().farms.get(farm_id).map(|farm| FarmRewardOverview {
+                            farm_id: farm_id.clone(),
+                            reward_token: farm.get_reward_token(),
+                            unclaimed_reward: self.internal_get_unclaimed_reward(farmer, farm_id).into(),
+                        }))
+                        .collect()
+                    )
+                    .unwrap_or_default();
+                let locked_seed = farmer.get_locked_seed_with_retention_wrapped(&seed_id)
+                    .map(|locked_seed| LockedSeedOverview {
+                        balance: locked_seed.balance.into(),
+                        started_at: locked_seed.started_at,
+                        ended_at: locked_seed.ended_at,
+                        remaining_sec: locked_seed.ended_at.saturating_sub(current_block_time),
+                    });
+                let nfts = farmer.nft_seeds.get(&seed_id)
+                    .map(|nft_ids| nft_ids.iter()
+                        .map(|contract_nft_token_id| StakedNftOverview {
+                            balance_equivalent: self.internal_get_nft_balance_equivalent(&seed_id, &contract_nft_token_id)
+                                .unwrap_or(0)
+                                .into(),
+                            contract_nft_token_id,
+                        })
+                        .collect()
+                    )
+                    .unwrap_or_default();
+                SeedOverview {
+                    amount: farmer.get_balance(&seed_id).into(),
+                    seed_id,
+                    farms,
+                    locked_seed,
+                    nfts,
+                }
+            })
+            .collect();
+
+        Some(FarmerOverview {
+            farmer_id: farmer.farmer_id.clone(),
+            seeds,
+            rewards: farmer.rewards.iter().map(|(token_id, bal)| (token_id.clone(), U128(*bal))).collect(),
+        })
+    }
+
+    /// Returns number of unique accounts staking the seed.
+    pub fn get_seed_staker_count(&self, seed_id: SeedId) -> u64 {
+        self.data().seed_stakers.get(&seed_id).map(|stakers| stakers.len()).unwrap_or(0)
+    }
+
+    /// Returns stakers of the seed with their balances of given length from given start index,
+    /// sort them by amount to get the top stakers.
+    pub fn list_seed_stakers(&self, seed_id: SeedId, from_index: u64, limit: u64) -> Vec<SeedStaker> {
+        if let Some(stakers) = self.data().seed_stakers.get(&seed_id) {
+            let keys = stakers.as_vector();
+            (from_index..std::cmp::min(from_index + limit, keys.len()))
+                .map(|index| {
+                    let account_id = keys.get(index).unwrap();
+                    let amount = self.get_farmer(&account_id).get_ref().get_balance(&seed_id);
+                    SeedStaker {
+                        account_id,
+                        amount: amount.into(),
+                    }
+                })
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
+    pub fn get_storage_sponsorship(&self) -> StorageSponsorshipInfo {
+        let storage_sponsorship = &self.data().storage_sponsorship;
+        StorageSponsorshipInfo {
+            balance: storage_sponsorship.balance.into(),
+            cap: storage_sponsorship.cap.into(),
+            sponsored: storage_sponsorship.sponsored.into(),
+        }
+    }
+
+    /// Returns account controlling withdrawals of the seed position of the farmer, if staked on his behalf.
+    pub fn get_staking_manager(&self, account_id: ValidAccountId, seed_id: SeedId) -> Option<AccountId> {
+        self.data().staking_managers.get(&(account_id.into(), seed_id))
+    }
+
+    pub fn is_auto_compound(&self, account_id: ValidAccountId, seed_id: SeedId) -> bool {
+        self.data().auto_compound_farmers.get(&seed_id)
+            .map(|farmers| farmers.contains(account_id.as_ref()))
+            .unwrap_or(false)
+    }
+
+    /// Returns farmers opted in for auto compound of the seed, to page through `auto_compound`.
+    pub fn get_auto_compound_farmer_count(&self, seed_id: SeedId) -> u64 {
+        self.data().auto_compound_farmers.get(&seed_id).map(|farmers| farmers.len()).unwrap_or(0)
+    }
+
+    pub fn is_seed_auto_register(&self, seed_id: SeedId) -> bool {
+        self.data().auto_register_seeds.contains(&seed_id)
+    }
+
+    /// Returns storage deposit of the farmer covered by the sponsorship pool.
+    pub fn get_sponsored_storage(&self, account_id: ValidAccountId) -> U128 {
+        self.internal_sponsored_storage(account_id.as_ref()).into()
+    }
+
+    pub fn get_nft_trait_rules(&self, seed_id: SeedId) -> Vec<NftTraitRule> {
+        self.data().nft_trait_rules.get(&seed_id).unwrap_or_default()
+    }
+
     pub fn get_nft_balance_equivalent(&self, seed_id: SeedId, nft_token_id: String) -> Option<U128> {
         let nft_balance = self.data().nft_balance_seeds.get(&seed_id).unwrap();
         let result: Option<U128>;
@@ -372,3 +704,40 @@ impl Contract {
 
 
 }
+
+impl Contract {
+
+    /// unclaimed reward of the farmer in the farm, 0 if farm or seed not exist
+    pub(crate) fn internal_get_unclaimed_reward(&self, farmer: &Farmer, farm_id: &FarmId) -> Balance {
+        let (seed_id, _) = parse_farm_id(farm_id);
+        if let (Some(farm_seed), Some(farm)) = (
+            self.get_seed_wrapped(&seed_id),
+            self.data().farms.get(farm_id),
+        ) {
+            farm.view_farmer_unclaimed_reward(
+                &farmer.get_rps(&farm.get_farm_id()),
+                farmer.seeds.get(&seed_id).unwrap_or(&0_u128),
+                &farm_seed.get_ref().amount,
+            )
+        } else {
+            0
+        }
+    }
+
+    /// locked seeds of the farmer still in their retention period
+    pub(crate) fn internal_get_locked_seeds(farmer: &Farmer) -> HashMap<SeedId, LockedSeed> {
+        farmer
+            .locked_seeds
+            .keys()
+            .filter_map(|seed| {
+                farmer.get_locked_seed_with_retention_wrapped(seed).map(|locked_seed| {
+                    (seed.clone(), LockedSeed{
+                        balance: locked_seed.balance.into(),
+                        started_at: locked_seed.started_at,
+                        ended_at: locked_seed.ended_at
+                    })
+                })
+            })
+            .collect()
+    }
+}
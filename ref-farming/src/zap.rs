@@ -0,0 +1,625 @@
+//! Zap of claimed rewards into a seed of ref exchange pool shares.
+//! The reward is deposited into the ref exchange account of this contract, part of it is swapped
+//! into the other token of the pool, and both are added as liquidity. The minted shares are
+//! held by this contract like any staked mft seed, so they are credited to the seed of the farmer.
+//! One zap at a time runs through an exchange, so whatever is left in the deposits of this contract
+//! there once the zap is done, beside the zap refunds, is withdrawn back to the farmer rewards.
+//! Withdrawn tokens are credited once the deposits confirm they left the exchange,
+//! tokens that fail to be withdrawn are kept as zap refunds of the farmer, to be withdrawn again.
+//! A zap that can not read the exchange stalls with the exchange locked, and is resumed by anyone.
+use std::collections::HashMap;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{ValidAccountId, U128};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::PromiseResult;
+
+use crate::errors::*;
+use crate::event::{NearEvent, ZapData};
+use crate::farm_seed::SeedType;
+use crate::utils::{
+    assert_one_yocto, ext_fungible_token, ext_ref_exchange, ext_self, parse_farm_id, parse_seed_id, wrap_mft_token_id,
+    SwapAction, GAS_FOR_RESOLVE_ZAP_ADD_LIQUIDITY, GAS_FOR_RESOLVE_ZAP_CONFIRM, GAS_FOR_RESOLVE_ZAP_DEPOSIT,
+    GAS_FOR_RESOLVE_ZAP_REMOVE_LIQUIDITY, GAS_FOR_RESOLVE_ZAP_SHARES, GAS_FOR_RESOLVE_ZAP_SHARES_BEFORE,
+    GAS_FOR_RESOLVE_ZAP_SWAP, GAS_FOR_RESOLVE_ZAP_SWEEP, GAS_FOR_RESOLVE_ZAP_WITHDRAW, GAS_FOR_ZAP_ADD_LIQUIDITY,
+    GAS_FOR_ZAP_DEPOSIT, GAS_FOR_ZAP_GET_DEPOSITS, GAS_FOR_ZAP_REMOVE_LIQUIDITY, GAS_FOR_ZAP_SHARE_BALANCE,
+    GAS_FOR_ZAP_SWAP, GAS_FOR_ZAP_WITHDRAW,
+};
+use crate::*;
+
+/// what to zap the claimed reward into, carried through the zap callbacks
+#[derive(Serialize, Deserialize, BorshSerialize, BorshDeserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ZapArgs {
+    /// seed of the pool shares `<exchange>@<pool_id>`
+    pub target_seed_id: SeedId,
+    pub reward_token: AccountId,
+    /// the two tokens of the pool in pool order
+    pub pool_token_ids: Vec<AccountId>,
+    /// part of the reward swapped into the other pool token
+    pub swap_amount: U128,
+    pub min_amount_out: U128,
+    /// fewer minted shares are removed from the pool and withdrawn back
+    pub min_shares: U128,
+}
+
+/// zap running through an exchange, the deposits of this contract there belong to it
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ZapState {
+    pub sender_id: AccountId,
+    /// tokens the zap may leave in the exchange deposits
+    pub token_ids: Vec<AccountId>,
+    /// step that could not read the exchange, set until the zap is resumed
+    pub stalled: Option<ZapStall>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub enum ZapStall {
+    /// add_liquidity succeeded without readable shares, minted shares are read from the share balance
+    Shares { args: ZapArgs, amount: U128, shares_before: U128 },
+    /// leftovers are read from the exchange deposits
+    Sweep,
+    /// withdrawn tokens are credited once the exchange deposits confirm they left
+    Withdraw { token_ids: Vec<AccountId>, amounts: Vec<U128> },
+}
+
+#[near_bindgen]
+impl Contract {
+    /// harvest all farms of the given seeds and the given farms, then zap the whole claimed reward
+    /// of reward_token into the pool of the target seed, with both pool tokens registered for zaps.
+    #[payable]
+    pub fn claim_and_zap(&mut self, seed_ids: Vec<SeedId>, farm_ids: Vec<FarmId>, args: ZapArgs) {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        let (exchange_id, _) = parse_seed_id(&args.target_seed_id);
+        assert_eq!(self.get_seed(&args.target_seed_id).get_ref().seed_type, SeedType::MFT, "{}", ERR77_ZAP_TARGET_NOT_MFT_SEED);
+        assert!(
+            args.pool_token_ids.len() == 2
+                && args.pool_token_ids[0] != args.pool_token_ids[1]
+                && args.pool_token_ids.contains(&args.reward_token),
+            "{}",
+            ERR78_INVALID_ZAP_POOL_TOKENS
+        );
+        for token_id in args.pool_token_ids.iter() {
+            assert!(
+                self.data().zap_tokens.contains(&(exchange_id.clone(), token_id.clone())),
+                "{}",
+                ERR83_ZAP_TOKEN_NOT_REGISTERED
+            );
+        }
+        assert!(self.data().zapping_exchanges.get(&exchange_id).is_none(), "{}", ERR84_ZAP_IN_PROGRESS);
+        self.internal_assert_not_managed(&sender_id, &args.target_seed_id);
+
+        for seed_id in seed_ids.iter() {
+            assert!(self.internal_get_approval_staked_nfts(&sender_id, seed_id).is_empty(), "{}", ERR56_APPROVAL_STAKED_SEED_CLAIM);
+            self.internal_claim_user_reward_by_seed_id(&sender_id, seed_id);
+        }
+        for farm_id in farm_ids.iter() {
+            let (seed_id, _) = parse_farm_id(farm_id);
+            assert!(self.internal_get_approval_staked_nfts(&sender_id, &seed_id).is_empty(), "{}", ERR56_APPROVAL_STAKED_SEED_CLAIM);
+            self.internal_claim_user_reward_by_farm_id(&sender_id, farm_id);
+        }
+
+        let storage_snapshot = self.internal_farmer_storage_snapshot(&sender_id);
+        let mut farmer = self.get_farmer(&sender_id);
+        let amount = farmer.get_ref().rewards.get(&args.reward_token).cloned().unwrap_or(0);
+        assert!(amount > 0, "{}", ERR79_NOTHING_TO_ZAP);
+        assert!(args.swap_amount.0 > 0 && args.swap_amount.0 < amount, "{}", ERR80_INVALID_ZAP_SWAP_AMOUNT);
+        // Note: subtraction, will be reverted if any step of the zap fails.
+        farmer.get_ref_mut().sub_reward(&args.reward_token, amount);
+        self.data_mut().farmers.insert(&sender_id, &farmer);
+        self.internal_update_farmer_storage(&sender_id, storage_snapshot);
+        self.assert_storage_usage(&sender_id);
+
+        self.internal_zap_lock(&sender_id, &exchange_id, args.pool_token_ids.clone());
+        ext_fungible_token::ft_transfer_call(
+            exchange_id,
+            amount.into(),
+            None,
+            String::new(),
+            &args.reward_token,
+            1,
+            GAS_FOR_ZAP_DEPOSIT,
+        )
+        .then(ext_self::callback_post_zap_deposit(
+            sender_id,
+            args,
+            amount.into(),
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_ZAP_DEPOSIT,
+        ));
+    }
+
+    /// withdraw again tokens of the predecessor that failed to be withdrawn from the exchange after a zap.
+    #[payable]
+    pub fn withdraw_zap_refund(&mut self, exchange_id: ValidAccountId, token_id: ValidAccountId) {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        let (exchange_id, token_id): (AccountId, AccountId) = (exchange_id.into(), token_id.into());
+        assert!(self.data().zapping_exchanges.get(&exchange_id).is_none(), "{}", ERR84_ZAP_IN_PROGRESS);
+        let amount = self.internal_take_zap_refund(&sender_id, &exchange_id, &token_id);
+        self.internal_zap_lock(&sender_id, &exchange_id, vec![token_id.clone()]);
+        self.internal_zap_withdraw(&sender_id, &exchange_id, vec![(token_id, amount)]);
+    }
+
+    /// run again the step of a zap that could not read the exchange, for the farmer of the zap.
+    pub fn resume_zap(&mut self, exchange_id: ValidAccountId) {
+        let exchange_id: AccountId = exchange_id.into();
+        let mut state = self.data().zapping_exchanges.get(&exchange_id).expect(ERR86_NO_ZAP_IN_PROGRESS);
+        let stall = state.stalled.take().expect(ERR87_ZAP_NOT_STALLED);
+        self.data_mut().zapping_exchanges.insert(&exchange_id, &state);
+        match stall {
+            ZapStall::Shares { args, amount, shares_before } => {
+                self.internal_zap_read_shares(&state.sender_id, args, amount, shares_before);
+            }
+            ZapStall::Sweep => {
+                self.internal_zap_sweep_tokens(&state.sender_id, &exchange_id, state.token_ids);
+            }
+            ZapStall::Withdraw { token_ids, amounts } => {
+                self.internal_zap_confirm(&state.sender_id, &exchange_id, token_ids, amounts);
+            }
+        }
+    }
+
+    pub fn get_zap_refund(&self, account_id: ValidAccountId, exchange_id: ValidAccountId, token_id: ValidAccountId) -> U128 {
+        self.data().zap_refunds.get(&(account_id.into(), exchange_id.into(), token_id.into())).unwrap_or(0).into()
+    }
+
+    /// swap part of the reward deposited in the exchange,
+    /// the part refunded by the reward token goes back to the farmer rewards.
+    #[private]
+    pub fn callback_post_zap_deposit(&mut self, sender_id: AccountId, args: ZapArgs, amount: U128) {
+        let used = match internal_zap_promise_result() {
+            Some(value) => near_sdk::serde_json::from_slice::<U128>(&value).map(|used| used.0).unwrap_or(0),
+            None => 0,
+        };
+        if used < amount.0 {
+            env::log(format!("{} zap deposit {} amount {}, used {}, Callback Failed.", sender_id, args.reward_token, amount.0, used).as_bytes());
+            self.internal_zap_credit_reward(&sender_id, &args.reward_token, amount.0 - used);
+            if used > 0 {
+                self.internal_zap_sweep(&sender_id, &args);
+            } else {
+                self.internal_zap_release(&args.target_seed_id);
+            }
+            return;
+        }
+
+        let (exchange_id, pool_id) = parse_seed_id(&args.target_seed_id);
+        let token_out = args.pool_token_ids.iter().find(|token_id| **token_id != args.reward_token).unwrap().clone();
+        ext_ref_exchange::swap(
+            vec![SwapAction {
+                pool_id: pool_id.parse().expect(ERR33_INVALID_SEED_ID),
+                token_in: args.reward_token.clone(),
+                amount_in: Some(args.swap_amount),
+                token_out,
+                min_amount_out: args.min_amount_out,
+            }],
+            None,
+            &exchange_id,
+            1,
+            GAS_FOR_ZAP_SWAP,
+        )
+        .then(ext_self::callback_post_zap_swap(
+            sender_id,
+            args,
+            amount,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_ZAP_SWAP,
+        ));
+    }
+
+    /// read the share balance of this contract before adding liquidity,
+    /// a failed swap withdraws the reward back.
+    #[private]
+    pub fn callback_post_zap_swap(&mut self, sender_id: AccountId, args: ZapArgs, amount: U128) {
+        let amount_out = internal_zap_promise_result()
+            .and_then(|value| near_sdk::serde_json::from_slice::<U128>(&value).ok());
+        let amount_out = match amount_out {
+            Some(amount_out) if amount_out.0 > 0 => amount_out,
+            _ => {
+                env::log(format!("{} zap swap {} amount {}, Callback Failed.", sender_id, args.reward_token, args.swap_amount.0).as_bytes());
+                self.internal_zap_sweep(&sender_id, &args);
+                return;
+            }
+        };
+
+        let (exchange_id, pool_id) = parse_seed_id(&args.target_seed_id);
+        ext_ref_exchange::mft_balance_of(
+            wrap_mft_token_id(&pool_id),
+            env::current_account_id(),
+            &exchange_id,
+            0,
+            GAS_FOR_ZAP_SHARE_BALANCE,
+        )
+        .then(ext_self::callback_post_zap_shares_before(
+            sender_id,
+            args,
+            amount,
+            amount_out,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_ZAP_SHARES_BEFORE,
+        ));
+    }
+
+    /// add the rest of the reward and the swapped token as liquidity,
+    /// the zap is swept back if the share balance can not be read.
+    #[private]
+    pub fn callback_post_zap_shares_before(&mut self, sender_id: AccountId, args: ZapArgs, amount: U128, amount_out: U128) {
+        let shares_before = match internal_zap_promise_result()
+            .and_then(|value| near_sdk::serde_json::from_slice::<U128>(&value).ok())
+        {
+            Some(shares_before) => shares_before,
+            None => {
+                env::log(format!("{} zap shares of {}, Callback Failed.", sender_id, args.target_seed_id).as_bytes());
+                self.internal_zap_sweep(&sender_id, &args);
+                return;
+            }
+        };
+
+        let amounts: Vec<U128> = args.pool_token_ids
+            .iter()
+            .map(|token_id| if *token_id == args.reward_token { U128(amount.0 - args.swap_amount.0) } else { amount_out })
+            .collect();
+        let (exchange_id, pool_id) = parse_seed_id(&args.target_seed_id);
+        ext_ref_exchange::add_liquidity(
+            pool_id.parse().expect(ERR33_INVALID_SEED_ID),
+            amounts,
+            &exchange_id,
+            1,
+            GAS_FOR_ZAP_ADD_LIQUIDITY,
+        )
+        .then(ext_self::callback_post_zap_add_liquidity(
+            sender_id,
+            args,
+            amount,
+            shares_before,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_ZAP_ADD_LIQUIDITY,
+        ));
+    }
+
+    /// settle the minted shares, read from the share balance if add_liquidity returned none,
+    /// a failed add_liquidity withdraws the pool tokens back.
+    #[private]
+    pub fn callback_post_zap_add_liquidity(&mut self, sender_id: AccountId, args: ZapArgs, amount: U128, shares_before: U128) {
+        match internal_zap_promise_result() {
+            None => {
+                env::log(format!("{} zap add liquidity to {}, Callback Failed.", sender_id, args.target_seed_id).as_bytes());
+                self.internal_zap_sweep(&sender_id, &args);
+            }
+            Some(value) => match near_sdk::serde_json::from_slice::<U128>(&value) {
+                Ok(shares) if shares.0 > 0 => self.internal_zap_shares(&sender_id, args, amount, shares),
+                _ => self.internal_zap_read_shares(&sender_id, args, amount, shares_before),
+            },
+        }
+    }
+
+    /// settle the shares minted since the balance read before add_liquidity,
+    /// the zap stalls if the balance can not be read.
+    #[private]
+    pub fn callback_post_zap_shares_after(&mut self, sender_id: AccountId, args: ZapArgs, amount: U128, shares_before: U128) {
+        let shares_after = match internal_zap_promise_result()
+            .and_then(|value| near_sdk::serde_json::from_slice::<U128>(&value).ok())
+        {
+            Some(shares_after) => shares_after,
+            None => {
+                env::log(format!("{} zap shares of {}, Callback Failed.", sender_id, args.target_seed_id).as_bytes());
+                let (exchange_id, _) = parse_seed_id(&args.target_seed_id);
+                self.internal_zap_stall(&exchange_id, ZapStall::Shares { args, amount, shares_before });
+                return;
+            }
+        };
+        let shares = shares_after.0.saturating_sub(shares_before.0);
+        if shares > 0 {
+            self.internal_zap_shares(&sender_id, args, amount, shares.into());
+        } else {
+            self.internal_zap_sweep(&sender_id, &args);
+        }
+    }
+
+    /// withdraw the pool tokens of the removed shares back to the farmer rewards,
+    /// shares that could not be removed are staked into the seed of the farmer.
+    #[private]
+    pub fn callback_post_zap_remove_liquidity(&mut self, sender_id: AccountId, args: ZapArgs, shares: U128) {
+        if internal_zap_promise_result().is_none() {
+            env::log(format!("{} zap remove {} shares of {}, Callback Failed.", sender_id, shares.0, args.target_seed_id).as_bytes());
+            self.internal_seed_credit(&args.target_seed_id, &sender_id, shares.0);
+        }
+        self.internal_zap_sweep(&sender_id, &args);
+    }
+
+    /// withdraw the tokens left in the exchange deposits of this contract, beside the zap refunds.
+    /// the zap stalls if the deposits can not be read.
+    #[private]
+    pub fn callback_post_zap_sweep(&mut self, sender_id: AccountId, exchange_id: AccountId, token_ids: Vec<AccountId>) {
+        let deposits = match internal_zap_deposits() {
+            Some(deposits) => deposits,
+            None => {
+                env::log(format!("{} zap get deposits of {}, Callback Failed.", sender_id, exchange_id).as_bytes());
+                self.internal_zap_stall(&exchange_id, ZapStall::Sweep);
+                return;
+            }
+        };
+        let tokens: Vec<(AccountId, Balance)> = token_ids
+            .into_iter()
+            .map(|token_id| {
+                let leftover = self.internal_zap_unrefunded_deposit(&deposits, &exchange_id, &token_id);
+                (token_id, leftover)
+            })
+            .collect();
+        self.internal_zap_withdraw(&sender_id, &exchange_id, tokens);
+    }
+
+    /// confirm withdraws the exchange reported successful against its deposits,
+    /// a failed withdraw is kept as zap refund of the farmer.
+    #[private]
+    pub fn callback_post_zap_withdraw(
+        &mut self,
+        sender_id: AccountId,
+        exchange_id: AccountId,
+        token_ids: Vec<AccountId>,
+        amounts: Vec<U128>,
+    ) {
+        assert_eq!(env::promise_results_count(), token_ids.len() as u64, "{}", ERR81_CALLBACK_POST_ZAP_INVALID);
+        let mut withdrawn_token_ids = vec![];
+        let mut withdrawn_amounts = vec![];
+        for (index, (token_id, amount)) in token_ids.into_iter().zip(amounts).enumerate() {
+            match env::promise_result(index as u64) {
+                PromiseResult::NotReady => unreachable!(),
+                PromiseResult::Successful(_) => {
+                    withdrawn_token_ids.push(token_id);
+                    withdrawn_amounts.push(amount);
+                }
+                PromiseResult::Failed => {
+                    env::log(format!("{} zap withdraw {} amount {}, Callback Failed.", sender_id, token_id, amount.0).as_bytes());
+                    self.internal_add_zap_refund(&sender_id, &exchange_id, &token_id, amount.0);
+                }
+            }
+        }
+        if withdrawn_token_ids.is_empty() {
+            self.data_mut().zapping_exchanges.remove(&exchange_id);
+        } else {
+            self.internal_zap_confirm(&sender_id, &exchange_id, withdrawn_token_ids, withdrawn_amounts);
+        }
+    }
+
+    /// credit withdrawn tokens that left the exchange deposits to the farmer rewards,
+    /// the ones the exchange put back in the deposits are kept as zap refunds, which ends the zap.
+    /// the zap stalls if the deposits can not be read.
+    #[private]
+    pub fn callback_post_zap_confirm(
+        &mut self,
+        sender_id: AccountId,
+        exchange_id: AccountId,
+        token_ids: Vec<AccountId>,
+        amounts: Vec<U128>,
+    ) {
+        let deposits = match internal_zap_deposits() {
+            Some(deposits) => deposits,
+            None => {
+                env::log(format!("{} zap get deposits of {}, Callback Failed.", sender_id, exchange_id).as_bytes());
+                self.internal_zap_stall(&exchange_id, ZapStall::Withdraw { token_ids, amounts });
+                return;
+            }
+        };
+        for (token_id, amount) in token_ids.iter().zip(amounts.iter()) {
+            let returned = std::cmp::min(amount.0, self.internal_zap_unrefunded_deposit(&deposits, &exchange_id, token_id));
+            if returned > 0 {
+                env::log(format!("{} zap withdraw {} amount {}, returned {}, Callback Failed.", sender_id, token_id, amount.0, returned).as_bytes());
+                self.internal_add_zap_refund(&sender_id, &exchange_id, token_id, returned);
+            }
+            if amount.0 > returned {
+                self.internal_zap_credit_reward(&sender_id, token_id, amount.0 - returned);
+            }
+        }
+        self.data_mut().zapping_exchanges.remove(&exchange_id);
+    }
+}
+
+/// result of the single promise a zap step waits for, None if it failed
+fn internal_zap_promise_result() -> Option<Vec<u8>> {
+    assert_eq!(env::promise_results_count(), 1, "{}", ERR81_CALLBACK_POST_ZAP_INVALID);
+    match env::promise_result(0) {
+        PromiseResult::NotReady => unreachable!(),
+        PromiseResult::Successful(value) => Some(value),
+        PromiseResult::Failed => None,
+    }
+}
+
+/// exchange deposits of this contract, None if they could not be read
+fn internal_zap_deposits() -> Option<HashMap<AccountId, U128>> {
+    internal_zap_promise_result()
+        .and_then(|value| near_sdk::serde_json::from_slice::<HashMap<AccountId, U128>>(&value).ok())
+}
+
+impl Contract {
+    fn internal_zap_credit_reward(&mut self, sender_id: &AccountId, token_id: &AccountId, amount: Balance) {
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
+        let mut farmer = self.get_farmer(sender_id);
+        farmer.get_ref_mut().add_reward(token_id, amount);
+        self.data_mut().farmers.insert(sender_id, &farmer);
+        self.internal_update_farmer_storage(sender_id, storage_snapshot);
+    }
+
+    fn internal_add_zap_refund(&mut self, sender_id: &AccountId, exchange_id: &AccountId, token_id: &AccountId, amount: Balance) {
+        let total_key = (exchange_id.clone(), token_id.clone());
+        let total = self.data().zap_refund_totals.get(&total_key).unwrap_or(0);
+        self.data_mut().zap_refund_totals.insert(&total_key, &(total + amount));
+
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
+        let key = (sender_id.clone(), exchange_id.clone(), token_id.clone());
+        let refund = self.data().zap_refunds.get(&key).unwrap_or(0);
+        self.data_mut().zap_refunds.insert(&key, &(refund + amount));
+        if refund == 0 {
+            let mut refund_ids = self.data().zap_refund_ids.get(sender_id).unwrap_or_default();
+            refund_ids.push(total_key);
+            self.data_mut().zap_refund_ids.insert(sender_id, &refund_ids);
+        }
+        self.internal_update_farmer_storage(sender_id, storage_snapshot);
+    }
+
+    fn internal_take_zap_refund(&mut self, sender_id: &AccountId, exchange_id: &AccountId, token_id: &AccountId) -> Balance {
+        let storage_snapshot = self.internal_farmer_storage_snapshot(sender_id);
+        let amount = self.data_mut().zap_refunds
+            .remove(&(sender_id.clone(), exchange_id.clone(), token_id.clone()))
+            .expect(ERR85_NO_ZAP_REFUND);
+        let total_key = (exchange_id.clone(), token_id.clone());
+        let mut refund_ids = self.data().zap_refund_ids.get(sender_id).unwrap_or_default();
+        refund_ids.retain(|refund_id| *refund_id != total_key);
+        if refund_ids.is_empty() {
+            self.data_mut().zap_refund_ids.remove(sender_id);
+        } else {
+            self.data_mut().zap_refund_ids.insert(sender_id, &refund_ids);
+        }
+        self.internal_update_farmer_storage(sender_id, storage_snapshot);
+
+        let total = self.data().zap_refund_totals.get(&total_key).unwrap_or(0).saturating_sub(amount);
+        if total > 0 {
+            self.data_mut().zap_refund_totals.insert(&total_key, &total);
+        } else {
+            self.data_mut().zap_refund_totals.remove(&total_key);
+        }
+        amount
+    }
+
+    /// deposit of the token in the exchange beside the zap refunds, which belongs to the running zap
+    fn internal_zap_unrefunded_deposit(&self, deposits: &HashMap<AccountId, U128>, exchange_id: &AccountId, token_id: &AccountId) -> Balance {
+        let deposit = deposits.get(token_id).map(|amount| amount.0).unwrap_or(0);
+        let refunds = self.data().zap_refund_totals.get(&(exchange_id.clone(), token_id.clone())).unwrap_or(0);
+        deposit.saturating_sub(refunds)
+    }
+
+    fn internal_zap_lock(&mut self, sender_id: &AccountId, exchange_id: &AccountId, token_ids: Vec<AccountId>) {
+        self.data_mut().zapping_exchanges.insert(exchange_id, &ZapState {
+            sender_id: sender_id.clone(),
+            token_ids,
+            stalled: None,
+        });
+    }
+
+    fn internal_zap_stall(&mut self, exchange_id: &AccountId, stall: ZapStall) {
+        let mut state = self.data().zapping_exchanges.get(exchange_id).expect(ERR86_NO_ZAP_IN_PROGRESS);
+        state.stalled = Some(stall);
+        self.data_mut().zapping_exchanges.insert(exchange_id, &state);
+    }
+
+    fn internal_zap_release(&mut self, seed_id: &SeedId) {
+        let (exchange_id, _) = parse_seed_id(seed_id);
+        self.data_mut().zapping_exchanges.remove(&exchange_id);
+    }
+
+    /// stake the minted shares into the seed of the farmer, or remove them again if fewer than min_shares,
+    /// then withdraw what is left of the pool tokens back to the farmer rewards.
+    fn internal_zap_shares(&mut self, sender_id: &AccountId, args: ZapArgs, amount: U128, shares: U128) {
+        if shares.0 < args.min_shares.0 {
+            env::log(format!("{} zap add liquidity to {}, shares {} below {}, Callback Failed.", sender_id, args.target_seed_id, shares.0, args.min_shares.0).as_bytes());
+            let (exchange_id, pool_id) = parse_seed_id(&args.target_seed_id);
+            ext_ref_exchange::remove_liquidity(
+                pool_id.parse().expect(ERR33_INVALID_SEED_ID),
+                shares,
+                vec![U128(0); args.pool_token_ids.len()],
+                &exchange_id,
+                1,
+                GAS_FOR_ZAP_REMOVE_LIQUIDITY,
+            )
+            .then(ext_self::callback_post_zap_remove_liquidity(
+                sender_id.clone(),
+                args,
+                shares,
+                &env::current_account_id(),
+                0,
+                GAS_FOR_RESOLVE_ZAP_REMOVE_LIQUIDITY,
+            ));
+            return;
+        }
+        self.internal_seed_credit(&args.target_seed_id, sender_id, shares.0);
+        NearEvent::log_zap(ZapData {
+            account_id: sender_id.clone(),
+            seed_id: args.target_seed_id.clone(),
+            reward_token: args.reward_token.clone(),
+            amount: amount.0.to_string(),
+            shares: shares.0.to_string(),
+        });
+        self.internal_zap_sweep(sender_id, &args);
+    }
+
+    /// read the share balance of this contract to find the shares minted by add_liquidity.
+    fn internal_zap_read_shares(&mut self, sender_id: &AccountId, args: ZapArgs, amount: U128, shares_before: U128) {
+        let (exchange_id, pool_id) = parse_seed_id(&args.target_seed_id);
+        ext_ref_exchange::mft_balance_of(
+            wrap_mft_token_id(&pool_id),
+            env::current_account_id(),
+            &exchange_id,
+            0,
+            GAS_FOR_ZAP_SHARE_BALANCE,
+        )
+        .then(ext_self::callback_post_zap_shares_after(
+            sender_id.clone(),
+            args,
+            amount,
+            shares_before,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_ZAP_SHARES,
+        ));
+    }
+
+    /// read the exchange deposits of this contract to withdraw what the zap left there.
+    fn internal_zap_sweep(&mut self, sender_id: &AccountId, args: &ZapArgs) {
+        let (exchange_id, _) = parse_seed_id(&args.target_seed_id);
+        self.internal_zap_sweep_tokens(sender_id, &exchange_id, args.pool_token_ids.clone());
+    }
+
+    pub(crate) fn internal_zap_sweep_tokens(&mut self, sender_id: &AccountId, exchange_id: &AccountId, token_ids: Vec<AccountId>) {
+        ext_ref_exchange::get_deposits(env::current_account_id(), exchange_id, 0, GAS_FOR_ZAP_GET_DEPOSITS)
+            .then(ext_self::callback_post_zap_sweep(
+                sender_id.clone(),
+                exchange_id.clone(),
+                token_ids,
+                &env::current_account_id(),
+                0,
+                GAS_FOR_RESOLVE_ZAP_SWEEP,
+            ));
+    }
+
+    /// withdraw tokens from the exchange deposits of this contract,
+    /// credited to the farmer once confirmed in callback_post_zap_confirm, which ends the zap.
+    fn internal_zap_withdraw(&mut self, sender_id: &AccountId, exchange_id: &AccountId, tokens: Vec<(AccountId, Balance)>) {
+        let tokens: Vec<(AccountId, Balance)> = tokens.into_iter().filter(|(_, amount)| *amount > 0).collect();
+        let mut withdraws = tokens.iter().map(|(token_id, amount)| {
+            ext_ref_exchange::withdraw(token_id.clone(), (*amount).into(), None, exchange_id, 1, GAS_FOR_ZAP_WITHDRAW)
+        });
+        if let Some(first) = withdraws.next() {
+            withdraws.fold(first, |joint, withdraw| joint.and(withdraw))
+                .then(ext_self::callback_post_zap_withdraw(
+                    sender_id.clone(),
+                    exchange_id.clone(),
+                    tokens.iter().map(|(token_id, _)| token_id.clone()).collect(),
+                    tokens.iter().map(|(_, amount)| (*amount).into()).collect(),
+                    &env::current_account_id(),
+                    0,
+                    GAS_FOR_RESOLVE_ZAP_WITHDRAW,
+                ));
+        } else {
+            self.data_mut().zapping_exchanges.remove(exchange_id);
+        }
+    }
+
+    /// read the exchange deposits of this contract to confirm the withdrawn tokens left them.
+    fn internal_zap_confirm(&mut self, sender_id: &AccountId, exchange_id: &AccountId, token_ids: Vec<AccountId>, amounts: Vec<U128>) {
+        ext_ref_exchange::get_deposits(env::current_account_id(), exchange_id, 0, GAS_FOR_ZAP_GET_DEPOSITS)
+            .then(ext_self::callback_post_zap_confirm(
+                sender_id.clone(),
+                exchange_id.clone(),
+                token_ids,
+                amounts,
+                &env::current_account_id(),
+                0,
+                GAS_FOR_RESOLVE_ZAP_CONFIRM,
+            ));
+    }
+}